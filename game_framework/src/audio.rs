@@ -0,0 +1,600 @@
+//! Audio module - sounds, software mixing and procedural tones/tunes.
+//!
+//! All the mixing is done in software, by [`Mixer`], so an audio backend only needs
+//! a way to periodically pull the mixed samples (see [`Mixer::mix`]).
+//! Client code only talks to the [`AudioBackend`] trait, so it does not care if the
+//! sounds end up on the speakers (SDL) or just get recorded ([`NullAudio`]).
+
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+/// The sample rate used when none is specified.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Identifies a sound, loaded into an audio backend.
+pub type SoundId = usize;
+
+/// Shared handle to an audio backend, passed to the clients of the game loop.
+pub type AudioHandle = Rc<RefCell<dyn AudioBackend>>;
+
+/// The interface for playing sounds. Volumes are in the 0.0 .. 1.0 range,
+/// while the pan goes from -1.0 (full left) to 1.0 (full right).
+pub trait AudioBackend {
+    /// Load a sound, so it can be played later.
+    fn load_sound(&mut self, sound: Sound) -> SoundId;
+
+    /// Play a "one-shot" sound effect (mixed with the others which are already playing).
+    fn play(&mut self, id: SoundId, volume: f32, pan: f32);
+
+    /// Start looping a sound as background music (replacing the previous music, if any).
+    fn play_music(&mut self, id: SoundId, volume: f32);
+
+    /// Stop the background music.
+    fn stop_music(&mut self);
+
+    /// Set the volume applied over everything being played.
+    fn set_master_volume(&mut self, volume: f32);
+}
+
+//-------------------------------------------------------
+
+/// A mono sound, with samples in the -1.0 .. 1.0 range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sound {
+    samples: Vec<f32>,
+    sample_rate: u32,
+}
+
+impl Sound {
+    pub fn from_samples(samples: Vec<f32>, sample_rate: u32) -> Self {
+        assert!(sample_rate > 0);
+        Sound { samples, sample_rate }
+    }
+
+    pub fn from_wav_file(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_wav_bytes(&bytes)
+    }
+
+    /// Decode a WAV file. Supports 8/16 bit PCM and 32 bit float samples.
+    /// Multi-channel sounds are mixed down to mono.
+    pub fn from_wav_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err("not a WAV file".to_string());
+        }
+
+        // walk the chunks, looking for "fmt " and "data"
+        let mut format: Option<(u16, u16, u32, u16)> = None;
+        let mut data: Option<&[u8]> = None;
+        let mut pos = 12;
+        while pos + 8 <= bytes.len() {
+            let id = &bytes[pos..pos + 4];
+            let len = read_u32(bytes, pos + 4) as usize;
+            let start = pos + 8;
+            let end = std::cmp::min(start + len, bytes.len());
+            match id {
+                b"fmt " if end - start >= 16 => {
+                    format = Some((
+                        read_u16(bytes, start),
+                        read_u16(bytes, start + 2),
+                        read_u32(bytes, start + 4),
+                        read_u16(bytes, start + 14),
+                    ));
+                }
+                b"data" => data = Some(&bytes[start..end]),
+                _ => {}
+            }
+            // chunks are padded to an even length
+            pos = start + len + (len & 1);
+        }
+
+        let (audio_format, channels, sample_rate, bits) = format.ok_or("missing WAV format chunk")?;
+        let data = data.ok_or("missing WAV data chunk")?;
+        if channels == 0 || sample_rate == 0 {
+            return Err("invalid WAV format".to_string());
+        }
+        let sample_size = match (audio_format, bits) {
+            (1, 8) => 1,
+            (1, 16) => 2,
+            (3, 32) => 4,
+            _ => return Err(format!("unsupported WAV format: {audio_format} / {bits} bits")),
+        };
+
+        let frame_size = sample_size * (channels as usize);
+        let samples = data
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                let sum: f32 = frame
+                    .chunks_exact(sample_size)
+                    .map(|s| match sample_size {
+                        1 => (s[0] as f32 - 128.0) / 128.0,
+                        2 => i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0,
+                        _ => f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+                    })
+                    .sum();
+                sum / (channels as f32)
+            })
+            .collect();
+
+        Ok(Sound { samples, sample_rate })
+    }
+
+    /// Encode the sound as a 16 bit mono PCM WAV file.
+    pub fn to_wav_bytes(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16_u32.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1_u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(self.sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2_u16.to_le_bytes());
+        bytes.extend_from_slice(&16_u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for s in self.samples.iter() {
+            let v = (s.clamp(-1.0, 1.0) * 32767.0) as i16;
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[inline]
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Duration, in seconds.
+    #[inline]
+    pub fn duration(&self) -> f64 {
+        (self.samples.len() as f64) / (self.sample_rate as f64)
+    }
+
+    /// Convert the sound to another sample rate (using linear interpolation).
+    pub fn resampled(&self, sample_rate: u32) -> Sound {
+        assert!(sample_rate > 0);
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return Sound {
+                samples: self.samples.clone(),
+                sample_rate,
+            };
+        }
+        let ratio = (self.sample_rate as f64) / (sample_rate as f64);
+        let new_len = ((self.samples.len() as f64) / ratio) as usize;
+        let last = self.samples.len() - 1;
+        let samples = (0..new_len)
+            .map(|i| {
+                let src = (i as f64) * ratio;
+                let idx = src as usize;
+                let frac = (src - idx as f64) as f32;
+                let a = self.samples[idx.min(last)];
+                let b = self.samples[(idx + 1).min(last)];
+                a + (b - a) * frac
+            })
+            .collect();
+        Sound { samples, sample_rate }
+    }
+}
+
+//-------------------------------------------------------
+//  Procedural sounds
+
+/// Basic waveforms for generating tones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+    Noise,
+}
+
+/// Generate a tone with the given frequency (Hz) and duration (seconds).
+/// A short fade in/out is applied, to avoid clicks.
+pub fn tone(waveform: Waveform, freq: f32, duration: f64, volume: f32, sample_rate: u32) -> Sound {
+    let mut samples = vec![0.0; (duration * sample_rate as f64) as usize];
+    render_tone(&mut samples, waveform, freq, volume, sample_rate);
+    Sound::from_samples(samples, sample_rate)
+}
+
+/// Generate a simple tune, from a list of space separated notes.
+/// Each note looks like `C4:1` or `F#5:0.5` (note + octave, then the length in beats),
+/// while `R:1` is a rest (silence) of one beat.
+pub fn chiptune(waveform: Waveform, bpm: u32, notes: &str, volume: f32, sample_rate: u32) -> Result<Sound, String> {
+    assert!(bpm > 0);
+    let beat_len = 60.0 / (bpm as f64);
+    let mut samples = vec![];
+    for token in notes.split_whitespace() {
+        let (note, beats) = token.split_once(':').unwrap_or((token, "1"));
+        let beats: f64 = beats.parse().map_err(|_| format!("invalid note length: {token}"))?;
+        let len = (beats * beat_len * sample_rate as f64) as usize;
+        let start = samples.len();
+        samples.resize(start + len, 0.0);
+        if note != "R" {
+            let freq = note_frequency(note).ok_or_else(|| format!("invalid note: {token}"))?;
+            render_tone(&mut samples[start..], waveform, freq, volume, sample_rate);
+        }
+    }
+    Ok(Sound::from_samples(samples, sample_rate))
+}
+
+/// Frequency of a note such as `A4` (440 Hz), `C#3` or `Bb5`.
+pub fn note_frequency(note: &str) -> Option<f32> {
+    let mut chars = note.chars().peekable();
+    let semitone: i32 = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let accidental = match chars.peek() {
+        Some('#') => 1,
+        Some('b') => -1,
+        _ => 0,
+    };
+    if accidental != 0 {
+        chars.next();
+    }
+    let octave: i32 = chars.collect::<String>().parse().ok()?;
+    let midi = (octave + 1) * 12 + semitone + accidental;
+    Some(440.0 * 2.0_f32.powf((midi - 69) as f32 / 12.0))
+}
+
+fn render_tone(out: &mut [f32], waveform: Waveform, freq: f32, volume: f32, sample_rate: u32) {
+    let fade_len = std::cmp::min(out.len() / 4, (sample_rate / 200) as usize).max(1);
+    let len = out.len();
+    let mut noise: u32 = 0x1234_5678;
+    let mut noise_value = 0.0;
+    let mut last_period = u32::MAX;
+    for (i, s) in out.iter_mut().enumerate() {
+        let t = (i as f32) * freq / (sample_rate as f32);
+        let phase = t.fract();
+        let v = match waveform {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Noise => {
+                // new random value on each period (xorshift, so it stays deterministic)
+                if t as u32 != last_period {
+                    last_period = t as u32;
+                    noise ^= noise << 13;
+                    noise ^= noise >> 17;
+                    noise ^= noise << 5;
+                    noise_value = (noise as f32) / (u32::MAX as f32) * 2.0 - 1.0;
+                }
+                noise_value
+            }
+        };
+        let envelope = if i < fade_len {
+            (i as f32) / (fade_len as f32)
+        } else if len - i < fade_len {
+            ((len - i) as f32) / (fade_len as f32)
+        } else {
+            1.0
+        };
+        *s = v * volume * envelope;
+    }
+}
+
+//-------------------------------------------------------
+//  Mixer
+
+struct Voice {
+    sound: SoundId,
+    pos: usize,
+    left: f32,
+    right: f32,
+}
+
+impl Voice {
+    fn new(sound: SoundId, volume: f32, pan: f32) -> Self {
+        // constant power panning
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+        let volume = volume.clamp(0.0, 1.0);
+        Voice {
+            sound,
+            pos: 0,
+            left: volume * angle.cos(),
+            right: volume * angle.sin(),
+        }
+    }
+}
+
+/// Software mixer: plays any number of one-shot sounds, plus a looping music track.
+/// The output is interleaved stereo (left, right, left, right ...).
+pub struct Mixer {
+    sample_rate: u32,
+    sounds: Vec<Sound>,
+    voices: Vec<Voice>,
+    music: Option<Voice>,
+    master_volume: f32,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        assert!(sample_rate > 0);
+        Mixer {
+            sample_rate,
+            sounds: vec![],
+            voices: vec![],
+            music: None,
+            master_volume: 1.0,
+        }
+    }
+
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Number of one-shot sounds still playing.
+    #[inline]
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    #[inline]
+    pub fn is_music_playing(&self) -> bool {
+        self.music.is_some()
+    }
+
+    /// Stop all the sounds, including the music.
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+        self.music = None;
+    }
+
+    /// Mix all the playing sounds into `out` (interleaved stereo).
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+
+        // one-shot sounds are dropped when they finish
+        let sounds = &self.sounds;
+        self.voices.retain_mut(|voice| {
+            let samples = &sounds[voice.sound].samples;
+            for frame in out.chunks_exact_mut(2) {
+                if voice.pos >= samples.len() {
+                    return false;
+                }
+                let s = samples[voice.pos];
+                frame[0] += s * voice.left;
+                frame[1] += s * voice.right;
+                voice.pos += 1;
+            }
+            voice.pos < samples.len()
+        });
+
+        // the music loops forever
+        if let Some(music) = self.music.as_mut() {
+            let samples = &sounds[music.sound].samples;
+            if !samples.is_empty() {
+                for frame in out.chunks_exact_mut(2) {
+                    if music.pos >= samples.len() {
+                        music.pos = 0;
+                    }
+                    let s = samples[music.pos];
+                    frame[0] += s * music.left;
+                    frame[1] += s * music.right;
+                    music.pos += 1;
+                }
+            }
+        }
+
+        for s in out.iter_mut() {
+            *s = (*s * self.master_volume).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+impl AudioBackend for Mixer {
+    fn load_sound(&mut self, sound: Sound) -> SoundId {
+        self.sounds.push(sound.resampled(self.sample_rate));
+        self.sounds.len() - 1
+    }
+
+    fn play(&mut self, id: SoundId, volume: f32, pan: f32) {
+        if id < self.sounds.len() {
+            self.voices.push(Voice::new(id, volume, pan));
+        }
+    }
+
+    fn play_music(&mut self, id: SoundId, volume: f32) {
+        if id < self.sounds.len() {
+            self.music = Some(Voice::new(id, volume, 0.0));
+        }
+    }
+
+    fn stop_music(&mut self) {
+        self.music = None;
+    }
+
+    fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.clamp(0.0, 1.0);
+    }
+}
+
+//-------------------------------------------------------
+//  Null audio - for headless runs and tests
+
+/// Everything that was asked from a [`NullAudio`] backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioEvent {
+    Load { id: SoundId, samples: usize },
+    Play { id: SoundId, volume: f32, pan: f32 },
+    PlayMusic { id: SoundId, volume: f32 },
+    StopMusic,
+    MasterVolume(f32),
+}
+
+/// Audio backend which plays nothing, but records what was played.
+/// The recorded events can be inspected via the (shared) log.
+pub struct NullAudio {
+    sound_count: usize,
+    log: Rc<RefCell<Vec<AudioEvent>>>,
+}
+
+impl NullAudio {
+    pub fn new() -> Self {
+        NullAudio {
+            sound_count: 0,
+            log: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    /// Returns the log of the audio events (which stays valid after the backend is moved).
+    pub fn log(&self) -> Rc<RefCell<Vec<AudioEvent>>> {
+        self.log.clone()
+    }
+}
+
+impl Default for NullAudio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for NullAudio {
+    fn load_sound(&mut self, sound: Sound) -> SoundId {
+        let id = self.sound_count;
+        self.sound_count += 1;
+        self.log.borrow_mut().push(AudioEvent::Load {
+            id,
+            samples: sound.samples.len(),
+        });
+        id
+    }
+
+    fn play(&mut self, id: SoundId, volume: f32, pan: f32) {
+        self.log.borrow_mut().push(AudioEvent::Play { id, volume, pan });
+    }
+
+    fn play_music(&mut self, id: SoundId, volume: f32) {
+        self.log.borrow_mut().push(AudioEvent::PlayMusic { id, volume });
+    }
+
+    fn stop_music(&mut self) {
+        self.log.borrow_mut().push(AudioEvent::StopMusic);
+    }
+
+    fn set_master_volume(&mut self, volume: f32) {
+        self.log.borrow_mut().push(AudioEvent::MasterVolume(volume));
+    }
+}
+
+//-------------------------------------------------------
+//  Internal stuff
+
+#[inline]
+fn read_u16(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+//-------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_roundtrip() {
+        let sound = tone(Waveform::Square, 440.0, 0.1, 0.5, 8000);
+        let decoded = Sound::from_wav_bytes(&sound.to_wav_bytes()).unwrap();
+        assert_eq!(8000, decoded.sample_rate());
+        assert_eq!(sound.samples().len(), decoded.samples().len());
+        for (a, b) in sound.samples().iter().zip(decoded.samples()) {
+            assert!((a - b).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn invalid_wav_is_rejected() {
+        assert!(Sound::from_wav_bytes(b"definitely not a wav file").is_err());
+    }
+
+    #[test]
+    fn notes_and_tunes() {
+        assert!((note_frequency("A4").unwrap() - 440.0).abs() < 0.01);
+        assert!((note_frequency("A5").unwrap() - 880.0).abs() < 0.01);
+        assert!((note_frequency("C#4").unwrap() - note_frequency("Db4").unwrap()).abs() < 0.01);
+        assert!(note_frequency("H2").is_none());
+
+        let tune = chiptune(Waveform::Triangle, 120, "C4:1 R:0.5 G4:0.5", 0.5, 1000).unwrap();
+        assert_eq!(1000, tune.samples().len());
+        assert!(chiptune(Waveform::Triangle, 120, "C4:x", 0.5, 1000).is_err());
+    }
+
+    #[test]
+    fn mixer_pans_and_finishes_one_shots() {
+        let mut mixer = Mixer::new(1000);
+        let id = mixer.load_sound(Sound::from_samples(vec![0.5; 10], 1000));
+        mixer.play(id, 1.0, -1.0);
+        let mut out = [0.0; 8];
+        mixer.mix(&mut out);
+        assert!((out[0] - 0.5).abs() < 0.001);
+        assert!(out[1].abs() < 0.001);
+        assert_eq!(1, mixer.active_voices());
+        let mut out = [0.0; 20];
+        mixer.mix(&mut out);
+        assert_eq!(0, mixer.active_voices());
+        assert_eq!(0.0, out[19]);
+    }
+
+    #[test]
+    fn mixer_loops_music() {
+        let mut mixer = Mixer::new(1000);
+        let id = mixer.load_sound(Sound::from_samples(vec![0.1, 0.2], 1000));
+        mixer.play_music(id, 1.0);
+        let mut out = [0.0; 12];
+        mixer.mix(&mut out);
+        assert!(out[0] > 0.0 && out[0] < out[2] && (out[0] - out[4]).abs() < 0.001);
+        mixer.stop_music();
+        mixer.mix(&mut out);
+        assert!(out.iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn null_audio_records_everything() {
+        let mut audio = NullAudio::new();
+        let log = audio.log();
+        let id = audio.load_sound(tone(Waveform::Sine, 220.0, 0.01, 1.0, 1000));
+        audio.play(id, 0.5, 0.25);
+        audio.stop_music();
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                AudioEvent::Load { id: 0, samples: 10 },
+                AudioEvent::Play {
+                    id: 0,
+                    volume: 0.5,
+                    pan: 0.25
+                },
+                AudioEvent::StopMusic,
+            ]
+        );
+    }
+}
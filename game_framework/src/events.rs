@@ -0,0 +1,184 @@
+//! Input events - our own (simplified) version of the SDL2 events.
+//!
+//! Since these are plain data, they can be recorded, replayed and created in tests,
+//! without needing SDL. The key codes use the same values as SDL2.
+
+/// Key code (same values as the SDL2 key codes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Keycode(pub i32);
+
+// the names mimic the SDL2 `Keycode` enum, so they are not upper case
+#[allow(non_upper_case_globals)]
+impl Keycode {
+    pub const Backspace: Keycode = Keycode(8);
+    pub const Tab: Keycode = Keycode(9);
+    pub const Return: Keycode = Keycode(13);
+    pub const Escape: Keycode = Keycode(27);
+    pub const Space: Keycode = Keycode(32);
+    pub const Comma: Keycode = Keycode(44);
+    pub const Minus: Keycode = Keycode(45);
+    pub const Period: Keycode = Keycode(46);
+    pub const Slash: Keycode = Keycode(47);
+    pub const Num0: Keycode = Keycode(48);
+    pub const Num1: Keycode = Keycode(49);
+    pub const Num2: Keycode = Keycode(50);
+    pub const Num3: Keycode = Keycode(51);
+    pub const Num4: Keycode = Keycode(52);
+    pub const Num5: Keycode = Keycode(53);
+    pub const Num6: Keycode = Keycode(54);
+    pub const Num7: Keycode = Keycode(55);
+    pub const Num8: Keycode = Keycode(56);
+    pub const Num9: Keycode = Keycode(57);
+    pub const Equals: Keycode = Keycode(61);
+    pub const A: Keycode = Keycode(97);
+    pub const B: Keycode = Keycode(98);
+    pub const C: Keycode = Keycode(99);
+    pub const D: Keycode = Keycode(100);
+    pub const E: Keycode = Keycode(101);
+    pub const F: Keycode = Keycode(102);
+    pub const G: Keycode = Keycode(103);
+    pub const H: Keycode = Keycode(104);
+    pub const I: Keycode = Keycode(105);
+    pub const J: Keycode = Keycode(106);
+    pub const K: Keycode = Keycode(107);
+    pub const L: Keycode = Keycode(108);
+    pub const M: Keycode = Keycode(109);
+    pub const N: Keycode = Keycode(110);
+    pub const O: Keycode = Keycode(111);
+    pub const P: Keycode = Keycode(112);
+    pub const Q: Keycode = Keycode(113);
+    pub const R: Keycode = Keycode(114);
+    pub const S: Keycode = Keycode(115);
+    pub const T: Keycode = Keycode(116);
+    pub const U: Keycode = Keycode(117);
+    pub const V: Keycode = Keycode(118);
+    pub const W: Keycode = Keycode(119);
+    pub const X: Keycode = Keycode(120);
+    pub const Y: Keycode = Keycode(121);
+    pub const Z: Keycode = Keycode(122);
+    pub const Delete: Keycode = Keycode(127);
+    pub const F1: Keycode = Keycode(0x4000_003A);
+    pub const F2: Keycode = Keycode(0x4000_003B);
+    pub const F3: Keycode = Keycode(0x4000_003C);
+    pub const F4: Keycode = Keycode(0x4000_003D);
+    pub const F5: Keycode = Keycode(0x4000_003E);
+    pub const F6: Keycode = Keycode(0x4000_003F);
+    pub const F7: Keycode = Keycode(0x4000_0040);
+    pub const F8: Keycode = Keycode(0x4000_0041);
+    pub const F9: Keycode = Keycode(0x4000_0042);
+    pub const F10: Keycode = Keycode(0x4000_0043);
+    pub const F11: Keycode = Keycode(0x4000_0044);
+    pub const F12: Keycode = Keycode(0x4000_0045);
+    pub const Insert: Keycode = Keycode(0x4000_0049);
+    pub const Home: Keycode = Keycode(0x4000_004A);
+    pub const PageUp: Keycode = Keycode(0x4000_004B);
+    pub const End: Keycode = Keycode(0x4000_004D);
+    pub const PageDown: Keycode = Keycode(0x4000_004E);
+    pub const Right: Keycode = Keycode(0x4000_004F);
+    pub const Left: Keycode = Keycode(0x4000_0050);
+    pub const Down: Keycode = Keycode(0x4000_0051);
+    pub const Up: Keycode = Keycode(0x4000_0052);
+    pub const KpEnter: Keycode = Keycode(0x4000_0058);
+    pub const LCtrl: Keycode = Keycode(0x4000_00E0);
+    pub const LShift: Keycode = Keycode(0x4000_00E1);
+    pub const LAlt: Keycode = Keycode(0x4000_00E2);
+    pub const RCtrl: Keycode = Keycode(0x4000_00E4);
+    pub const RShift: Keycode = Keycode(0x4000_00E5);
+    pub const RAlt: Keycode = Keycode(0x4000_00E6);
+}
+
+/// Key modifiers (same bits as the SDL2 key modifiers).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Mod(pub u16);
+
+impl Mod {
+    pub const NOMOD: Mod = Mod(0);
+    pub const LSHIFTMOD: Mod = Mod(0x0001);
+    pub const RSHIFTMOD: Mod = Mod(0x0002);
+    pub const LCTRLMOD: Mod = Mod(0x0040);
+    pub const RCTRLMOD: Mod = Mod(0x0080);
+    pub const LALTMOD: Mod = Mod(0x0100);
+    pub const RALTMOD: Mod = Mod(0x0200);
+
+    #[inline]
+    pub fn contains(&self, other: Mod) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: Mod) -> bool {
+        (self.0 & other.0) != 0
+    }
+}
+
+impl std::ops::BitOr for Mod {
+    type Output = Mod;
+
+    fn bitor(self, rhs: Mod) -> Mod {
+        Mod(self.0 | rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Other,
+}
+
+/// An input event. Mouse coordinates are in screen pixels (i.e. already divided by the pixel size).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Quit,
+    KeyDown {
+        keycode: Option<Keycode>,
+        keymod: Mod,
+        repeat: bool,
+    },
+    KeyUp {
+        keycode: Option<Keycode>,
+        keymod: Mod,
+        repeat: bool,
+    },
+    TextInput {
+        text: String,
+    },
+    MouseMotion {
+        x: i32,
+        y: i32,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        x: i32,
+        y: i32,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        x: i32,
+        y: i32,
+    },
+    MouseWheel {
+        y: i32,
+    },
+}
+
+impl Event {
+    /// Shortcut for a key press, without modifiers.
+    pub fn key_down(key: Keycode) -> Self {
+        Event::KeyDown {
+            keycode: Some(key),
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+
+    /// Shortcut for a key release, without modifiers.
+    pub fn key_up(key: Keycode) -> Self {
+        Event::KeyUp {
+            keycode: Some(key),
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Tiny built-in bitmap font (3x5 pixels per character), for drawing text with a [`Painter`].
+//! It only has upper case letters, digits and the usual punctuation - lower case letters
+//! are drawn as upper case, and everything else as `?`.
+
+use crate::painter::*;
+
+/// Width of a character (including the space after it), in font pixels.
+pub const CHAR_WIDTH: i32 = 4;
+/// Height of a line of text (including the space after it), in font pixels.
+pub const LINE_HEIGHT: i32 = 6;
+
+// one glyph per character, from ' ' (32) to '_' (95), 3 bits per row, top row first
+const GLYPHS: &[u16] = &[
+    0b000_000_000_000_000, // (space)
+    0b010_010_010_000_010, // !
+    0b101_101_000_000_000, // "
+    0b101_111_101_111_101, // #
+    0b011_110_010_011_110, // $
+    0b101_001_010_100_101, // %
+    0b010_101_010_101_011, // &
+    0b010_010_000_000_000, // '
+    0b001_010_010_010_001, // (
+    0b100_010_010_010_100, // )
+    0b000_101_010_101_000, // *
+    0b000_010_111_010_000, // +
+    0b000_000_000_010_100, // ,
+    0b000_000_111_000_000, // -
+    0b000_000_000_000_010, // .
+    0b001_001_010_100_100, // /
+    0b111_101_101_101_111, // 0
+    0b010_110_010_010_111, // 1
+    0b111_001_111_100_111, // 2
+    0b111_001_011_001_111, // 3
+    0b101_101_111_001_001, // 4
+    0b111_100_111_001_111, // 5
+    0b111_100_111_101_111, // 6
+    0b111_001_001_010_010, // 7
+    0b111_101_111_101_111, // 8
+    0b111_101_111_001_111, // 9
+    0b000_010_000_010_000, // :
+    0b000_010_000_010_100, // ;
+    0b001_010_100_010_001, // <
+    0b000_111_000_111_000, // =
+    0b100_010_001_010_100, // >
+    0b111_001_011_000_010, // ?
+    0b010_101_111_100_011, // @
+    0b010_101_111_101_101, // A
+    0b110_101_110_101_110, // B
+    0b011_100_100_100_011, // C
+    0b110_101_101_101_110, // D
+    0b111_100_110_100_111, // E
+    0b111_100_110_100_100, // F
+    0b011_100_101_101_011, // G
+    0b101_101_111_101_101, // H
+    0b111_010_010_010_111, // I
+    0b001_001_001_101_010, // J
+    0b101_101_110_101_101, // K
+    0b100_100_100_100_111, // L
+    0b101_111_111_101_101, // M
+    0b110_101_101_101_101, // N
+    0b010_101_101_101_010, // O
+    0b110_101_110_100_100, // P
+    0b010_101_101_110_011, // Q
+    0b110_101_110_101_101, // R
+    0b011_100_010_001_110, // S
+    0b111_010_010_010_010, // T
+    0b101_101_101_101_111, // U
+    0b101_101_101_101_010, // V
+    0b101_101_111_111_101, // W
+    0b101_101_010_101_101, // X
+    0b101_101_010_010_010, // Y
+    0b111_001_010_100_111, // Z
+    0b011_010_010_010_011, // [
+    0b100_100_010_001_001, // \
+    0b110_010_010_010_110, // ]
+    0b010_101_000_000_000, // ^
+    0b000_000_000_000_111, // _
+];
+
+/// The width of the text, in screen pixels (for single-line text).
+pub fn text_width(text: &str, scale: i32) -> i32 {
+    let len = text.chars().count() as i32;
+    if len == 0 {
+        0
+    } else {
+        (len * CHAR_WIDTH - 1) * scale
+    }
+}
+
+/// Draw a single character, with its top left corner at (x, y).
+pub fn draw_char<P: Painter + ?Sized>(painter: &mut P, x: i32, y: i32, ch: char, scale: i32, color: RGB) {
+    let glyph = GLYPHS[glyph_index(ch)];
+    for row in 0..5 {
+        for col in 0..3 {
+            let bit = 14 - (row * 3 + col);
+            if (glyph >> bit) & 1 != 0 {
+                painter.fill_rect(x + col * scale, y + row * scale, scale, scale, color);
+            }
+        }
+    }
+}
+
+/// Draw some text, with its top left corner at (x, y). It can contain new lines.
+pub fn draw_text<P: Painter + ?Sized>(painter: &mut P, x: i32, y: i32, text: &str, scale: i32, color: RGB) {
+    let mut cx = x;
+    let mut cy = y;
+    for ch in text.chars() {
+        if ch == '\n' {
+            cx = x;
+            cy += LINE_HEIGHT * scale;
+        } else {
+            draw_char(painter, cx, cy, ch, scale, color);
+            cx += CHAR_WIDTH * scale;
+        }
+    }
+}
+
+/// Draw some single-line text, horizontally centered on `center_x`.
+pub fn draw_text_centered<P: Painter + ?Sized>(
+    painter: &mut P,
+    center_x: i32,
+    y: i32,
+    text: &str,
+    scale: i32,
+    color: RGB,
+) {
+    let x = center_x - text_width(text, scale) / 2;
+    draw_text(painter, x, y, text, scale, color);
+}
+
+#[inline]
+fn glyph_index(ch: char) -> usize {
+    let ch = ch.to_ascii_uppercase();
+    if (' '..='_').contains(&ch) {
+        (ch as usize) - 32
+    } else {
+        ('?' as usize) - 32
+    }
+}
@@ -0,0 +1,22 @@
+//! The main loop abstraction - independent of SDL, so logic crates (and tests) can use it without linking SDL.
+
+use crate::audio::*;
+use crate::events::*;
+use crate::painter::*;
+
+/// Trait to be implemented by clients of `run_sdl_loop`.
+/// Its methods will be called periodically, during the main game loop.
+pub trait GraphicsLoop {
+    /// Handle/capture events (e.g. keys, mouse etc).
+    fn handle_event(&mut self, event: &Event) -> bool;
+
+    /// Update the internal state.
+    fn update_state(&mut self, elapsed_time: f64) -> bool;
+
+    /// Paint the world, based on the updated internal state.
+    fn paint(&self, painter: &mut dyn Painter);
+
+    /// Receive the audio backend, once, before the main loop starts.
+    /// By default, it is ignored (so there is no sound).
+    fn attach_audio(&mut self, _audio: AudioHandle) {}
+}
@@ -0,0 +1,79 @@
+//! Headless runner - drives a [`GraphicsLoop`] without any window, e.g. for tests and replays.
+
+use crate::graphics_loop::GraphicsLoop;
+use crate::painter::*;
+use crate::replay::ReplayFrame;
+
+/// Feed the given frames to a graphics loop (no painting is done).
+/// Returns the number of frames which were fully played - it stops early if the client code wants to quit.
+pub fn run_headless(gfx_loop: &mut dyn GraphicsLoop, frames: &[ReplayFrame]) -> usize {
+    for (idx, frame) in frames.iter().enumerate() {
+        for event in frame.events.iter() {
+            if !gfx_loop.handle_event(event) {
+                return idx;
+            }
+        }
+        if !gfx_loop.update_state(frame.elapsed_time) {
+            return idx;
+        }
+    }
+    frames.len()
+}
+
+/// In-memory painter, useful for checking what was painted.
+pub struct FrameBuffer {
+    width: i32,
+    height: i32,
+    pixels: Vec<RGB>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        assert!(width > 0);
+        assert!(height > 0);
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![RGB::from(0, 0, 0); (width * height) as usize],
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Returns the color of a pixel, or `None` if it's outside the buffer.
+    pub fn pixel(&self, x: i32, y: i32) -> Option<RGB> {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some(self.pixels[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// A hash of all the pixels (FNV-1a), for quickly comparing frames.
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for p in self.pixels.iter() {
+            for b in [p.r, p.g, p.b] {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        hash
+    }
+}
+
+impl Painter for FrameBuffer {
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.pixels[(y * self.width + x) as usize] = color;
+        }
+    }
+}
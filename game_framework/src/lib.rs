@@ -0,0 +1,66 @@
+//! Shared framework for the games: painting, events, the main loop (via SDL2), audio,
+//! replays, scenes, UI widgets and persistent files.
+//!
+//! SDL2 is only used when the `sdl` feature is enabled (the default), so crates with
+//! game logic (and their tests) can depend on this one without linking SDL2.
+
+mod audio;
+mod events;
+pub mod font;
+mod graphics_loop;
+mod headless;
+mod painter;
+mod replay;
+mod scene;
+#[cfg(feature = "sdl")]
+mod sdl_wrapper;
+pub mod storage;
+mod ui;
+
+pub use audio::*;
+pub use events::*;
+pub use graphics_loop::*;
+pub use headless::*;
+pub use painter::*;
+pub use palette::*;
+pub use replay::*;
+pub use scene::*;
+#[cfg(feature = "sdl")]
+pub use sdl_wrapper::*;
+pub use ui::*;
+
+/// The color palette (hand-aligned).
+#[rustfmt::skip]
+mod palette {
+    use crate::painter::RGB;
+
+    pub const      BLACK: RGB = RGB { r:   0, g:   0, b:   0 };
+    pub const  DARK_GREY: RGB = RGB { r:  64, g:  64, b:  64 };
+    pub const       GREY: RGB = RGB { r: 128, g: 128, b: 128 };
+    pub const LIGHT_GREY: RGB = RGB { r: 192, g: 192, b: 192 };
+    pub const      WHITE: RGB = RGB { r: 255, g: 255, b: 255 };
+
+    pub const     DARK_RED: RGB = RGB { r: 64, g: 0, b: 0 };
+    pub const   DARK_GREEN: RGB = RGB { r: 0, g: 64, b: 0 };
+    pub const    DARK_BLUE: RGB = RGB { r: 0, g: 0, b: 64 };
+    pub const    DARK_CYAN: RGB = RGB { r: 0, g: 64, b: 64 };
+    pub const DARK_MAGENTA: RGB = RGB { r: 64, g: 0, b: 64 };
+    pub const  DARK_YELLOW: RGB = RGB { r: 64, g: 64, b: 0 };
+    pub const   DARK_BROWN: RGB = RGB { r: 64, g: 32, b: 0 };
+
+    pub const     RED: RGB = RGB { r: 160, g: 0, b: 0 };
+    pub const   GREEN: RGB = RGB { r: 0, g: 160, b: 0 };
+    pub const    BLUE: RGB = RGB { r: 0, g: 0, b: 160 };
+    pub const    CYAN: RGB = RGB { r: 0, g: 160, b: 160 };
+    pub const MAGENTA: RGB = RGB { r: 160, g: 0, b: 160 };
+    pub const  YELLOW: RGB = RGB { r: 160, g: 160, b: 0 };
+    pub const   BROWN: RGB = RGB { r: 160, g: 80, b: 0 };
+    pub const  ORANGE: RGB = RGB { r: 255, g: 128, b: 0 };
+
+    pub const     LIGHT_RED: RGB = RGB { r: 255, g: 0, b: 0 };
+    pub const   LIGHT_GREEN: RGB = RGB { r: 0, g: 255, b: 0 };
+    pub const    LIGHT_BLUE: RGB = RGB { r: 0, g: 0, b: 255 };
+    pub const    LIGHT_CYAN: RGB = RGB { r: 0, g: 255, b: 255 };
+    pub const LIGHT_MAGENTA: RGB = RGB { r: 255, g: 0, b: 255 };
+    pub const  LIGHT_YELLOW: RGB = RGB { r: 255, g: 255, b: 0 };
+}
@@ -1,6 +1,8 @@
 //! Painter module
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+use crate::font;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RGB {
     pub r: u8,
     pub g: u8,
@@ -108,7 +110,7 @@ pub trait Painter {
         let r = if rx > ry { rx } else { ry };
         let mut r2 = r * r;
         let mut sub = 1;
-        let imax = ((r as f64) / 1.4142135 + 0.5) as i32;
+        let imax = ((r as f64) / std::f64::consts::SQRT_2 + 0.5) as i32;
 
         for qx in 0..=imax {
             let qy = ((r2 as f64).sqrt() + 0.5) as i32;
@@ -137,7 +139,7 @@ pub trait Painter {
         let r = if rx > ry { rx } else { ry };
         let mut r2 = r * r;
         let mut sub = 1;
-        let imax = ((r as f64) / 1.4142135 + 0.5) as i32;
+        let imax = ((r as f64) / std::f64::consts::SQRT_2 + 0.5) as i32;
 
         for qx in 0..=imax {
             let qy = ((r2 as f64).sqrt() + 0.5) as i32;
@@ -155,4 +157,9 @@ pub trait Painter {
             self.draw_horiz_line(x - px2, x + px2, y - py2, color);
         }
     }
+
+    /// Draw some text, using the built-in font (see the [`font`] module).
+    fn draw_text(&mut self, x: i32, y: i32, text: &str, scale: i32, color: RGB) {
+        font::draw_text(self, x, y, text, scale, color);
+    }
 }
@@ -0,0 +1,432 @@
+//! Input recording and deterministic replay.
+//!
+//! A replay holds, for each frame, the events delivered to [`GraphicsLoop::handle_event`]
+//! and the elapsed time passed to [`GraphicsLoop::update_state`]. If the client code is
+//! deterministic (i.e. all its randomness comes from [`Replay::seed`]), feeding the same
+//! frames again reproduces exactly the same game.
+//!
+//! Replay files are plain text: a header, then one line per frame:
+//! ```text
+//! replay 1
+//! seed 42
+//! f 0.016 kd:119:0:0
+//! f 0.017 ku:119:0:0
+//! ```
+
+use crate::audio::AudioHandle;
+use crate::events::*;
+use crate::graphics_loop::GraphicsLoop;
+use crate::painter::Painter;
+
+const REPLAY_VERSION: u32 = 1;
+
+/// The data recorded for one frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    pub events: Vec<Event>,
+    pub elapsed_time: f64,
+}
+
+/// A recorded game session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay { seed, frames: vec![] }
+    }
+
+    /// Total duration, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|f| f.elapsed_time).sum()
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("replay {REPLAY_VERSION}\nseed {}\n", self.seed);
+        for frame in self.frames.iter() {
+            // (the default float formatting is lossless, which is important for determinism)
+            text.push_str(&format!("f {}", frame.elapsed_time));
+            for event in frame.events.iter() {
+                text.push(' ');
+                text.push_str(&encode_event(event));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, line)) if line.trim() == format!("replay {REPLAY_VERSION}") => {}
+            _ => return Err("not a replay file (or unsupported version)".to_string()),
+        }
+
+        let mut replay = Replay::new(0);
+        for (idx, line) in lines {
+            let err = |msg: &str| format!("replay line {}: {msg}", idx + 1);
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("seed") => {
+                    let seed = tokens.next().and_then(|s| s.parse().ok());
+                    replay.seed = seed.ok_or_else(|| err("invalid seed"))?;
+                }
+                Some("f") => {
+                    let elapsed_time = tokens.next().and_then(|s| s.parse().ok());
+                    let elapsed_time = elapsed_time.ok_or_else(|| err("invalid elapsed time"))?;
+                    let events = tokens.map(decode_event).collect::<Option<Vec<_>>>();
+                    let events = events.ok_or_else(|| err("invalid event"))?;
+                    replay.frames.push(ReplayFrame { events, elapsed_time });
+                }
+                _ => return Err(err("unknown record")),
+            }
+        }
+        Ok(replay)
+    }
+}
+
+//-------------------------------------------------------
+
+/// What to do with replays, as requested on the command line:
+/// `--record <file>`, `--replay <file>` or `--replay <file> --headless`.
+pub enum ReplayMode {
+    Off,
+    Record(String),
+    Play(Replay),
+    PlayHeadless(Replay),
+}
+
+impl ReplayMode {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let value_of = |name: &str| {
+            let pos = args.iter().position(|a| a == name)?;
+            args.get(pos + 1).cloned()
+        };
+        let headless = args.iter().any(|a| a == "--headless");
+        if let Some(path) = value_of("--replay") {
+            let replay = Replay::load(&path)?;
+            Ok(if headless {
+                ReplayMode::PlayHeadless(replay)
+            } else {
+                ReplayMode::Play(replay)
+            })
+        } else if let Some(path) = value_of("--record") {
+            Ok(ReplayMode::Record(path))
+        } else {
+            Ok(ReplayMode::Off)
+        }
+    }
+
+    /// The random seed to use: the replay's seed, when playing a replay.
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            ReplayMode::Play(replay) | ReplayMode::PlayHeadless(replay) => Some(replay.seed),
+            _ => None,
+        }
+    }
+}
+
+//-------------------------------------------------------
+
+/// Wraps a [`GraphicsLoop`], recording everything it receives.
+pub struct ReplayRecorder<'a> {
+    inner: &'a mut dyn GraphicsLoop,
+    replay: Replay,
+    pending: Vec<Event>,
+}
+
+impl<'a> ReplayRecorder<'a> {
+    pub fn new(inner: &'a mut dyn GraphicsLoop, seed: u64) -> Self {
+        ReplayRecorder {
+            inner,
+            replay: Replay::new(seed),
+            pending: vec![],
+        }
+    }
+
+    /// Stop recording and return the replay.
+    pub fn finish(mut self) -> Replay {
+        // events received after the last update (e.g. the one which ended the game) are kept too
+        if !self.pending.is_empty() {
+            self.replay.frames.push(ReplayFrame {
+                events: std::mem::take(&mut self.pending),
+                elapsed_time: 0.0,
+            });
+        }
+        self.replay
+    }
+}
+
+impl<'a> GraphicsLoop for ReplayRecorder<'a> {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        self.pending.push(event.clone());
+        self.inner.handle_event(event)
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.replay.frames.push(ReplayFrame {
+            events: std::mem::take(&mut self.pending),
+            elapsed_time,
+        });
+        self.inner.update_state(elapsed_time)
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.inner.paint(painter);
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        self.inner.attach_audio(audio);
+    }
+}
+
+/// Wraps a [`GraphicsLoop`], feeding it the frames of a replay instead of the live input.
+/// It can be run via `run_sdl_loop`, to watch a replay. It stops after the last frame.
+pub struct ReplayPlayer<'a> {
+    inner: &'a mut dyn GraphicsLoop,
+    replay: &'a Replay,
+    next_frame: usize,
+}
+
+impl<'a> ReplayPlayer<'a> {
+    pub fn new(inner: &'a mut dyn GraphicsLoop, replay: &'a Replay) -> Self {
+        ReplayPlayer {
+            inner,
+            replay,
+            next_frame: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.replay.frames.len()
+    }
+
+    /// Play the next frame. Returns false if the replay or the client code finished.
+    pub fn play_frame(&mut self) -> bool {
+        let Some(frame) = self.replay.frames.get(self.next_frame) else {
+            return false;
+        };
+        self.next_frame += 1;
+        for event in frame.events.iter() {
+            if !self.inner.handle_event(event) {
+                return false;
+            }
+        }
+        self.inner.update_state(frame.elapsed_time)
+    }
+}
+
+impl<'a> GraphicsLoop for ReplayPlayer<'a> {
+    fn handle_event(&mut self, _event: &Event) -> bool {
+        // live input is ignored during playback
+        true
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> bool {
+        self.play_frame()
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.inner.paint(painter);
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        self.inner.attach_audio(audio);
+    }
+}
+
+//-------------------------------------------------------
+//  Internal stuff
+
+fn encode_event(event: &Event) -> String {
+    let key = |keycode: &Option<Keycode>| keycode.map(|k| k.0).unwrap_or(0);
+    let button = |button: &MouseButton| match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::Other => 0,
+    };
+    match event {
+        Event::Quit => "q".to_string(),
+        Event::KeyDown {
+            keycode,
+            keymod,
+            repeat,
+        } => format!("kd:{}:{}:{}", key(keycode), keymod.0, *repeat as u8),
+        Event::KeyUp {
+            keycode,
+            keymod,
+            repeat,
+        } => format!("ku:{}:{}:{}", key(keycode), keymod.0, *repeat as u8),
+        // the text is hex-encoded, so it cannot contain spaces or colons
+        Event::TextInput { text } => {
+            let hex: String = text.bytes().map(|b| format!("{b:02x}")).collect();
+            format!("ti:{hex}")
+        }
+        Event::MouseMotion { x, y } => format!("mm:{x}:{y}"),
+        Event::MouseButtonDown { button: b, x, y } => format!("md:{}:{x}:{y}", button(b)),
+        Event::MouseButtonUp { button: b, x, y } => format!("mu:{}:{x}:{y}", button(b)),
+        Event::MouseWheel { y } => format!("mw:{y}"),
+    }
+}
+
+fn decode_event(token: &str) -> Option<Event> {
+    let parts: Vec<&str> = token.split(':').collect();
+    let num = |idx: usize| parts.get(idx).and_then(|s| s.parse::<i32>().ok());
+    let keycode = |idx: usize| num(idx).map(|k| if k == 0 { None } else { Some(Keycode(k)) });
+    let button = |idx: usize| {
+        num(idx).map(|b| match b {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            _ => MouseButton::Other,
+        })
+    };
+    let event = match parts[0] {
+        "q" => Event::Quit,
+        "kd" => Event::KeyDown {
+            keycode: keycode(1)?,
+            keymod: Mod(num(2)? as u16),
+            repeat: num(3)? != 0,
+        },
+        "ku" => Event::KeyUp {
+            keycode: keycode(1)?,
+            keymod: Mod(num(2)? as u16),
+            repeat: num(3)? != 0,
+        },
+        "ti" => {
+            let hex = parts.get(1)?;
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Event::TextInput {
+                text: String::from_utf8(bytes).ok()?,
+            }
+        }
+        "mm" => Event::MouseMotion { x: num(1)?, y: num(2)? },
+        "md" => Event::MouseButtonDown {
+            button: button(1)?,
+            x: num(2)?,
+            y: num(3)?,
+        },
+        "mu" => Event::MouseButtonUp {
+            button: button(1)?,
+            x: num(2)?,
+            y: num(3)?,
+        },
+        "mw" => Event::MouseWheel { y: num(1)? },
+        _ => return None,
+    };
+    Some(event)
+}
+
+//-------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::run_headless;
+
+    /// Minimal deterministic client: counts keys and accumulates time.
+    #[derive(Default)]
+    struct Counter {
+        keys: u32,
+        time: f64,
+    }
+
+    impl GraphicsLoop for Counter {
+        fn handle_event(&mut self, event: &Event) -> bool {
+            if let Event::KeyDown { keycode: Some(key), .. } = event {
+                self.keys += 1;
+                return *key != Keycode::Q;
+            }
+            true
+        }
+
+        fn update_state(&mut self, elapsed_time: f64) -> bool {
+            self.time += elapsed_time;
+            true
+        }
+
+        fn paint(&self, _painter: &mut dyn Painter) {}
+    }
+
+    fn sample_replay() -> Replay {
+        let mut replay = Replay::new(1234);
+        replay.frames.push(ReplayFrame {
+            events: vec![
+                Event::key_down(Keycode::Left),
+                Event::KeyUp {
+                    keycode: Some(Keycode::A),
+                    keymod: Mod::LSHIFTMOD | Mod::LALTMOD,
+                    repeat: true,
+                },
+                Event::TextInput {
+                    text: "hi: ünïcode".to_string(),
+                },
+            ],
+            elapsed_time: 1.0 / 60.0,
+        });
+        replay.frames.push(ReplayFrame {
+            events: vec![
+                Event::MouseMotion { x: -3, y: 7 },
+                Event::MouseButtonDown {
+                    button: MouseButton::Right,
+                    x: 1,
+                    y: 2,
+                },
+                Event::MouseWheel { y: -1 },
+                Event::Quit,
+            ],
+            elapsed_time: 0.1 + 0.2,
+        });
+        replay
+    }
+
+    #[test]
+    fn text_roundtrip_is_lossless() {
+        let replay = sample_replay();
+        let decoded = Replay::from_text(&replay.to_text()).unwrap();
+        assert_eq!(replay, decoded);
+    }
+
+    #[test]
+    fn invalid_text_is_rejected() {
+        assert!(Replay::from_text("hello").is_err());
+        assert!(Replay::from_text("replay 1\nf abc").is_err());
+        assert!(Replay::from_text("replay 1\nf 0.1 xx:1").is_err());
+    }
+
+    #[test]
+    fn recorded_session_replays_identically() {
+        let mut live = Counter::default();
+        let mut recorder = ReplayRecorder::new(&mut live, 7);
+        for (i, dt) in [0.016, 0.017, 0.015].iter().enumerate() {
+            recorder.handle_event(&Event::key_down(Keycode(100 + i as i32)));
+            recorder.update_state(*dt);
+        }
+        recorder.handle_event(&Event::key_down(Keycode::Q));
+        let replay = recorder.finish();
+        assert_eq!(4, replay.frames.len());
+
+        let mut replayed = Counter::default();
+        let frames = run_headless(&mut replayed, &replay.frames);
+        assert_eq!(3, frames);
+        assert_eq!(live.keys, replayed.keys);
+        assert_eq!(live.time, replayed.time);
+    }
+}
@@ -0,0 +1,487 @@
+//! Scene stack - for menus, pause screens, gameplay etc.
+//!
+//! The [`SceneStack`] is a [`GraphicsLoop`] which holds a stack of [`Scene`]s.
+//! Only the top scene receives the input and gets updated, but an *overlay* scene
+//! (e.g. a pause menu) is painted over the scene below it.
+//! Scenes change the stack by returning a [`Transition`].
+
+use crate::audio::AudioHandle;
+use crate::events::*;
+use crate::font;
+use crate::graphics_loop::GraphicsLoop;
+use crate::painter::*;
+
+const MENU_TITLE_COLOR: RGB = RGB { r: 255, g: 255, b: 0 };
+const MENU_ITEM_COLOR: RGB = RGB { r: 192, g: 192, b: 192 };
+const MENU_SELECTED_COLOR: RGB = RGB { r: 255, g: 255, b: 255 };
+const MENU_BACKGROUND: RGB = RGB { r: 0, g: 0, b: 64 };
+const DIM_COLOR: RGB = RGB { r: 0, g: 0, b: 0 };
+
+// 4x4 ordered dithering matrix, used for "transparency" effects (the painter has no alpha)
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// What the scene stack should do next.
+pub enum Transition {
+    /// Nothing - keep running the current scene.
+    None,
+    /// Push a new scene on top of the current one.
+    Push(Box<dyn Scene>),
+    /// Remove the current scene (going back to the one below it).
+    Pop,
+    /// Replace the current scene with another one (with a fade out/in).
+    Replace(Box<dyn Scene>),
+    /// Remove all the scenes and start over with the given one (with a fade out/in).
+    Reset(Box<dyn Scene>),
+    /// Exit the game.
+    Quit,
+}
+
+/// A game state (title menu, gameplay, pause overlay etc), managed by a [`SceneStack`].
+pub trait Scene {
+    /// Handle/capture events (e.g. keys, mouse etc).
+    fn handle_event(&mut self, event: &Event) -> Transition;
+
+    /// Update the internal state.
+    fn update_state(&mut self, elapsed_time: f64) -> Transition;
+
+    /// Paint the scene.
+    fn paint(&self, painter: &mut dyn Painter);
+
+    /// If true, the scene below this one is painted first.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// Called when this scene becomes the top scene again (the one above it was popped).
+    fn resume(&mut self) {}
+
+    /// Receive the audio backend (once, when the scene is added to the stack).
+    fn attach_audio(&mut self, _audio: AudioHandle) {}
+}
+
+//-------------------------------------------------------
+
+/// Stack of scenes, which can be run by `run_sdl_loop`.
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    scr_width: i32,
+    scr_height: i32,
+    audio: Option<AudioHandle>,
+    fade_duration: f64,
+    fade: Option<Fade>,
+}
+
+/// A fade out, followed by applying the pending transition, then a fade in.
+struct Fade {
+    pending: Option<Transition>,
+    time: f64,
+}
+
+impl SceneStack {
+    pub fn new(scr_width: i32, scr_height: i32, first_scene: Box<dyn Scene>) -> Self {
+        SceneStack {
+            scenes: vec![first_scene],
+            scr_width,
+            scr_height,
+            audio: None,
+            fade_duration: 0.25,
+            fade: None,
+        }
+    }
+
+    /// Set the duration of the fade out (and fade in) for `Replace` and `Reset`. Zero disables fading.
+    pub fn set_fade_duration(&mut self, seconds: f64) {
+        self.fade_duration = seconds.max(0.0);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// Returns false if the game should end.
+    fn apply(&mut self, transition: Transition) -> bool {
+        match transition {
+            Transition::None => {}
+            Transition::Push(scene) => self.push(scene),
+            Transition::Pop => {
+                self.scenes.pop();
+                if let Some(top) = self.scenes.last_mut() {
+                    top.resume();
+                }
+            }
+            Transition::Replace(_) | Transition::Reset(_) if self.fade.is_none() && self.fade_duration > 0.0 => {
+                self.fade = Some(Fade {
+                    pending: Some(transition),
+                    time: 0.0,
+                });
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.push(scene);
+            }
+            Transition::Reset(scene) => {
+                self.scenes.clear();
+                self.push(scene);
+            }
+            Transition::Quit => self.scenes.clear(),
+        }
+        !self.scenes.is_empty()
+    }
+
+    fn push(&mut self, mut scene: Box<dyn Scene>) {
+        if let Some(audio) = &self.audio {
+            scene.attach_audio(audio.clone());
+        }
+        self.scenes.push(scene);
+    }
+
+    /// Returns false if the game should end.
+    fn update_fade(&mut self, elapsed_time: f64) -> bool {
+        let Some(fade) = self.fade.as_mut() else {
+            return true;
+        };
+        fade.time += elapsed_time;
+        if fade.time >= self.fade_duration {
+            // half way: perform the transition (only once)
+            if let Some(transition) = fade.pending.take() {
+                if !self.apply(transition) {
+                    return false;
+                }
+            }
+        }
+        if self.fade.as_ref().is_some_and(|f| f.time >= 2.0 * self.fade_duration) {
+            self.fade = None;
+        }
+        true
+    }
+
+    /// How dark the screen should be, due to fading (0.0 .. 1.0).
+    fn fade_level(&self) -> f64 {
+        match &self.fade {
+            Some(fade) if self.fade_duration > 0.0 => {
+                let t = fade.time / self.fade_duration;
+                (if t <= 1.0 { t } else { 2.0 - t }).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl GraphicsLoop for SceneStack {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if *event == Event::Quit {
+            return false;
+        }
+        // no input during fades
+        if self.fade.is_some() {
+            return true;
+        }
+        match self.scenes.last_mut() {
+            Some(top) => {
+                let transition = top.handle_event(event);
+                self.apply(transition)
+            }
+            None => false,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        if self.fade.is_some() {
+            return self.update_fade(elapsed_time);
+        }
+        match self.scenes.last_mut() {
+            Some(top) => {
+                let transition = top.update_state(elapsed_time);
+                self.apply(transition)
+            }
+            None => false,
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        // start from the top-most scene which is not an overlay
+        let mut first = self.scenes.len().saturating_sub(1);
+        while first > 0 && self.scenes[first].is_overlay() {
+            first -= 1;
+        }
+        for scene in self.scenes.iter().skip(first) {
+            scene.paint(painter);
+        }
+        let level = self.fade_level();
+        if level > 0.0 {
+            dim_screen(painter, self.scr_width, self.scr_height, level);
+        }
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        for scene in self.scenes.iter_mut() {
+            scene.attach_audio(audio.clone());
+        }
+        self.audio = Some(audio);
+    }
+}
+
+/// Darken an area of the screen, by painting black pixels over it (using ordered dithering).
+/// The amount goes from 0.0 (unchanged) to 1.0 (all black).
+pub fn dim_area(painter: &mut dyn Painter, x: i32, y: i32, w: i32, h: i32, amount: f64) {
+    let threshold = (amount.clamp(0.0, 1.0) * 16.0).round() as u8;
+    for yy in y..(y + h) {
+        let row = &BAYER_4X4[(yy & 3) as usize];
+        for xx in x..(x + w) {
+            if row[(xx & 3) as usize] < threshold {
+                painter.draw_pixel(xx, yy, DIM_COLOR);
+            }
+        }
+    }
+}
+
+/// Darken the whole screen - see [`dim_area`].
+pub fn dim_screen(painter: &mut dyn Painter, scr_width: i32, scr_height: i32, amount: f64) {
+    dim_area(painter, 0, 0, scr_width, scr_height, amount);
+}
+
+//-------------------------------------------------------
+
+type MenuAction = Box<dyn FnMut() -> Transition>;
+
+/// A simple menu: a title and a list of items, selected with the Up/Down keys and activated
+/// with Enter/Space. Escape triggers the "cancel" action (which does nothing, by default).
+pub struct MenuScene {
+    title: String,
+    items: Vec<(String, MenuAction)>,
+    cancel: Option<MenuAction>,
+    selected: usize,
+    overlay: bool,
+    scr_width: i32,
+    scr_height: i32,
+}
+
+impl MenuScene {
+    pub fn new(title: &str, scr_width: i32, scr_height: i32) -> Self {
+        MenuScene {
+            title: title.to_string(),
+            items: vec![],
+            cancel: None,
+            selected: 0,
+            overlay: false,
+            scr_width,
+            scr_height,
+        }
+    }
+
+    pub fn item(mut self, text: &str, action: impl FnMut() -> Transition + 'static) -> Self {
+        self.items.push((text.to_string(), Box::new(action)));
+        self
+    }
+
+    pub fn on_cancel(mut self, action: impl FnMut() -> Transition + 'static) -> Self {
+        self.cancel = Some(Box::new(action));
+        self
+    }
+
+    /// Make the menu an overlay, painted over the (dimmed) scene below it.
+    pub fn overlay(mut self) -> Self {
+        self.overlay = true;
+        self
+    }
+
+    #[inline]
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    fn scale(&self) -> i32 {
+        (self.scr_height / 120).max(1)
+    }
+}
+
+impl Scene for MenuScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        if self.items.is_empty() {
+            return Transition::None;
+        }
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Up) | Some(Keycode::W),
+                ..
+            } => {
+                self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Down) | Some(Keycode::S),
+                ..
+            } => {
+                self.selected = (self.selected + 1) % self.items.len();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Return) | Some(Keycode::KpEnter) | Some(Keycode::Space),
+                repeat: false,
+                ..
+            } => {
+                return (self.items[self.selected].1)();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => {
+                if let Some(cancel) = self.cancel.as_mut() {
+                    return cancel();
+                }
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        if self.overlay {
+            dim_screen(painter, self.scr_width, self.scr_height, 0.5);
+        } else {
+            painter.fill_rect(0, 0, self.scr_width, self.scr_height, MENU_BACKGROUND);
+        }
+
+        let scale = self.scale();
+        let line_height = font::LINE_HEIGHT * scale * 2;
+        let total_height = line_height * (self.items.len() as i32 + 2);
+        let center_x = self.scr_width / 2;
+        let mut y = (self.scr_height - total_height) / 2;
+
+        font::draw_text_centered(painter, center_x, y, &self.title, scale * 2, MENU_TITLE_COLOR);
+        y += line_height * 2;
+        for (idx, (text, _)) in self.items.iter().enumerate() {
+            if idx == self.selected {
+                let marked = format!("> {text} <");
+                font::draw_text_centered(painter, center_x, y, &marked, scale, MENU_SELECTED_COLOR);
+            } else {
+                font::draw_text_centered(painter, center_x, y, text, scale, MENU_ITEM_COLOR);
+            }
+            y += line_height;
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        self.overlay
+    }
+}
+
+//-------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Scene which logs what happens to it, and pops itself on Escape.
+    struct LogScene {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        overlay: bool,
+    }
+
+    impl Scene for LogScene {
+        fn handle_event(&mut self, event: &Event) -> Transition {
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => Transition::Pop,
+                _ => Transition::None,
+            }
+        }
+
+        fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+            self.log.borrow_mut().push(format!("update {}", self.name));
+            Transition::None
+        }
+
+        fn paint(&self, _painter: &mut dyn Painter) {
+            self.log.borrow_mut().push(format!("paint {}", self.name));
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+
+        fn resume(&mut self) {
+            self.log.borrow_mut().push(format!("resume {}", self.name));
+        }
+    }
+
+    fn log_scene(name: &'static str, overlay: bool, log: &Rc<RefCell<Vec<String>>>) -> Box<dyn Scene> {
+        Box::new(LogScene {
+            name,
+            log: log.clone(),
+            overlay,
+        })
+    }
+
+    #[test]
+    fn overlays_are_painted_over_the_scene_below() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut stack = SceneStack::new(10, 10, log_scene("game", false, &log));
+        stack.apply(Transition::Push(log_scene("pause", true, &log)));
+        stack.update_state(0.1);
+        stack.paint(&mut crate::headless::FrameBuffer::new(10, 10));
+        assert_eq!(*log.borrow(), vec!["update pause", "paint game", "paint pause"]);
+
+        log.borrow_mut().clear();
+        assert!(stack.handle_event(&Event::key_down(Keycode::Escape)));
+        assert_eq!(*log.borrow(), vec!["resume game"]);
+        assert!(!stack.handle_event(&Event::key_down(Keycode::Escape)));
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn replace_happens_after_the_fade_out() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut stack = SceneStack::new(10, 10, log_scene("title", false, &log));
+        stack.set_fade_duration(1.0);
+        stack.apply(Transition::Replace(log_scene("game", false, &log)));
+        assert!(stack.update_state(0.5));
+        assert!(stack.fade_level() > 0.4);
+        assert!(stack.update_state(0.6));
+        assert!(stack.update_state(1.0));
+        assert_eq!(0.0, stack.fade_level());
+        stack.update_state(0.1);
+        assert_eq!(1, stack.len());
+        assert_eq!(log.borrow().last().unwrap(), "update game");
+    }
+
+    #[test]
+    fn menu_selection_wraps_around() {
+        let chosen = Rc::new(RefCell::new(None));
+        let (c1, c2) = (chosen.clone(), chosen.clone());
+        let mut menu = MenuScene::new("TEST", 100, 100)
+            .item("One", move || {
+                *c1.borrow_mut() = Some(1);
+                Transition::None
+            })
+            .item("Two", move || {
+                *c2.borrow_mut() = Some(2);
+                Transition::Quit
+            });
+        menu.handle_event(&Event::key_down(Keycode::Up));
+        assert_eq!(1, menu.selected());
+        assert!(matches!(
+            menu.handle_event(&Event::key_down(Keycode::Return)),
+            Transition::Quit
+        ));
+        assert_eq!(Some(2), *chosen.borrow());
+        assert!(matches!(
+            menu.handle_event(&Event::key_down(Keycode::Escape)),
+            Transition::None
+        ));
+    }
+}
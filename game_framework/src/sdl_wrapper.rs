@@ -0,0 +1,361 @@
+//! SDL2 wrapper, to simplify using SDL2
+
+use crate::audio::*;
+use crate::events::*;
+use crate::graphics_loop::*;
+use crate::headless::*;
+use crate::painter::*;
+use crate::replay::*;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Keycode as SdlKeycode;
+use sdl2::mouse::{MouseButton as SdlMouseButton, MouseWheelDirection};
+use sdl2::pixels::PixelFormatEnum;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Enum for if/how to slep during each game loop execution.
+#[derive(PartialEq, Eq)]
+pub enum SleepKind {
+    NONE,
+    YIELD,
+    SLEEP(u32),
+}
+
+/// The configuration to be used for initializing SDL.
+pub struct SdlConfiguration {
+    title: String,
+    scr_width: i32,
+    scr_height: i32,
+    pixel_size: i32,
+    sleep_kind: SleepKind,
+}
+
+impl SdlConfiguration {
+    pub fn new(title: &str, scr_width: i32, scr_height: i32, pixel_size: i32, sleep_kind: SleepKind) -> Self {
+        assert!(scr_width > 0);
+        assert!(scr_height > 0);
+        assert!(pixel_size > 0);
+        SdlConfiguration {
+            title: String::from(title),
+            scr_width,
+            scr_height,
+            pixel_size,
+            sleep_kind,
+        }
+    }
+}
+
+/// Main function to run the continuous SDL loop
+pub fn run_sdl_loop(cfg: &SdlConfiguration, gfx_loop: &mut dyn GraphicsLoop) -> Result<(), String> {
+    assert!(cfg.scr_width > 0);
+    assert!(cfg.scr_height > 0);
+    assert!(cfg.pixel_size > 0);
+
+    let win_width = (cfg.scr_width * cfg.pixel_size) as u32;
+    let win_height = (cfg.scr_height * cfg.pixel_size) as u32;
+    let scr_width = cfg.scr_width as u32;
+    let scr_height = cfg.scr_height as u32;
+
+    // create window
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    let window = video_subsystem
+        .window(&cfg.title, win_width, win_height)
+        .position_centered()
+        .opengl()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+
+    // create texture, to paint on
+    let texture_creator = canvas.texture_creator();
+    let mut screen_buffer = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, scr_width, scr_height)
+        .map_err(|e| e.to_string())?;
+
+    // open the audio device (if this fails, we just go on without sound)
+    let audio: AudioHandle = match SdlAudio::open(&sdl_context) {
+        Ok(sdl_audio) => Rc::new(RefCell::new(sdl_audio)),
+        Err(msg) => {
+            println!("WARNING: audio is disabled - {msg}");
+            Rc::new(RefCell::new(NullAudio::new()))
+        }
+    };
+    gfx_loop.attach_audio(audio);
+
+    let mut timer = FpsAndElapsedCounter::new();
+    let mut last_fps = 42;
+    let mut event_pump = sdl_context.event_pump()?;
+
+    // Main game loop
+    'running: loop {
+        // consume the event loop
+        // (Escape is passed to the client code too, so it can be used for menus)
+        for event in event_pump.poll_iter() {
+            match event {
+                SdlEvent::Quit { .. } => break 'running,
+                _ => {
+                    if let Some(event) = convert_event(&event, cfg.pixel_size) {
+                        if !gfx_loop.handle_event(&event) {
+                            break 'running;
+                        }
+                    }
+                }
+            }
+        }
+
+        // compute time
+        let elapsed_time = timer.update_and_get_ellapsed_time();
+        if last_fps != timer.fps {
+            last_fps = timer.fps;
+            let title_with_fps = format!("{} - FPS: {}", cfg.title, last_fps);
+            canvas
+                .window_mut()
+                .set_title(&title_with_fps)
+                .map_err(|e| e.to_string())?;
+        }
+
+        // update the internal state
+        if !gfx_loop.update_state(elapsed_time) {
+            break 'running;
+        }
+
+        // paint the screen, using a SDL2 streaming texture
+        // - see: https://github.com/Rust-SDL2/rust-sdl2/blob/master/examples/renderer-texture.rs
+        // - see: https://www.reddit.com/r/cpp_questions/comments/eqwsao/sdl_rendering_way_too_slow/
+        screen_buffer.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            // all painting must be done in this closure
+            let mut painter = InternalTexturePainter {
+                buffer,
+                pitch,
+                scr_width: cfg.scr_width,
+                scr_height: cfg.scr_height,
+            };
+            gfx_loop.paint(&mut painter);
+        })?;
+
+        // paint texture on screen
+        canvas.copy(&screen_buffer, None, None)?;
+        canvas.present();
+
+        // sleep a bit, so we don't hog the CPU
+        match cfg.sleep_kind {
+            SleepKind::SLEEP(nanos) => {
+                std::thread::sleep(Duration::new(0, nanos));
+            }
+            SleepKind::YIELD => {
+                std::thread::yield_now();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the SDL loop, recording or playing a replay, as specified by the replay mode.
+/// The seed is only used when recording, to be saved in the replay.
+/// When playing a replay headless, no window is opened - the final frame's checksum is printed instead.
+pub fn run_sdl_loop_with_replays(
+    cfg: &SdlConfiguration,
+    gfx_loop: &mut dyn GraphicsLoop,
+    mode: &ReplayMode,
+    seed: u64,
+) -> Result<(), String> {
+    match mode {
+        ReplayMode::Off => run_sdl_loop(cfg, gfx_loop),
+        ReplayMode::Record(path) => {
+            let mut recorder = ReplayRecorder::new(gfx_loop, seed);
+            let res = run_sdl_loop(cfg, &mut recorder);
+            recorder.finish().save(path)?;
+            res
+        }
+        ReplayMode::Play(replay) => {
+            let mut player = ReplayPlayer::new(gfx_loop, replay);
+            run_sdl_loop(cfg, &mut player)
+        }
+        ReplayMode::PlayHeadless(replay) => {
+            let frames = run_headless(gfx_loop, &replay.frames);
+            let mut frame_buffer = FrameBuffer::new(cfg.scr_width, cfg.scr_height);
+            gfx_loop.paint(&mut frame_buffer);
+            println!(
+                "Replayed {frames} of {} frames - final frame checksum: {:016x}",
+                replay.frames.len(),
+                frame_buffer.checksum()
+            );
+            Ok(())
+        }
+    }
+}
+
+//--------------------------------
+// Internal details
+
+/// Convert a SDL2 event to our own event type (the events we don't care about are dropped).
+fn convert_event(event: &SdlEvent, pixel_size: i32) -> Option<Event> {
+    let key = |keycode: &Option<SdlKeycode>| keycode.map(|k| Keycode(k as i32));
+    let button = |button: &SdlMouseButton| match button {
+        SdlMouseButton::Left => MouseButton::Left,
+        SdlMouseButton::Middle => MouseButton::Middle,
+        SdlMouseButton::Right => MouseButton::Right,
+        _ => MouseButton::Other,
+    };
+    let event = match event {
+        SdlEvent::Quit { .. } => Event::Quit,
+        SdlEvent::KeyDown {
+            keycode,
+            keymod,
+            repeat,
+            ..
+        } => Event::KeyDown {
+            keycode: key(keycode),
+            keymod: Mod(keymod.bits()),
+            repeat: *repeat,
+        },
+        SdlEvent::KeyUp {
+            keycode,
+            keymod,
+            repeat,
+            ..
+        } => Event::KeyUp {
+            keycode: key(keycode),
+            keymod: Mod(keymod.bits()),
+            repeat: *repeat,
+        },
+        SdlEvent::TextInput { text, .. } => Event::TextInput { text: text.clone() },
+        SdlEvent::MouseMotion { x, y, .. } => Event::MouseMotion {
+            x: x / pixel_size,
+            y: y / pixel_size,
+        },
+        SdlEvent::MouseButtonDown { mouse_btn, x, y, .. } => Event::MouseButtonDown {
+            button: button(mouse_btn),
+            x: x / pixel_size,
+            y: y / pixel_size,
+        },
+        SdlEvent::MouseButtonUp { mouse_btn, x, y, .. } => Event::MouseButtonUp {
+            button: button(mouse_btn),
+            x: x / pixel_size,
+            y: y / pixel_size,
+        },
+        SdlEvent::MouseWheel { y, direction, .. } => Event::MouseWheel {
+            y: if *direction == MouseWheelDirection::Flipped {
+                -y
+            } else {
+                *y
+            },
+        },
+        _ => return None,
+    };
+    Some(event)
+}
+
+struct InternalTexturePainter<'a> {
+    buffer: &'a mut [u8],
+    pitch: usize,
+    scr_width: i32,
+    scr_height: i32,
+}
+
+impl<'a> Painter for InternalTexturePainter<'a> {
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        if x >= 0 && y >= 0 && x < self.scr_width && y < self.scr_height {
+            let offset = (y as usize) * self.pitch + (x as usize) * 3;
+            self.buffer[offset] = color.r;
+            self.buffer[offset + 1] = color.g;
+            self.buffer[offset + 2] = color.b;
+        }
+    }
+}
+
+/// Audio backend which plays the sounds via SDL2, using our own [`Mixer`].
+struct SdlAudio {
+    device: AudioDevice<MixerCallback>,
+}
+
+impl SdlAudio {
+    fn open(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(DEFAULT_SAMPLE_RATE as i32),
+            channels: Some(2),
+            samples: Some(1024),
+        };
+        let device =
+            audio_subsystem.open_playback(None, &desired_spec, |spec| MixerCallback(Mixer::new(spec.freq as u32)))?;
+        device.resume();
+        Ok(SdlAudio { device })
+    }
+}
+
+impl AudioBackend for SdlAudio {
+    fn load_sound(&mut self, sound: Sound) -> SoundId {
+        self.device.lock().0.load_sound(sound)
+    }
+
+    fn play(&mut self, id: SoundId, volume: f32, pan: f32) {
+        self.device.lock().0.play(id, volume, pan);
+    }
+
+    fn play_music(&mut self, id: SoundId, volume: f32) {
+        self.device.lock().0.play_music(id, volume);
+    }
+
+    fn stop_music(&mut self) {
+        self.device.lock().0.stop_music();
+    }
+
+    fn set_master_volume(&mut self, volume: f32) {
+        self.device.lock().0.set_master_volume(volume);
+    }
+}
+
+struct MixerCallback(Mixer);
+
+impl AudioCallback for MixerCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.mix(out);
+    }
+}
+
+struct FpsAndElapsedCounter {
+    time_sum: f64,
+    time_cnt: u32,
+    fps: u32,
+    last_moment: Instant,
+}
+
+impl FpsAndElapsedCounter {
+    fn new() -> Self {
+        FpsAndElapsedCounter {
+            time_cnt: 0,
+            time_sum: 0.0,
+            last_moment: Instant::now(),
+            fps: 0,
+        }
+    }
+
+    fn update_and_get_ellapsed_time(&mut self) -> f64 {
+        // compute time
+        let next_moment = Instant::now();
+        let elapsed_time = next_moment.duration_since(self.last_moment).as_secs_f64();
+        self.last_moment = next_moment;
+
+        // compute FPS
+        self.time_sum += elapsed_time;
+        self.time_cnt += 1;
+        if self.time_sum >= 1.0 {
+            let avg = self.time_sum / (self.time_cnt as f64);
+            self.fps = if avg <= 0.0 { 999999 } else { (1.0 / avg) as u32 };
+            self.time_cnt = 0;
+            self.time_sum = 0.0;
+        }
+
+        elapsed_time
+    }
+}
@@ -0,0 +1,79 @@
+//! Persistent files: where to keep them, and how to write them safely.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory for the files of the given application, inside the user's config directory:
+/// - Windows: `%APPDATA%\app_name`
+/// - macOS: `~/Library/Application Support/app_name`
+/// - others: `$XDG_CONFIG_HOME/app_name`, or `~/.config/app_name`
+///
+/// Returns `None` if the config directory is unknown. The directory is not created.
+pub fn config_dir(app_name: &str) -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".config")))
+    };
+    base.map(|dir| dir.join(app_name))
+}
+
+/// Write a whole file, creating its directory if needed. The text is written to a temporary file first,
+/// which then replaces the old one: a crash while saving cannot leave a half-written file.
+pub fn save_file(path: &Path, text: &str) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("{}: {e}", path.display());
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(err)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, text).map_err(err)?;
+    std::fs::rename(&tmp_path, path).map_err(err)
+}
+
+/// Today's date (UTC), as "YYYY-MM-DD".
+pub fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_date(secs / 86400);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Convert days since 1970-01-01 to (year, month, day), in the proleptic Gregorian calendar.
+/// - see: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!((1970, 1, 1), civil_date(0));
+        assert_eq!((2000, 2, 29), civil_date(11_016));
+        assert_eq!((2024, 12, 31), civil_date(20_088));
+    }
+
+    #[test]
+    fn saved_files_replace_the_old_ones() {
+        let dir = std::env::temp_dir().join(format!("game_framework_storage_{}", std::process::id()));
+        let path = dir.join("sub").join("file.txt");
+        save_file(&path, "first").unwrap();
+        save_file(&path, "second").unwrap();
+        assert_eq!("second", std::fs::read_to_string(&path).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
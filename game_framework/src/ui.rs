@@ -0,0 +1,570 @@
+//! Immediate-mode UI widgets (labels, buttons, sliders, checkboxes, lists, text input).
+//!
+//! How to use it:
+//!  1. pass all the input events to [`Ui::handle_event`];
+//!  2. in `update_state`, call [`Ui::begin`], then the widget methods (which return true
+//!     when they were clicked/changed), then [`Ui::end`];
+//!  3. in `paint`, call [`Ui::paint`].
+//!
+//! The widgets are only *recorded* during the update and painted later, because
+//! painting only gets `&self` access.
+//!
+//! The widgets are laid out vertically. They can be used with the mouse, or with the keyboard:
+//! Up/Down/Tab move the focus, Enter/Space activate, Left/Right change sliders and checkboxes.
+
+use crate::events::*;
+use crate::font;
+use crate::painter::*;
+
+/// Colors and sizes used by the UI.
+#[derive(Clone, Debug)]
+pub struct UiStyle {
+    pub scale: i32,
+    pub padding: i32,
+    pub text: RGB,
+    pub text_focused: RGB,
+    pub widget: RGB,
+    pub widget_focused: RGB,
+    pub accent: RGB,
+    pub panel: RGB,
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        UiStyle {
+            scale: 2,
+            padding: 3,
+            text: RGB::from(192, 192, 192),
+            text_focused: RGB::from(255, 255, 255),
+            widget: RGB::from(48, 48, 96),
+            widget_focused: RGB::from(80, 80, 160),
+            accent: RGB::from(255, 192, 0),
+            panel: RGB::from(16, 16, 40),
+        }
+    }
+}
+
+/// Things to paint, recorded during the update.
+enum DrawCmd {
+    Rect(i32, i32, i32, i32, RGB),
+    FillRect(i32, i32, i32, i32, RGB),
+    Text(i32, i32, String, i32, RGB),
+}
+
+/// Immediate-mode UI context.
+pub struct Ui {
+    style: UiStyle,
+    // input collected since the last frame
+    mouse_x: i32,
+    mouse_y: i32,
+    mouse_moved: bool,
+    mouse_down: bool,
+    mouse_pressed: bool,
+    keys: Vec<(Keycode, Mod)>,
+    typed_text: String,
+    // widgets + layout for the current frame
+    x: i32,
+    y: i32,
+    width: i32,
+    widget_count: usize,
+    focus: usize,
+    dragging: Option<usize>,
+    commands: Vec<DrawCmd>,
+}
+
+impl Ui {
+    pub fn new(style: UiStyle) -> Self {
+        Ui {
+            style,
+            mouse_x: -1,
+            mouse_y: -1,
+            mouse_moved: false,
+            mouse_down: false,
+            mouse_pressed: false,
+            keys: vec![],
+            typed_text: String::new(),
+            x: 0,
+            y: 0,
+            width: 0,
+            widget_count: 0,
+            focus: 0,
+            dragging: None,
+            commands: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn style(&self) -> &UiStyle {
+        &self.style
+    }
+
+    /// Index of the focused widget (widgets are numbered in the order they are created, from 0).
+    #[inline]
+    pub fn focus(&self) -> usize {
+        self.focus
+    }
+
+    #[inline]
+    pub fn set_focus(&mut self, focus: usize) {
+        self.focus = focus;
+    }
+
+    /// Collect the input. Must be called for all the events.
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::MouseMotion { x, y } => {
+                self.mouse_x = *x;
+                self.mouse_y = *y;
+                self.mouse_moved = true;
+            }
+            Event::MouseButtonDown {
+                button: MouseButton::Left,
+                x,
+                y,
+            } => {
+                self.mouse_x = *x;
+                self.mouse_y = *y;
+                self.mouse_down = true;
+                self.mouse_pressed = true;
+            }
+            Event::MouseButtonUp {
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.mouse_down = false;
+                self.dragging = None;
+            }
+            Event::KeyDown {
+                keycode: Some(key),
+                keymod,
+                ..
+            } => self.keys.push((*key, *keymod)),
+            Event::TextInput { text } => self.typed_text.push_str(text),
+            _ => {}
+        }
+    }
+
+    /// Start laying out the widgets, from (x, y), with the given width.
+    pub fn begin(&mut self, x: i32, y: i32, width: i32) {
+        self.x = x;
+        self.y = y;
+        self.width = width;
+        self.widget_count = 0;
+        self.commands.clear();
+    }
+
+    /// Finish the frame: handle focus navigation and forget the consumed input.
+    pub fn end(&mut self) {
+        for (key, keymod) in std::mem::take(&mut self.keys) {
+            let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+            match key {
+                Keycode::Up => self.move_focus(-1),
+                Keycode::Down => self.move_focus(1),
+                Keycode::Tab if shift => self.move_focus(-1),
+                Keycode::Tab => self.move_focus(1),
+                _ => {}
+            }
+        }
+        if self.widget_count > 0 && self.focus >= self.widget_count {
+            self.focus = self.widget_count - 1;
+        }
+        self.typed_text.clear();
+        self.mouse_moved = false;
+        self.mouse_pressed = false;
+    }
+
+    /// Paint the widgets recorded during the last frame.
+    pub fn paint(&self, painter: &mut dyn Painter) {
+        for cmd in self.commands.iter() {
+            match cmd {
+                DrawCmd::Rect(x, y, w, h, color) => painter.draw_rect(*x, *y, *w, *h, *color),
+                DrawCmd::FillRect(x, y, w, h, color) => painter.fill_rect(*x, *y, *w, *h, *color),
+                DrawCmd::Text(x, y, text, scale, color) => font::draw_text(painter, *x, *y, text, *scale, *color),
+            }
+        }
+    }
+
+    //-------------------------------------------------------
+    //  Widgets
+
+    /// A background panel (not part of the layout).
+    pub fn panel(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.commands.push(DrawCmd::FillRect(x, y, w, h, self.style.panel));
+        self.commands.push(DrawCmd::Rect(x, y, w, h, self.style.widget_focused));
+    }
+
+    /// Leave some vertical space.
+    pub fn space(&mut self, pixels: i32) {
+        self.y += pixels;
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let (x, y) = (self.x, self.y);
+        let color = self.style.text;
+        self.text(x, y + self.style.padding, text, color);
+        self.y += self.row_height();
+    }
+
+    /// A title: a bigger label, centered.
+    pub fn title(&mut self, text: &str) {
+        let scale = self.style.scale * 2;
+        let x = self.x + (self.width - font::text_width(text, scale)) / 2;
+        let y = self.y;
+        self.commands
+            .push(DrawCmd::Text(x, y, text.to_string(), scale, self.style.accent));
+        self.y += font::LINE_HEIGHT * scale + self.style.padding * 2;
+    }
+
+    /// Returns true if the button was clicked/activated.
+    pub fn button(&mut self, text: &str) -> bool {
+        let (_, focused, rect) = self.next_widget(1);
+        let clicked = self.clicked(rect) || (focused && self.take_key(&[Keycode::Return, Keycode::Space]));
+        self.draw_box(rect, focused);
+        let tx = rect.0 + (rect.2 - font::text_width(text, self.style.scale)) / 2;
+        self.text(tx, rect.1 + self.style.padding, text, self.text_color(focused));
+        clicked
+    }
+
+    /// Returns true if the value was changed.
+    pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+        let (_, focused, rect) = self.next_widget(1);
+        let toggled = self.clicked(rect)
+            || (focused && self.take_key(&[Keycode::Return, Keycode::Space, Keycode::Left, Keycode::Right]));
+        if toggled {
+            *value = !*value;
+        }
+        self.draw_box(rect, focused);
+        let side = rect.3 - 2 * self.style.padding;
+        let (bx, by) = (rect.0 + self.style.padding, rect.1 + self.style.padding);
+        self.commands.push(DrawCmd::Rect(bx, by, side, side, self.style.text));
+        if *value {
+            self.commands
+                .push(DrawCmd::FillRect(bx + 2, by + 2, side - 4, side - 4, self.style.accent));
+        }
+        let tx = bx + side + self.style.padding * 2;
+        self.text(tx, rect.1 + self.style.padding, text, self.text_color(focused));
+        toggled
+    }
+
+    /// A horizontal slider, for values between `min` and `max`, changing in increments of `step`.
+    /// Returns true if the value was changed.
+    pub fn slider(&mut self, text: &str, value: &mut f64, min: f64, max: f64, step: f64) -> bool {
+        assert!(max > min && step > 0.0);
+        let (id, focused, rect) = self.next_widget(1);
+        let old_value = *value;
+
+        // keyboard
+        if focused && self.take_key(&[Keycode::Left]) {
+            *value -= step;
+        }
+        if focused && self.take_key(&[Keycode::Right]) {
+            *value += step;
+        }
+
+        // mouse (the bar is the right half of the widget)
+        let bar = (rect.0 + rect.2 / 2, rect.1, rect.2 - rect.2 / 2, rect.3);
+        if self.mouse_pressed && self.contains(bar) {
+            self.dragging = Some(id);
+        }
+        if self.mouse_down && self.dragging == Some(id) {
+            let ratio = ((self.mouse_x - bar.0) as f64 / (bar.2 - 1).max(1) as f64).clamp(0.0, 1.0);
+            *value = min + ratio * (max - min);
+        }
+
+        // snap to the steps
+        *value = (min + ((*value - min) / step).round() * step).clamp(min, max);
+
+        self.draw_box(rect, focused);
+        let label = if step >= 1.0 {
+            format!("{text}: {}", value.round() as i64)
+        } else {
+            format!("{text}: {value:.2}")
+        };
+        self.text(
+            rect.0 + self.style.padding,
+            rect.1 + self.style.padding,
+            &label,
+            self.text_color(focused),
+        );
+        let pad = self.style.padding;
+        let track_y = bar.1 + bar.3 / 2;
+        self.commands
+            .push(DrawCmd::FillRect(bar.0, track_y - 1, bar.2 - pad, 2, self.style.text));
+        let knob_w = 2 * pad;
+        let knob_x = bar.0 + (((*value - min) / (max - min)) * (bar.2 - pad - knob_w) as f64) as i32;
+        self.commands.push(DrawCmd::FillRect(
+            knob_x,
+            bar.1 + 2,
+            knob_w,
+            bar.3 - 4,
+            self.style.accent,
+        ));
+
+        *value != old_value
+    }
+
+    /// A list of items, showing at most `visible_rows` items (scrolling to keep the selected one visible).
+    /// Clicking an item selects it; Enter (or clicking the selected item) activates it.
+    /// While the list has the focus, Up/Down move the selection (and only leave the list at its ends).
+    /// Returns true if the selected item was activated.
+    pub fn list_select<S: AsRef<str>>(&mut self, items: &[S], selected: &mut usize, visible_rows: usize) -> bool {
+        let rows = visible_rows.clamp(1, items.len().max(1));
+        let (_, focused, rect) = self.next_widget(rows as i32);
+        let mut activated = false;
+        if !items.is_empty() {
+            *selected = (*selected).min(items.len() - 1);
+            if focused {
+                loop {
+                    if *selected > 0 && self.take_key(&[Keycode::Up]) {
+                        *selected -= 1;
+                    } else if *selected + 1 < items.len() && self.take_key(&[Keycode::Down]) {
+                        *selected += 1;
+                    } else {
+                        break;
+                    }
+                }
+                activated = self.take_key(&[Keycode::Return, Keycode::Space]);
+            }
+        }
+
+        // keep the selected item visible
+        let first = (*selected + 1).saturating_sub(rows);
+        let row_h = self.row_height();
+        if self.mouse_pressed && self.contains(rect) {
+            let clicked = first + ((self.mouse_y - rect.1) / row_h) as usize;
+            if clicked < items.len() {
+                activated |= clicked == *selected;
+                *selected = clicked;
+            }
+        }
+
+        self.draw_box(rect, focused);
+        for (row, item) in items.iter().enumerate().skip(first).take(rows) {
+            let y = rect.1 + ((row - first) as i32) * row_h;
+            let is_selected = row == *selected;
+            if is_selected {
+                self.commands.push(DrawCmd::FillRect(
+                    rect.0 + 1,
+                    y + 1,
+                    rect.2 - 2,
+                    row_h - 2,
+                    self.style.widget_focused,
+                ));
+            }
+            let color = if is_selected {
+                self.style.accent
+            } else {
+                self.style.text
+            };
+            self.text(
+                rect.0 + self.style.padding,
+                y + self.style.padding,
+                item.as_ref(),
+                color,
+            );
+        }
+        activated
+    }
+
+    /// A single-line text input. Returns true if the text was changed.
+    pub fn text_input(&mut self, text: &str, value: &mut String, max_len: usize) -> bool {
+        let (_, focused, rect) = self.next_widget(1);
+        let mut changed = false;
+        if focused {
+            for ch in self.typed_text.chars() {
+                if value.chars().count() < max_len && !ch.is_control() {
+                    value.push(ch);
+                    changed = true;
+                }
+            }
+            self.typed_text.clear();
+            if self.take_key(&[Keycode::Backspace]) {
+                changed |= value.pop().is_some();
+            }
+        }
+        self.draw_box(rect, focused);
+        let cursor = if focused { "_" } else { "" };
+        let label = format!("{text}: {value}{cursor}");
+        self.text(
+            rect.0 + self.style.padding,
+            rect.1 + self.style.padding,
+            &label,
+            self.text_color(focused),
+        );
+        changed
+    }
+
+    //-------------------------------------------------------
+    //  Internal stuff
+
+    #[inline]
+    fn row_height(&self) -> i32 {
+        font::LINE_HEIGHT * self.style.scale + self.style.padding * 2
+    }
+
+    /// Allocate the next (focusable) widget: returns its id, if it's focused and its rectangle.
+    fn next_widget(&mut self, rows: i32) -> (usize, bool, (i32, i32, i32, i32)) {
+        let id = self.widget_count;
+        self.widget_count += 1;
+        let rect = (self.x, self.y, self.width, self.row_height() * rows);
+        self.y += rect.3 + self.style.padding;
+        // the mouse moves the focus
+        if (self.mouse_moved || self.mouse_pressed) && self.contains(rect) {
+            self.focus = id;
+        }
+        (id, self.focus == id, rect)
+    }
+
+    fn contains(&self, (x, y, w, h): (i32, i32, i32, i32)) -> bool {
+        self.mouse_x >= x && self.mouse_x < x + w && self.mouse_y >= y && self.mouse_y < y + h
+    }
+
+    fn clicked(&self, rect: (i32, i32, i32, i32)) -> bool {
+        self.mouse_pressed && self.contains(rect)
+    }
+
+    /// Consume the first pressed key which is in the given list.
+    fn take_key(&mut self, keys: &[Keycode]) -> bool {
+        match self.keys.iter().position(|(k, _)| keys.contains(k)) {
+            Some(pos) => {
+                self.keys.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn move_focus(&mut self, delta: i32) {
+        if self.widget_count > 0 {
+            let count = self.widget_count as i32;
+            self.focus = ((self.focus as i32 + delta).rem_euclid(count)) as usize;
+        }
+    }
+
+    fn text_color(&self, focused: bool) -> RGB {
+        if focused {
+            self.style.text_focused
+        } else {
+            self.style.text
+        }
+    }
+
+    fn text(&mut self, x: i32, y: i32, text: &str, color: RGB) {
+        self.commands
+            .push(DrawCmd::Text(x, y, text.to_string(), self.style.scale, color));
+    }
+
+    fn draw_box(&mut self, (x, y, w, h): (i32, i32, i32, i32), focused: bool) {
+        let (fill, border) = if focused {
+            (self.style.widget_focused, self.style.accent)
+        } else {
+            (self.style.widget, self.style.widget)
+        };
+        self.commands.push(DrawCmd::FillRect(x, y, w, h, fill));
+        self.commands.push(DrawCmd::Rect(x, y, w, h, border));
+    }
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new(UiStyle::default())
+    }
+}
+
+//-------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run one UI frame, with a button, a checkbox, a slider, a list and a text input.
+    fn frame(ui: &mut Ui, state: &mut (bool, f64, usize, String)) -> (bool, bool) {
+        ui.begin(0, 0, 200);
+        let clicked = ui.button("Go");
+        ui.checkbox("Check", &mut state.0);
+        ui.slider("Value", &mut state.1, 0.0, 10.0, 1.0);
+        let activated = ui.list_select(&["A", "B", "C"], &mut state.2, 2);
+        ui.text_input("Name", &mut state.3, 5);
+        ui.end();
+        (clicked, activated)
+    }
+
+    #[test]
+    fn keyboard_navigation() {
+        let mut ui = Ui::default();
+        let mut state = (false, 5.0, 0, String::new());
+
+        ui.handle_event(&Event::key_down(Keycode::Return));
+        assert_eq!((true, false), frame(&mut ui, &mut state));
+
+        // checkbox
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        frame(&mut ui, &mut state);
+        ui.handle_event(&Event::key_down(Keycode::Space));
+        frame(&mut ui, &mut state);
+        assert!(state.0);
+
+        // slider
+        ui.handle_event(&Event::key_down(Keycode::Tab));
+        frame(&mut ui, &mut state);
+        ui.handle_event(&Event::key_down(Keycode::Right));
+        frame(&mut ui, &mut state);
+        assert_eq!(6.0, state.1);
+
+        // the list keeps Up/Down until its ends
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        frame(&mut ui, &mut state);
+        assert_eq!(3, ui.focus());
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        frame(&mut ui, &mut state);
+        assert_eq!((3, 2), (ui.focus(), state.2));
+        ui.handle_event(&Event::key_down(Keycode::Return));
+        assert_eq!((false, true), frame(&mut ui, &mut state));
+
+        // text input, with wrap around to the first widget afterwards
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        frame(&mut ui, &mut state);
+        ui.handle_event(&Event::TextInput {
+            text: "Hello World".to_string(),
+        });
+        ui.handle_event(&Event::key_down(Keycode::Backspace));
+        frame(&mut ui, &mut state);
+        assert_eq!("Hell", state.3);
+        ui.handle_event(&Event::key_down(Keycode::Down));
+        frame(&mut ui, &mut state);
+        assert_eq!(0, ui.focus());
+    }
+
+    #[test]
+    fn mouse_clicks_and_drags() {
+        let mut ui = Ui::default();
+        let mut state = (false, 0.0, 0, String::new());
+        let row = ui.row_height() + ui.style().padding;
+
+        // click the button (first row)
+        ui.handle_event(&Event::MouseButtonDown {
+            button: MouseButton::Left,
+            x: 10,
+            y: 5,
+        });
+        ui.handle_event(&Event::MouseButtonUp {
+            button: MouseButton::Left,
+            x: 10,
+            y: 5,
+        });
+        assert_eq!((true, false), frame(&mut ui, &mut state));
+
+        // drag the slider (third row) all the way to the right
+        let y = 2 * row + 5;
+        ui.handle_event(&Event::MouseButtonDown {
+            button: MouseButton::Left,
+            x: 150,
+            y,
+        });
+        frame(&mut ui, &mut state);
+        ui.handle_event(&Event::MouseMotion { x: 500, y: 500 });
+        frame(&mut ui, &mut state);
+        assert_eq!(10.0, state.1);
+    }
+}
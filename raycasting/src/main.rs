@@ -5,6 +5,8 @@
 #![cfg_attr(not(any(test, debug_assertions)), windows_subsystem = "windows")]
 
 use raycasting::*;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const MAP: &[&str] = &[
     "CACACACACAC",
@@ -13,13 +15,29 @@ const MAP: &[&str] = &[
     "A..FEF.F..A",
     "C..F...EE.C",
     "A....@..F.A",
-    "C.........C",
+    "C..o...o..C",
     "A..GB.....A",
     "C..B...D..C",
     "A.........A",
     "CACACACACAC",
 ];
 
+const MAZE: &[&str] = &[
+    "BBBBBBBBBBB",
+    "B@..B.....B",
+    "B.B.B.BBB.B",
+    "B.B...B...B",
+    "B.BBBBB.BBB",
+    "B...B...B.B",
+    "BBB.B.BBB.B",
+    "B...B.B...B",
+    "B.BBB.B.BBB",
+    "B.....B...B",
+    "BBBBBBBBBBB",
+];
+
+const LEVELS: &[(&str, &[&str])] = &[("Columns", MAP), ("Maze", MAZE)];
+
 const SCR_WIDTH: i32 = 1200;
 const SCR_HEIGHT: i32 = 600;
 const PIXEL_SIZE: i32 = 1;
@@ -27,25 +45,231 @@ const PIXEL_SIZE: i32 = 1;
 const SLEEP_KIND: SleepKind = SleepKind::YIELD;
 
 fn main() {
+    // main game loop (possibly recording or playing a replay)
+    let args: Vec<String> = std::env::args().collect();
+    let res = ReplayMode::from_args(&args).and_then(|replay_mode| {
+        let settings = Rc::new(RefCell::new(RayCasterSettings::default()));
+        let mut scenes = SceneStack::new(SCR_WIDTH, SCR_HEIGHT, Box::new(title_menu(&settings)));
+        let sdl_config = SdlConfiguration::new("Ray Caster Demo", SCR_WIDTH, SCR_HEIGHT, PIXEL_SIZE, SLEEP_KIND);
+        raycasting::run_sdl_loop_with_replays(&sdl_config, &mut scenes, &replay_mode, 0)
+    });
+    if let Err(msg) = res {
+        println!("ERROR: {msg}");
+    } else {
+        println!("Raycaster demo finished OK :)");
+    }
+}
+
+/// Settings shared by all the scenes.
+type SharedSettings = Rc<RefCell<RayCasterSettings>>;
+
+fn build_raycaster(map: &[&str], settings: &RayCasterSettings) -> RayCaster {
     // prepare the map data
-    let map_width = MAP[0].len() as i32;
-    let map_height = MAP.len() as i32;
-    let flatmap = MAP.concat();
+    let map_width = map[0].len() as i32;
+    let map_height = map.len() as i32;
+    let flatmap = map.concat();
 
     // build the ray caster "demo"
     let mut builder = RayCasterBuilder::new();
     builder
         .scr_size(SCR_WIDTH, SCR_HEIGHT)
         .map_size(map_width, map_height)
-        .map_from_str(&flatmap);
-    let mut raycaster = builder.build();
+        .map_from_str(&flatmap)
+        .settings(settings);
+    builder.build()
+}
 
-    // main game loop
-    let sdl_config = SdlConfiguration::new("Ray Caster Demo", SCR_WIDTH, SCR_HEIGHT, PIXEL_SIZE, SLEEP_KIND);
-    let res = raycasting::run_sdl_loop(&sdl_config, &mut raycaster);
-    if let Err(msg) = res {
-        println!("ERROR: {msg}");
-    } else {
-        println!("Raycaster demo finished OK :)");
+//----------------------------
+// Scenes
+
+fn title_menu(settings: &SharedSettings) -> MenuScene {
+    let s1 = settings.clone();
+    let s2 = settings.clone();
+    MenuScene::new("RAY CASTER DEMO", SCR_WIDTH, SCR_HEIGHT)
+        .item("Start", move || {
+            Transition::Replace(Box::new(LevelSelectScene::new(&s1)))
+        })
+        .item("Settings", move || Transition::Push(Box::new(SettingsScene::new(&s2))))
+        .item("Quit", || Transition::Quit)
+        .on_cancel(|| Transition::Quit)
+}
+
+fn pause_menu(settings: &SharedSettings) -> MenuScene {
+    let s1 = settings.clone();
+    let s2 = settings.clone();
+    MenuScene::new("PAUSED", SCR_WIDTH, SCR_HEIGHT)
+        .overlay()
+        .item("Resume", || Transition::Pop)
+        .item("Settings", move || Transition::Push(Box::new(SettingsScene::new(&s1))))
+        .item("Quit to title", move || Transition::Reset(Box::new(title_menu(&s2))))
+        .item("Quit game", || Transition::Quit)
+        .on_cancel(|| Transition::Pop)
+}
+
+/// Choose the map to play.
+struct LevelSelectScene {
+    ui: Ui,
+    selected: usize,
+    settings: SharedSettings,
+}
+
+impl LevelSelectScene {
+    fn new(settings: &SharedSettings) -> Self {
+        LevelSelectScene {
+            ui: Ui::new(UiStyle::default()),
+            selected: 0,
+            settings: settings.clone(),
+        }
+    }
+
+    fn start(&self) -> Transition {
+        let raycaster = build_raycaster(LEVELS[self.selected].1, &self.settings.borrow());
+        Transition::Replace(Box::new(GameplayScene {
+            raycaster,
+            settings: self.settings.clone(),
+        }))
+    }
+}
+
+impl Scene for LevelSelectScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Replace(Box::new(title_menu(&self.settings))),
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let names: Vec<&str> = LEVELS.iter().map(|(name, _)| *name).collect();
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 3, SCR_HEIGHT / 4, SCR_WIDTH / 3);
+        ui.title("SELECT LEVEL");
+        ui.space(10);
+        let mut transition = Transition::None;
+        // both widgets are declared every frame, whichever is used
+        let mut play = ui.list_select(&names, &mut self.selected, 6);
+        play |= ui.button("Play");
+        if play {
+            transition = self.start();
+        }
+        if self.ui.button("Back") {
+            transition = Transition::Replace(Box::new(title_menu(&self.settings)));
+        }
+        self.ui.end();
+        transition
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.ui.paint(painter);
+    }
+}
+
+/// Edit the settings; the changes are applied right away.
+struct SettingsScene {
+    ui: Ui,
+    settings: SharedSettings,
+}
+
+impl SettingsScene {
+    fn new(settings: &SharedSettings) -> Self {
+        SettingsScene {
+            ui: Ui::new(UiStyle::default()),
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for SettingsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Pop,
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let mut settings = self.settings.borrow_mut();
+        let (x, y, w) = (SCR_WIDTH / 4, SCR_HEIGHT / 6, SCR_WIDTH / 2);
+        let ui = &mut self.ui;
+        ui.begin(x, y, w);
+        ui.panel(x - 10, y - 10, w + 20, SCR_HEIGHT * 2 / 3 + 20);
+        ui.title("SETTINGS");
+        ui.space(10);
+        ui.slider("Walk speed", &mut settings.walk_speed, 1.0, 8.0, 0.5);
+        ui.slider("Rotate speed", &mut settings.rotate_speed, 30.0, 180.0, 10.0);
+        ui.slider("Field of view", &mut settings.half_fov, 15.0, 35.0, 1.0);
+        ui.slider("Volume", &mut settings.volume, 0.0, 1.0, 0.1);
+        ui.space(10);
+        let back = ui.button("Back");
+        ui.end();
+        if back {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.5);
+        self.ui.paint(painter);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// The ray caster itself, with a pause menu on Escape.
+struct GameplayScene {
+    raycaster: RayCaster,
+    settings: SharedSettings,
+}
+
+impl Scene for GameplayScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Push(Box::new(pause_menu(&self.settings))),
+            _ if !self.raycaster.handle_event(event) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        if self.raycaster.update_state(elapsed_time) {
+            Transition::None
+        } else {
+            Transition::Quit
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.raycaster.paint(painter);
+    }
+
+    fn resume(&mut self) {
+        self.raycaster.release_keys();
+        // the settings may have been changed from the pause menu
+        self.raycaster.set_settings(&self.settings.borrow());
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        self.raycaster.attach_audio(audio);
     }
 }
@@ -7,7 +7,6 @@
  */
 
 use crate::*;
-use sdl2::keyboard::*;
 
 // constant for converting degrees to radians
 const DEG_TO_RAD: f64 = std::f64::consts::PI / 180.0;
@@ -16,11 +15,26 @@ const DEG_TO_RAD: f64 = std::f64::consts::PI / 180.0;
 const WALK_SPEED: f64 = 3.5;
 const ROTATE_SPEED: f64 = 90.0;
 const RUN_MULTIPLIER: f64 = 2.0;
-const MIN_DISTANCE_TO_WALL: f64 = 0.375;
+const PLAYER_RADIUS: f64 = 0.375;
+// the radius and color of the sprites put in the map with 'o'
+const PILLAR_RADIUS: f64 = 0.25;
+const PILLAR_COLOR: RGB = LIGHT_GREY;
+// the height of the sprites, relative to the walls
+const SPRITE_HEIGHT: f64 = 0.75;
+// how many times the collisions are resolved, at most, after each (partial) move
+const COLLISION_ITERATIONS: usize = 4;
 const HALF_HORIZ_FOV: f64 = 23.0;
 const WALL_HEIGHT_SCALER: f64 = 1.0;
 const MINI_MAP_WIDTH_PERCENT: i32 = 30;
+const MINI_MAP_MARGIN: i32 = 10;
+const MIN_MAP_ZOOM: f64 = 2.0;
+const MAX_MAP_ZOOM: f64 = 64.0;
+const MAP_ZOOM_STEP: f64 = 1.25;
+const MAP_FLOOR_COLOR: RGB = RGB { r: 40, g: 40, b: 40 };
+// the angle between the rays cast to explore the map, in degrees
+const EXPLORE_RAY_STEP: f64 = 0.5;
 const EPSILON: f64 = 0.001;
+const BUMP_SOUND_COOLDOWN: f64 = 0.4;
 
 // bit flags for keys
 const DO_WALK_FWD1: u32 = 1 << 0;
@@ -53,6 +67,73 @@ const WALL_SHADINGS: &[u32] = &[100, 80, 60, 80];
 
 //-------------------------------------------------------
 
+/// How the automap is shown (cycled with Tab).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutomapMode {
+    Hidden,
+    /// In a corner, over the 3D view.
+    Overlay,
+    /// Instead of the 3D view.
+    Fullscreen,
+}
+
+impl AutomapMode {
+    pub fn next(self) -> Self {
+        match self {
+            AutomapMode::Hidden => AutomapMode::Overlay,
+            AutomapMode::Overlay => AutomapMode::Fullscreen,
+            AutomapMode::Fullscreen => AutomapMode::Hidden,
+        }
+    }
+}
+
+//-------------------------------------------------------
+
+/// A round obstacle standing in the map (e.g. a pillar), painted as a billboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    /// Position of the center, in map cells.
+    pub x: f64,
+    pub y: f64,
+    /// Collision radius, in map cells.
+    pub radius: f64,
+    pub color: RGB,
+}
+
+impl Sprite {
+    pub fn new(x: f64, y: f64, radius: f64, color: RGB) -> Self {
+        Sprite { x, y, radius, color }
+    }
+}
+
+//-------------------------------------------------------
+
+/// User-adjustable settings of the [`RayCaster`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RayCasterSettings {
+    /// Walking speed, in map cells per second.
+    pub walk_speed: f64,
+    /// Rotation speed, in degrees per second.
+    pub rotate_speed: f64,
+    /// Half of the vertical field of view, in degrees.
+    pub half_fov: f64,
+    /// Master volume, 0..1.
+    pub volume: f64,
+}
+
+impl Default for RayCasterSettings {
+    fn default() -> Self {
+        RayCasterSettings {
+            walk_speed: WALK_SPEED,
+            rotate_speed: ROTATE_SPEED,
+            half_fov: HALF_HORIZ_FOV,
+            volume: 1.0,
+        }
+    }
+}
+
+//-------------------------------------------------------
+
 /// `RayCaster` engine. Must be built using [`RayCasterBuilder`].
 pub struct RayCaster {
     scr_width: i32,
@@ -60,27 +141,89 @@ pub struct RayCaster {
     map_width: i32,
     map_height: i32,
     map: Vec<u8>, // just 1s and 0s, for now (later, we can use texture indices instead of 1)
+    sprites: Vec<Sprite>,
     pos_x: f64,
     pos_y: f64,
     pos_angle: f64, // angle in DEGREES
     pdx: f64,
     pdy: f64,
-    mini_map_side: i32,
+    automap: AutomapMode,
+    /// Pixels per map cell.
+    automap_zoom: f64,
+    /// Rotate the automap with the player, so that they always look up.
+    automap_rotates: bool,
+    /// The map cells seen so far (the fog of war hides the others on the automap).
+    seen: Vec<bool>,
     view_x: i32,
     view_y: i32,
     view_width: i32,
     view_height: i32,
     keys: u32,
+    audio: Option<AudioHandle>,
+    bump_sound: SoundId,
+    bump_cooldown: f64,
+    settings: RayCasterSettings,
 }
 
 impl RayCaster {
     pub fn walk(&mut self, distance: f64) {
-        self.move_and_keep_away_from_obstacles(distance, self.pdx, self.pdy);
+        if self.move_and_slide(distance * self.pdx, distance * self.pdy) {
+            self.play_bump_sound();
+        }
     }
 
     pub fn strafe(&mut self, distance: f64) {
         // "fake" strafing by swapping pdx and pdy + changing the sign for the Y direction
-        self.move_and_keep_away_from_obstacles(distance, self.pdy, -self.pdx);
+        if self.move_and_slide(distance * self.pdy, -distance * self.pdx) {
+            self.play_bump_sound();
+        }
+    }
+
+    /// The settings currently in use.
+    #[inline]
+    pub fn settings(&self) -> &RayCasterSettings {
+        &self.settings
+    }
+
+    /// Apply new settings; the volume changes right away.
+    pub fn set_settings(&mut self, settings: &RayCasterSettings) {
+        self.settings = settings.clone();
+        if let Some(audio) = &self.audio {
+            audio.borrow_mut().set_master_volume(self.settings.volume as f32);
+        }
+    }
+
+    /// Forget all the pressed keys (e.g. when returning from a menu, which got the key releases).
+    pub fn release_keys(&mut self) {
+        self.keys = 0;
+    }
+
+    #[inline]
+    pub fn automap_mode(&self) -> AutomapMode {
+        self.automap
+    }
+
+    pub fn set_automap_mode(&mut self, mode: AutomapMode) {
+        self.automap = mode;
+    }
+
+    /// Zoom the automap in (`steps` > 0) or out (`steps` < 0).
+    pub fn zoom_automap(&mut self, steps: i32) {
+        self.automap_zoom = (self.automap_zoom * MAP_ZOOM_STEP.powi(steps)).clamp(MIN_MAP_ZOOM, MAX_MAP_ZOOM);
+    }
+
+    #[inline]
+    pub fn automap_rotates(&self) -> bool {
+        self.automap_rotates
+    }
+
+    pub fn set_automap_rotates(&mut self, rotates: bool) {
+        self.automap_rotates = rotates;
+    }
+
+    /// Has a ray reached this map cell yet?
+    pub fn is_seen(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.map_width && y < self.map_height && self.seen[(y * self.map_width + x) as usize]
     }
 
     pub fn rotate(&mut self, rotation_degrees: f64) {
@@ -89,35 +232,241 @@ impl RayCaster {
         self.pdy = (self.pos_angle * DEG_TO_RAD).sin();
     }
 
-    fn move_and_keep_away_from_obstacles(&mut self, distance: f64, pdx: f64, pdy: f64) {
-        // perform move
-        let move_x = pdx * distance;
-        let move_y = pdy * distance;
-        self.pos_x += move_x;
-        self.pos_y += move_y;
-        // compute self position + position ahead of movement direction, as integers
-        let px = self.pos_x as i32;
-        let py = self.pos_y as i32;
-        let ax = (self.pos_x + MIN_DISTANCE_TO_WALL * move_x.signum()) as i32;
-        let ay = (self.pos_y + MIN_DISTANCE_TO_WALL * move_y.signum()) as i32;
-        // check for collisions on each axis
-        if ax < 0 || ax >= self.map_width || self.map[(py * self.map_width + ax) as usize] != 0 {
-            self.pos_x -= move_x;
-        }
-        if ay < 0 || ay >= self.map_height || self.map[(ay * self.map_width + px) as usize] != 0 {
-            self.pos_y -= move_y;
-        }
-    }
-
-    fn draw_mini_map(&self, painter: &mut dyn Painter) {
-        let ms = self.mini_map_side;
-        for x in 0..self.map_width {
-            for y in 0..self.map_height {
-                let idx = (y * self.map_width + x) as usize;
-                let color = Self::get_wall_color(self.map[idx], 0);
-                painter.fill_rect(x * ms + 1, y * ms + 1, ms - 1, ms - 1, color);
+    #[inline]
+    pub fn sprites(&self) -> &[Sprite] {
+        &self.sprites
+    }
+
+    /// Move the player (a circle) by (`move_x`, `move_y`), sliding along the walls and the sprites.
+    /// Returns true if the movement was (at least partially) blocked.
+    fn move_and_slide(&mut self, move_x: f64, move_y: f64) -> bool {
+        // move in steps shorter than the radius, so that fast moves can't go through thin obstacles
+        let steps = ((move_x.abs().max(move_y.abs())) / (PLAYER_RADIUS * 0.5))
+            .ceil()
+            .max(1.0);
+        let mut blocked = false;
+        for _ in 0..(steps as usize) {
+            let (last_x, last_y) = (self.pos_x, self.pos_y);
+            self.pos_x += move_x / steps;
+            self.pos_y += move_y / steps;
+            for _ in 0..COLLISION_ITERATIONS {
+                if !self.push_out_of_obstacles() {
+                    break;
+                }
+                blocked = true;
+            }
+            if self.overlaps_obstacle(self.pos_x, self.pos_y) {
+                // stuck (e.g. squeezed between obstacles): don't move at all
+                self.pos_x = last_x;
+                self.pos_y = last_y;
+                return true;
+            }
+        }
+        blocked
+    }
+
+    /// Push the player out of the walls and the sprites they overlap, along the shortest way
+    /// (which makes them slide along what they bump into). Returns true if there was any overlap.
+    fn push_out_of_obstacles(&mut self) -> bool {
+        let mut pushed = false;
+        for (cell_x, cell_y) in self.cells_around(self.pos_x, self.pos_y) {
+            if !self.is_solid(cell_x, cell_y) {
+                continue;
+            }
+            // the point of the cell nearest to the player's center
+            let near_x = self.pos_x.clamp(cell_x as f64, (cell_x + 1) as f64);
+            let near_y = self.pos_y.clamp(cell_y as f64, (cell_y + 1) as f64);
+            pushed |= self.push_away_from(near_x, near_y, PLAYER_RADIUS);
+        }
+        for i in 0..self.sprites.len() {
+            let (x, y, radius) = (self.sprites[i].x, self.sprites[i].y, self.sprites[i].radius);
+            pushed |= self.push_away_from(x, y, PLAYER_RADIUS + radius);
+        }
+        pushed
+    }
+
+    /// Move the player's center at `min_dist` from a point, if it's nearer.
+    fn push_away_from(&mut self, x: f64, y: f64, min_dist: f64) -> bool {
+        let (dx, dy) = (self.pos_x - x, self.pos_y - y);
+        let dist = (dx * dx + dy * dy).sqrt();
+        if dist >= min_dist || dist < EPSILON * EPSILON {
+            // (when the center is right on the point, there's no way to know where to go:
+            // the move is undone by `move_and_slide`)
+            return false;
+        }
+        self.pos_x = x + dx * min_dist / dist;
+        self.pos_y = y + dy * min_dist / dist;
+        true
+    }
+
+    /// Would the player overlap a wall or a sprite there? (touching is fine)
+    fn overlaps_obstacle(&self, x: f64, y: f64) -> bool {
+        let overlaps =
+            |dx: f64, dy: f64, min_dist: f64| dx * dx + dy * dy < (min_dist - EPSILON) * (min_dist - EPSILON);
+        self.cells_around(x, y).any(|(cell_x, cell_y)| {
+            self.is_solid(cell_x, cell_y)
+                && overlaps(
+                    x - x.clamp(cell_x as f64, (cell_x + 1) as f64),
+                    y - y.clamp(cell_y as f64, (cell_y + 1) as f64),
+                    PLAYER_RADIUS,
+                )
+        }) || self
+            .sprites
+            .iter()
+            .any(|s| overlaps(x - s.x, y - s.y, PLAYER_RADIUS + s.radius))
+    }
+
+    /// The map cells the player may overlap, at this position.
+    fn cells_around(&self, x: f64, y: f64) -> impl Iterator<Item = (i32, i32)> {
+        let (x1, x2) = ((x - PLAYER_RADIUS).floor() as i32, (x + PLAYER_RADIUS).floor() as i32);
+        let (y1, y2) = ((y - PLAYER_RADIUS).floor() as i32, (y + PLAYER_RADIUS).floor() as i32);
+        (y1..=y2).flat_map(move |cell_y| (x1..=x2).map(move |cell_x| (cell_x, cell_y)))
+    }
+
+    /// Is this a wall (everything outside the map is)?
+    fn is_solid(&self, x: i32, y: i32) -> bool {
+        x < 0
+            || y < 0
+            || x >= self.map_width
+            || y >= self.map_height
+            || self.map[(y * self.map_width + x) as usize] != 0
+    }
+
+    fn play_bump_sound(&mut self) {
+        if self.bump_cooldown <= 0.0 {
+            if let Some(audio) = &self.audio {
+                audio.borrow_mut().play(self.bump_sound, 0.6, 0.0);
+                self.bump_cooldown = BUMP_SOUND_COOLDOWN;
+            }
+        }
+    }
+
+    /// Reveal the map cells crossed (or hit) by the rays of the field of view.
+    fn explore(&mut self) {
+        let chhf = self.half_view_angle();
+        let ray_count = (2.0 * chhf / EXPLORE_RAY_STEP).ceil() as usize;
+        let mut seen = std::mem::take(&mut self.seen);
+        for i in 0..=ray_count {
+            let angle = add_angles_in_degrees(self.pos_angle, -chhf + (i as f64) * 2.0 * chhf / (ray_count as f64));
+            self.trace_ray(angle, |idx| seen[idx] = true);
+        }
+        self.seen = seen;
+    }
+
+    /// Where the automap is drawn: x, y, width, height.
+    fn automap_rect(&self) -> (i32, i32, i32, i32) {
+        match self.automap {
+            AutomapMode::Fullscreen => (0, 0, self.scr_width, self.scr_height),
+            _ => {
+                let side = (self.scr_width * MINI_MAP_WIDTH_PERCENT / 100).min(self.scr_height - 2 * MINI_MAP_MARGIN);
+                (MINI_MAP_MARGIN, MINI_MAP_MARGIN, side, side)
+            }
+        }
+    }
+
+    /// The rotation of the automap: (sin, cos) of its angle.
+    fn automap_rotation(&self) -> (f64, f64) {
+        if self.automap_rotates {
+            // the player's direction points up
+            let angle = (-90.0 - self.pos_angle) * DEG_TO_RAD;
+            (angle.sin(), angle.cos())
+        } else {
+            (0.0, 1.0)
+        }
+    }
+
+    /// Screen position of a map position, on the automap (centered on the player).
+    fn automap_pos(&self, map_x: f64, map_y: f64) -> (f64, f64) {
+        let (x, y, w, h) = self.automap_rect();
+        let (sin, cos) = self.automap_rotation();
+        let (dx, dy) = (map_x - self.pos_x, map_y - self.pos_y);
+        (
+            (x as f64) + (w as f64) / 2.0 + (dx * cos - dy * sin) * self.automap_zoom,
+            (y as f64) + (h as f64) / 2.0 + (dx * sin + dy * cos) * self.automap_zoom,
+        )
+    }
+
+    fn draw_automap(&self, painter: &mut dyn Painter) {
+        let (x0, y0, w, h) = self.automap_rect();
+        painter.fill_rect(x0, y0, w, h, BLACK);
+        if self.automap == AutomapMode::Overlay {
+            painter.draw_rect(x0 - 1, y0 - 1, w + 2, h + 2, GREY);
+        }
+
+        // the map cells, one span of the same color at a time
+        // (each screen pixel is mapped back to the map, so that the map can be rotated)
+        let (sin, cos) = self.automap_rotation();
+        let (cx, cy) = ((x0 as f64) + (w as f64) / 2.0, (y0 as f64) + (h as f64) / 2.0);
+        for sy in y0..y0 + h {
+            let dy = ((sy as f64) + 0.5 - cy) / self.automap_zoom;
+            let mut span: Option<(i32, RGB)> = None;
+            for sx in x0..=x0 + w {
+                let color = if sx < x0 + w {
+                    let dx = ((sx as f64) + 0.5 - cx) / self.automap_zoom;
+                    let map_x = (self.pos_x + dx * cos + dy * sin).floor() as i32;
+                    let map_y = (self.pos_y - dx * sin + dy * cos).floor() as i32;
+                    self.automap_cell_color(map_x, map_y)
+                } else {
+                    None
+                };
+                if span.map(|s| s.1) != color {
+                    if let Some((start, span_color)) = span {
+                        painter.draw_horiz_line(start, sx - 1, sy, span_color);
+                    }
+                    span = color.map(|c| (sx, c));
+                }
             }
         }
+
+        // SOME of the rays
+        let (px, py) = self.automap_pos(self.pos_x, self.pos_y);
+        let chhf = self.half_view_angle();
+        let ray_count = self.view_width >> 4;
+        for i in 0..=ray_count {
+            let angle = add_angles_in_degrees(self.pos_angle, -chhf + (i as f64) * 2.0 * chhf / (ray_count as f64));
+            let (dist, wall, orientation) = self.compute_ray(angle);
+            let end_x = self.pos_x + (angle * DEG_TO_RAD).cos() * dist;
+            let end_y = self.pos_y + (angle * DEG_TO_RAD).sin() * dist;
+            let (ex, ey) = self.automap_pos(end_x, end_y);
+            let color = Self::get_wall_color(wall, orientation);
+            draw_clipped_line(painter, (x0, y0, w, h), (px, py), (ex, ey), color);
+        }
+
+        // the sprites in the seen cells
+        for sprite in self.sprites.iter() {
+            if self.is_seen(sprite.x as i32, sprite.y as i32) {
+                let (sx, sy) = self.automap_pos(sprite.x, sprite.y);
+                let radius = ((sprite.radius * self.automap_zoom) as i32).max(1);
+                if sx >= (x0 + radius) as f64
+                    && sy >= (y0 + radius) as f64
+                    && sx < (x0 + w - radius) as f64
+                    && sy < (y0 + h - radius) as f64
+                {
+                    painter.fill_circle(sx as i32, sy as i32, radius, sprite.color);
+                }
+            }
+        }
+
+        // the player, over the rays, and their direction
+        painter.fill_circle(px as i32, py as i32, 3, LIGHT_YELLOW);
+        let (dx, dy) = self.automap_pos(self.pos_x + self.pdx * 0.6, self.pos_y + self.pdy * 0.6);
+        draw_clipped_line(painter, (x0, y0, w, h), (px, py), (dx, dy), LIGHT_YELLOW);
+
+        if self.automap == AutomapMode::Fullscreen {
+            let help = "TAB: MAP MODE   +/-: ZOOM   R: ROTATE";
+            painter.draw_text(x0 + 10, y0 + h - 20, help, 2, GREY);
+        }
+    }
+
+    /// The color of a cell on the automap (`None` for the unknown cells).
+    fn automap_cell_color(&self, x: i32, y: i32) -> Option<RGB> {
+        if !self.is_seen(x, y) {
+            return None;
+        }
+        match self.map[(y * self.map_width + x) as usize] {
+            0 => Some(MAP_FLOOR_COLOR),
+            wall => Some(Self::get_wall_color(wall, 0)),
+        }
     }
 
     fn draw_3d_view(&self, painter: &mut dyn Painter) {
@@ -141,29 +490,26 @@ impl RayCaster {
         }
     }
 
-    fn draw_rays(&self, painter: &mut dyn Painter) {
-        // player position on the mini map
-        let ms = self.mini_map_side;
-        let px = (self.pos_x * (ms as f64)) as i32;
-        let py = (self.pos_y * (ms as f64)) as i32;
+    /// Half of the horizontal field of view, in degrees.
+    fn half_view_angle(&self) -> f64 {
         // Half FOV, corrected for screen aspect ratio
-        let chhf = HALF_HORIZ_FOV * (self.view_width as f64) / (self.view_height as f64);
+        self.settings.half_fov * (self.view_width as f64) / (self.view_height as f64)
+    }
+
+    fn draw_rays(&self, painter: &mut dyn Painter) {
+        let chhf = self.half_view_angle();
 
         // cast rays to draw the walls
         let mut fov_angle = add_angles_in_degrees(self.pos_angle, -chhf);
         let fov_step = 2.0 * chhf / (self.view_width as f64);
+        let mut wall_dists = Vec::with_capacity(self.view_width as usize);
         for x in 0..self.view_width {
             let (dist, wall, orientation) = self.compute_ray(fov_angle);
             let color = Self::get_wall_color(wall, orientation);
-            // draw SOME of the rays on the mini map
-            if (x & 0x0F) == 0 {
-                let ray_x = ((fov_angle * DEG_TO_RAD).cos() * dist * (ms as f64)) as i32;
-                let ray_y = ((fov_angle * DEG_TO_RAD).sin() * dist * (ms as f64)) as i32;
-                painter.draw_line(px, py, px + ray_x, py + ray_y, color);
-            }
             // rectify the ray distance, to avoid the "fish eye" distortion
             // - see: https://gamedev.stackexchange.com/questions/97574/how-can-i-fix-the-fisheye-distortion-in-my-raycast-renderer
             let fish_eye_rectified_dist = dist * ((self.pos_angle - fov_angle) * DEG_TO_RAD).cos();
+            wall_dists.push(fish_eye_rectified_dist);
             // draw the result of the ray cast on the 3D view
             let s = WALL_HEIGHT_SCALER / fish_eye_rectified_dist;
             if s > 0.01 {
@@ -179,23 +525,61 @@ impl RayCaster {
             fov_angle += fov_step;
         }
 
-        // after the rays, draw the player on the mini map
-        // (so it appears over the rays)
-        painter.fill_circle(px, py, 2, LIGHT_YELLOW);
-        // draw the player's direction
-        let delta_x = (self.pdx * (self.mini_map_side as f64) * 0.3) as i32;
-        let delta_y = (self.pdy * (self.mini_map_side as f64) * 0.3) as i32;
-        painter.draw_line(px, py, px + delta_x, py + delta_y, LIGHT_YELLOW);
+        self.draw_sprites(painter, &wall_dists);
+    }
+
+    /// Draw the sprites in front of the walls, from the farthest to the nearest.
+    fn draw_sprites(&self, painter: &mut dyn Painter, wall_dists: &[f64]) {
+        let chhf = self.half_view_angle();
+        let fov_step = 2.0 * chhf / (self.view_width as f64);
+        let mut visible: Vec<(f64, f64, &Sprite)> = vec![];
+        for sprite in self.sprites.iter() {
+            let (dx, dy) = (sprite.x - self.pos_x, sprite.y - self.pos_y);
+            let dist = (dx * dx + dy * dy).sqrt();
+            // the angle of the sprite, relative to the view direction, in -180..180
+            let angle = add_angles_in_degrees(dy.atan2(dx) / DEG_TO_RAD, 180.0 - self.pos_angle) - 180.0;
+            let rectified_dist = dist * (angle * DEG_TO_RAD).cos();
+            if rectified_dist > EPSILON && angle.abs() < chhf + 90.0 {
+                visible.push((rectified_dist, angle, sprite));
+            }
+        }
+        visible.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (dist, angle, sprite) in visible {
+            let center_x = (angle + chhf) / fov_step;
+            let half_width = (sprite.radius / dist).atan() / DEG_TO_RAD / fov_step;
+            let h = (WALL_HEIGHT_SCALER / dist * (self.view_height as f64)) as i32;
+            // standing on the floor
+            let bottom = (self.view_height + h) >> 1;
+            let top = bottom - (SPRITE_HEIGHT * (h as f64)) as i32;
+            let x1 = ((center_x - half_width) as i32).max(0);
+            let x2 = ((center_x + half_width) as i32).min(self.view_width - 1);
+            for x in x1..=x2 {
+                if dist < wall_dists[x as usize] {
+                    // shaded as a cylinder, lit from the left
+                    let side = ((x as f64) - center_x) / half_width;
+                    let color = shade(sprite.color, (100.0 - 40.0 * (side + 1.0) / 2.0) as u32);
+                    painter.draw_vert_line(x + self.view_x, top.max(0), bottom.min(self.view_height), color);
+                }
+            }
+        }
     }
 
     /// Computes: distance to wall, wall color index, wall orientation(0=N, 1=W, 2=S, 3=E).
     /// Thanks to [javidx9 a.k.a. olc](https://www.youtube.com/watch?v=NbSee-XM7WA)
     fn compute_ray(&self, angle: f64) -> (f64, u8, u8) {
+        self.trace_ray(angle, |_| {})
+    }
+
+    /// Same as [`compute_ray`](Self::compute_ray), also calling `visit` with the index of each map cell
+    /// the ray goes through, including the wall it hits (if it's inside the map).
+    fn trace_ray(&self, angle: f64, mut visit: impl FnMut(usize)) -> (f64, u8, u8) {
         let sin = (angle * DEG_TO_RAD).sin();
         let cos = (angle * DEG_TO_RAD).cos();
         let mut map_x = self.pos_x as i32;
         let mut map_y = self.pos_y as i32;
         let mut map_idx = map_y * self.map_width + map_x;
+        visit(map_idx as usize);
 
         let (mut dist_x, scale_x, dir_x, orient_x) = if cos > EPSILON {
             // looking RIGHT
@@ -231,6 +615,7 @@ impl RayCaster {
                 let m = if map_x < 0 || map_x >= self.map_width {
                     MAP_EDGE
                 } else {
+                    visit(map_idx as usize);
                     self.map[map_idx as usize]
                 };
                 if m != 0 {
@@ -245,6 +630,7 @@ impl RayCaster {
                 let m = if map_y < 0 || map_y >= self.map_height {
                     MAP_EDGE
                 } else {
+                    visit(map_idx as usize);
                     self.map[map_idx as usize]
                 };
                 if m != 0 {
@@ -270,6 +656,18 @@ impl RayCaster {
         }
     }
 
+    /// Returns true if the key controls the automap.
+    fn handle_automap_key(&mut self, key: &Keycode) -> bool {
+        match *key {
+            Keycode::Tab => self.automap = self.automap.next(),
+            Keycode::Equals => self.zoom_automap(1),
+            Keycode::Minus => self.zoom_automap(-1),
+            Keycode::R => self.automap_rotates = !self.automap_rotates,
+            _ => return false,
+        }
+        true
+    }
+
     #[inline]
     fn handle_key_up(&mut self, key: &Keycode) {
         for p in KEY_PAIRS {
@@ -286,11 +684,7 @@ impl RayCaster {
         } else {
             let color = WALL_COLORS[(wall as usize) % WALL_COLORS.len()];
             let shading = WALL_SHADINGS[(orientation as usize) % WALL_SHADINGS.len()];
-            RGB {
-                r: ((color.r as u32) * shading / 100) as u8,
-                g: ((color.g as u32) * shading / 100) as u8,
-                b: ((color.b as u32) * shading / 100) as u8,
-            }
+            shade(color, shading)
         }
     }
 }
@@ -305,12 +699,16 @@ impl GraphicsLoop for RayCaster {
                 keycode: Some(Keycode::X),
                 keymod: modd,
                 ..
-            } => {
-                if modd.contains(Mod::LALTMOD) {
-                    return false;
-                }
+            } if modd.contains(Mod::LALTMOD) => {
+                return false;
             }
 
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat: false,
+                ..
+            } if self.handle_automap_key(key) => {}
+
             Event::KeyDown { keycode: Some(key), .. } => {
                 self.handle_key_down(key);
             }
@@ -338,6 +736,7 @@ impl GraphicsLoop for RayCaster {
     }
 
     fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.bump_cooldown -= elapsed_time;
         let mult = if self.is_key_pressed(DO_RUN) {
             RUN_MULTIPLIER
         } else {
@@ -346,26 +745,27 @@ impl GraphicsLoop for RayCaster {
 
         // handle movement
         if self.is_key_pressed(DO_WALK_FWD) {
-            self.walk(mult * WALK_SPEED * elapsed_time);
+            self.walk(mult * self.settings.walk_speed * elapsed_time);
         }
         if self.is_key_pressed(DO_WALK_BACK) {
-            self.walk(-mult * WALK_SPEED * elapsed_time);
+            self.walk(-mult * self.settings.walk_speed * elapsed_time);
         }
 
         if self.is_key_pressed(DO_STRAFE_LEFT) {
-            self.strafe(mult * WALK_SPEED * elapsed_time);
+            self.strafe(mult * self.settings.walk_speed * elapsed_time);
         }
         if self.is_key_pressed(DO_STRAFE_RIGHT) {
-            self.strafe(-mult * WALK_SPEED * elapsed_time);
+            self.strafe(-mult * self.settings.walk_speed * elapsed_time);
         }
 
         if self.is_key_pressed(DO_ROT_LEFT) {
-            self.rotate(-mult * ROTATE_SPEED * elapsed_time);
+            self.rotate(-mult * self.settings.rotate_speed * elapsed_time);
         }
         if self.is_key_pressed(DO_ROT_RIGHT) {
-            self.rotate(mult * ROTATE_SPEED * elapsed_time);
+            self.rotate(mult * self.settings.rotate_speed * elapsed_time);
         }
 
+        self.explore();
         true
     }
 
@@ -373,9 +773,20 @@ impl GraphicsLoop for RayCaster {
         // clear the screen ...
         painter.fill_rect(0, 0, self.scr_width, self.scr_height, DARK_GREY);
         // ... and draw everything
-        self.draw_mini_map(painter);
-        self.draw_3d_view(painter);
-        self.draw_rays(painter);
+        if self.automap != AutomapMode::Fullscreen {
+            self.draw_3d_view(painter);
+            self.draw_rays(painter);
+        }
+        if self.automap != AutomapMode::Hidden {
+            self.draw_automap(painter);
+        }
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        let bump = tone(Waveform::Triangle, 70.0, 0.12, 0.8, DEFAULT_SAMPLE_RATE);
+        self.bump_sound = audio.borrow_mut().load_sound(bump);
+        audio.borrow_mut().set_master_volume(self.settings.volume as f32);
+        self.audio = Some(audio);
     }
 }
 
@@ -384,28 +795,40 @@ impl GraphicsLoop for RayCaster {
 /// Builder for [`RayCaster`].
 pub struct RayCasterBuilder(RayCaster);
 
+impl Default for RayCasterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RayCasterBuilder {
     pub fn new() -> Self {
-        RayCasterBuilder {
-            0: RayCaster {
-                scr_width: 0,
-                scr_height: 0,
-                map_width: 0,
-                map_height: 0,
-                map: vec![],
-                pos_x: 0.0,
-                pos_y: 0.0,
-                pos_angle: 270.0,
-                pdx: 0.0,
-                pdy: 0.0,
-                mini_map_side: 0,
-                view_x: 0,
-                view_y: 0,
-                view_width: 0,
-                view_height: 0,
-                keys: 0,
-            },
-        }
+        RayCasterBuilder(RayCaster {
+            scr_width: 0,
+            scr_height: 0,
+            map_width: 0,
+            map_height: 0,
+            map: vec![],
+            sprites: vec![],
+            pos_x: 0.0,
+            pos_y: 0.0,
+            pos_angle: 270.0,
+            pdx: 0.0,
+            pdy: 0.0,
+            automap: AutomapMode::Overlay,
+            automap_zoom: 0.0,
+            automap_rotates: false,
+            seen: vec![],
+            view_x: 0,
+            view_y: 0,
+            view_width: 0,
+            view_height: 0,
+            keys: 0,
+            audio: None,
+            bump_sound: 0,
+            bump_cooldown: 0.0,
+            settings: RayCasterSettings::default(),
+        })
     }
 
     #[inline]
@@ -426,6 +849,12 @@ impl RayCasterBuilder {
         self
     }
 
+    #[inline]
+    pub fn settings(&mut self, settings: &RayCasterSettings) -> &mut Self {
+        self.0.settings = settings.clone();
+        self
+    }
+
     pub fn map_from_str(&mut self, map_data: &str) -> &mut Self {
         assert!(self.0.map_width > 0);
         assert!(self.0.map_height > 0);
@@ -440,7 +869,7 @@ impl RayCasterBuilder {
             match ch {
                 'A'..='Z' => {
                     // wall
-                    self.0.map[idx as usize] = 1 + (ch as u8) - ('A' as u8);
+                    self.0.map[idx as usize] = 1 + (ch as u8) - b'A';
                     idx += 1;
                 }
                 '.' => {
@@ -448,6 +877,19 @@ impl RayCasterBuilder {
                     self.0.map[idx as usize] = 0;
                     idx += 1;
                 }
+                'o' => {
+                    // a pillar, in the middle of an empty cell
+                    let y = idx / self.0.map_width;
+                    let x = idx - y * self.0.map_width;
+                    self.0.map[idx as usize] = 0;
+                    self.0.sprites.push(Sprite::new(
+                        (x as f64) + 0.5,
+                        (y as f64) + 0.5,
+                        PILLAR_RADIUS,
+                        PILLAR_COLOR,
+                    ));
+                    idx += 1;
+                }
                 '@' => {
                     // player position
                     let y = idx / self.0.map_width;
@@ -466,6 +908,12 @@ impl RayCasterBuilder {
         self
     }
 
+    #[inline]
+    pub fn sprite(&mut self, sprite: Sprite) -> &mut Self {
+        self.0.sprites.push(sprite);
+        self
+    }
+
     pub fn build(mut self) -> RayCaster {
         // validate the data
         assert!(self.0.map_width > 0);
@@ -473,18 +921,20 @@ impl RayCasterBuilder {
         let expected_len = self.0.map_width * self.0.map_height;
         assert_eq!(expected_len as usize, self.0.map.len());
 
-        // compute automap layout data
-        let w = (self.0.scr_width * MINI_MAP_WIDTH_PERCENT / 100) / self.0.map_width;
-        let h = self.0.scr_height / self.0.map_height;
-        self.0.mini_map_side = std::cmp::min(w, h);
-
-        // the properties of the 3D view
-        self.0.view_x = self.0.mini_map_side * (self.0.map_width) + 2;
-        self.0.view_width = self.0.scr_width - self.0.view_x;
+        // the 3D view takes the whole screen (the automap is drawn over it)
+        self.0.view_x = 0;
+        self.0.view_width = self.0.scr_width;
         self.0.view_height = self.0.scr_height;
 
+        // the automap starts with the whole map in the overlay, and only what the player sees
+        let (_, _, side, _) = self.0.automap_rect();
+        let map_side = std::cmp::max(self.0.map_width, self.0.map_height);
+        self.0.automap_zoom = ((side as f64) / (map_side as f64)).clamp(MIN_MAP_ZOOM, MAX_MAP_ZOOM);
+        self.0.seen = vec![false; expected_len as usize];
+
         // pre-compute rotation data
         self.0.rotate(0.0);
+        self.0.explore();
 
         self.0
     }
@@ -493,6 +943,36 @@ impl RayCasterBuilder {
 //-------------------------------------------------------
 //  Internal stuff
 
+/// Draw a line, only inside a rectangle (x, y, width, height).
+fn draw_clipped_line(
+    painter: &mut dyn Painter,
+    rect: (i32, i32, i32, i32),
+    from: (f64, f64),
+    to: (f64, f64),
+    color: RGB,
+) {
+    let (x, y, w, h) = rect;
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as i32;
+    for i in 0..=steps {
+        let t = (i as f64) / (steps as f64);
+        let px = (from.0 + (to.0 - from.0) * t) as i32;
+        let py = (from.1 + (to.1 - from.1) * t) as i32;
+        if px >= x && py >= y && px < x + w && py < y + h {
+            painter.draw_pixel(px, py, color);
+        }
+    }
+}
+
+/// Darken a color to `percent` of its brightness.
+#[inline]
+fn shade(color: RGB, percent: u32) -> RGB {
+    RGB {
+        r: ((color.r as u32) * percent / 100) as u8,
+        g: ((color.g as u32) * percent / 100) as u8,
+        b: ((color.b as u32) * percent / 100) as u8,
+    }
+}
+
 #[inline]
 fn add_angles_in_degrees(a1: f64, a2: f64) -> f64 {
     let new_angle = a1 + a2;
@@ -504,3 +984,241 @@ fn add_angles_in_degrees(a1: f64, a2: f64) -> f64 {
         new_angle
     }
 }
+
+//-------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MAP: &str = "AAAAA A...A A.@.A A...A AAAAA";
+    const FRAME_TIME: f64 = 1.0 / 60.0;
+
+    fn build_test_raycaster() -> RayCaster {
+        let mut builder = RayCasterBuilder::new();
+        builder.scr_size(160, 80).map_size(5, 5).map_from_str(TEST_MAP);
+        builder.build()
+    }
+
+    fn replay_with_key_held(key: Keycode, frame_count: usize) -> Replay {
+        let mut replay = Replay::new(0);
+        for i in 0..frame_count {
+            let events = if i == 0 { vec![Event::key_down(key)] } else { vec![] };
+            replay.frames.push(ReplayFrame {
+                events,
+                elapsed_time: FRAME_TIME,
+            });
+        }
+        replay
+    }
+
+    #[test]
+    fn replayed_walk_stops_before_the_wall() {
+        // the player starts facing north, 1.5 units away from the wall
+        let replay = replay_with_key_held(Keycode::W, 120);
+        let mut raycaster = build_test_raycaster();
+        assert_eq!(120, run_headless(&mut raycaster, &replay.frames));
+        assert!(raycaster.pos_y >= 1.0 + PLAYER_RADIUS - EPSILON);
+        assert!(raycaster.pos_y < 1.5);
+        assert!((raycaster.pos_x - 2.5).abs() < EPSILON);
+    }
+
+    fn build_raycaster_with_map(width: i32, height: i32, map: &str) -> RayCaster {
+        let mut builder = RayCasterBuilder::new();
+        builder.scr_size(160, 80).map_size(width, height).map_from_str(map);
+        builder.build()
+    }
+
+    fn distance(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+        ((x1 - x2) * (x1 - x2) + (y1 - y2) * (y1 - y2)).sqrt()
+    }
+
+    #[test]
+    fn the_player_slides_along_the_walls() {
+        let mut raycaster = build_test_raycaster();
+        raycaster.pos_y = 1.5;
+        // diagonally into the north wall: only the move along the wall is left
+        assert!(raycaster.move_and_slide(0.5, -0.5));
+        assert!((raycaster.pos_x - 3.0).abs() < EPSILON);
+        assert!((raycaster.pos_y - (1.0 + PLAYER_RADIUS)).abs() < EPSILON);
+        // along the wall, there's nothing in the way
+        assert!(!raycaster.move_and_slide(-1.0, 0.0));
+        assert!((raycaster.pos_x - 2.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn the_player_stops_in_inner_corners() {
+        let mut raycaster = build_test_raycaster();
+        assert!(raycaster.move_and_slide(-2.0, -2.0));
+        assert!((raycaster.pos_x - (1.0 + PLAYER_RADIUS)).abs() < EPSILON);
+        assert!((raycaster.pos_y - (1.0 + PLAYER_RADIUS)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn the_player_goes_around_outer_corners() {
+        // a block in the middle of the room, with its corner at (2, 2)
+        let mut raycaster = build_raycaster_with_map(6, 6, "AAAAAA A....A A.B..A A....A A....A AAAAAA");
+        raycaster.pos_x = 1.5;
+        raycaster.pos_y = 1.5;
+        // towards the corner: the player can't clip it
+        raycaster.move_and_slide(0.4, 0.4);
+        assert!(distance(raycaster.pos_x, raycaster.pos_y, 2.0, 2.0) >= PLAYER_RADIUS - EPSILON);
+        // passing by the corner, slightly off: the player slides around it instead of snagging
+        raycaster.pos_x = 2.8;
+        raycaster.pos_y = 1.5;
+        for _ in 0..20 {
+            raycaster.move_and_slide(-0.1, 0.0);
+        }
+        assert!(raycaster.pos_x < 1.5);
+        assert!(raycaster.pos_y < 2.0 - PLAYER_RADIUS + EPSILON);
+        assert!(!raycaster.overlaps_obstacle(raycaster.pos_x, raycaster.pos_y));
+    }
+
+    #[test]
+    fn the_player_walks_through_tight_corridors() {
+        let mut raycaster = build_raycaster_with_map(9, 3, "AAAAAAAAA A@......A AAAAAAAAA");
+        // walking slightly off the corridor's direction: sliding along the side walls
+        raycaster.rotate(80.0);
+        for _ in 0..160 {
+            raycaster.walk(0.05);
+        }
+        assert!(raycaster.pos_x > 7.0);
+        assert!(raycaster.pos_y >= 1.0 + PLAYER_RADIUS - EPSILON);
+        assert!(raycaster.pos_y <= 2.0 - PLAYER_RADIUS + EPSILON);
+        assert!((raycaster.pos_x - (8.0 - PLAYER_RADIUS)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn the_player_cant_squeeze_between_diagonal_walls() {
+        let mut raycaster = build_raycaster_with_map(4, 4, "AAAA A@AA AA.A AAAA");
+        raycaster.move_and_slide(1.0, 1.0);
+        assert!(raycaster.pos_x < 2.0 - PLAYER_RADIUS + EPSILON);
+        assert!(raycaster.pos_y < 2.0 - PLAYER_RADIUS + EPSILON);
+        // even with a single big move
+        raycaster.move_and_slide(5.0, 5.0);
+        assert!(raycaster.pos_x < 2.0 && raycaster.pos_y < 2.0);
+    }
+
+    #[test]
+    fn the_player_collides_with_the_sprites() {
+        let mut builder = RayCasterBuilder::new();
+        builder
+            .scr_size(160, 80)
+            .map_size(5, 5)
+            .map_from_str("AAAAA A.o.A A.@.A A...A AAAAA")
+            .sprite(Sprite::new(1.5, 3.5, 0.1, RED));
+        let mut raycaster = builder.build();
+        assert_eq!(2, raycaster.sprites().len());
+        assert_eq!(0, raycaster.map[7]);
+
+        // straight into the pillar
+        assert!(raycaster.move_and_slide(0.0, -1.0));
+        assert!((raycaster.pos_y - (1.5 + PILLAR_RADIUS + PLAYER_RADIUS)).abs() < EPSILON);
+        // slightly off its center: sliding around it
+        raycaster.pos_x = 2.4;
+        raycaster.pos_y = 2.5;
+        for _ in 0..20 {
+            raycaster.move_and_slide(0.0, -0.1);
+        }
+        assert!(raycaster.pos_x < 2.5 - PILLAR_RADIUS);
+        assert!(distance(raycaster.pos_x, raycaster.pos_y, 2.5, 1.5) >= PILLAR_RADIUS + PLAYER_RADIUS - EPSILON);
+
+        // the sprites are painted
+        let mut frame_buffer = FrameBuffer::new(160, 80);
+        raycaster.pos_x = 2.5;
+        raycaster.pos_y = 3.0;
+        raycaster.paint(&mut frame_buffer);
+        let mut without_sprites = FrameBuffer::new(160, 80);
+        raycaster.sprites.clear();
+        raycaster.paint(&mut without_sprites);
+        assert_ne!(frame_buffer.checksum(), without_sprites.checksum());
+    }
+
+    #[test]
+    fn replays_are_deterministic() {
+        let mut replay = replay_with_key_held(Keycode::Left, 30);
+        replay.frames.extend(replay_with_key_held(Keycode::W, 30).frames);
+        let replay = Replay::from_text(&replay.to_text()).unwrap();
+
+        let mut checksums = vec![];
+        for _ in 0..2 {
+            let mut raycaster = build_test_raycaster();
+            run_headless(&mut raycaster, &replay.frames);
+            let mut frame_buffer = FrameBuffer::new(160, 80);
+            raycaster.paint(&mut frame_buffer);
+            checksums.push(frame_buffer.checksum());
+        }
+        assert_eq!(checksums[0], checksums[1]);
+    }
+
+    #[test]
+    fn the_fog_of_war_hides_what_is_behind_the_walls() {
+        let mut builder = RayCasterBuilder::new();
+        builder
+            .scr_size(160, 80)
+            .map_size(7, 3)
+            .map_from_str("AAAAAAA A.@B..A AAAAAAA");
+        let mut raycaster = builder.build();
+        // facing north: only the walls around are seen
+        assert!(raycaster.is_seen(2, 1));
+        assert!(raycaster.is_seen(2, 0));
+        assert!(!raycaster.is_seen(4, 1));
+        // facing east: the inner wall is seen, not what is behind it
+        raycaster.rotate(90.0);
+        raycaster.explore();
+        assert!(raycaster.is_seen(3, 1));
+        assert!(!raycaster.is_seen(4, 1));
+        assert!(!raycaster.is_seen(5, 1));
+        assert!(!raycaster.is_seen(-1, 1));
+    }
+
+    #[test]
+    fn the_rotating_automap_shows_the_player_looking_up() {
+        let mut raycaster = build_test_raycaster();
+        raycaster.rotate(-30.0);
+        raycaster.set_automap_rotates(true);
+        let (x, y, w, h) = raycaster.automap_rect();
+        let (cx, cy) = ((x as f64) + (w as f64) / 2.0, (y as f64) + (h as f64) / 2.0);
+        let (px, py) = raycaster.automap_pos(raycaster.pos_x, raycaster.pos_y);
+        assert!((px - cx).abs() < EPSILON && (py - cy).abs() < EPSILON);
+        let (ax, ay) = raycaster.automap_pos(raycaster.pos_x + raycaster.pdx, raycaster.pos_y + raycaster.pdy);
+        assert!((ax - cx).abs() < EPSILON);
+        assert!((ay - (cy - raycaster.automap_zoom)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn automap_keys() {
+        let mut raycaster = build_test_raycaster();
+        assert_eq!(AutomapMode::Overlay, raycaster.automap_mode());
+        raycaster.handle_event(&Event::key_down(Keycode::Tab));
+        assert_eq!(AutomapMode::Fullscreen, raycaster.automap_mode());
+        raycaster.handle_event(&Event::key_down(Keycode::Tab));
+        assert_eq!(AutomapMode::Hidden, raycaster.automap_mode());
+        // the automap keys don't move the player
+        assert_eq!(0, raycaster.keys);
+
+        for _ in 0..100 {
+            raycaster.handle_event(&Event::key_down(Keycode::Equals));
+        }
+        assert_eq!(MAX_MAP_ZOOM, raycaster.automap_zoom);
+        for _ in 0..100 {
+            raycaster.handle_event(&Event::key_down(Keycode::Minus));
+        }
+        assert_eq!(MIN_MAP_ZOOM, raycaster.automap_zoom);
+
+        raycaster.handle_event(&Event::key_down(Keycode::R));
+        assert!(raycaster.automap_rotates());
+    }
+
+    #[test]
+    fn alt_x_quits() {
+        let mut raycaster = build_test_raycaster();
+        let alt_x = Event::KeyDown {
+            keycode: Some(Keycode::X),
+            keymod: Mod::LALTMOD,
+            repeat: false,
+        };
+        assert!(raycaster.handle_event(&Event::key_down(Keycode::X)));
+        assert!(!raycaster.handle_event(&alt_x));
+    }
+}
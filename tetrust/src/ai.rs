@@ -0,0 +1,422 @@
+//! A computer player: it finds all the places the piece (or the held one) can reach, rates the resulting boards
+//! with the [`Weights`] of the board features, and plays the best one by issuing the same inputs as a human player.
+
+use crate::board::*;
+use crate::evaluation::*;
+use crate::game::*;
+use crate::generator::*;
+use crate::tetrimino::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The inputs the AI can issue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Left,
+    Right,
+    Down,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    HardDrop,
+}
+
+impl Input {
+    pub const ALL: &'static [Input] = &[
+        Input::Left,
+        Input::Right,
+        Input::Down,
+        Input::RotateCw,
+        Input::RotateCcw,
+        Input::Rotate180,
+        Input::Hold,
+        Input::HardDrop,
+    ];
+
+    /// A short name, e.g. for the replay files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Input::Left => "left",
+            Input::Right => "right",
+            Input::Down => "down",
+            Input::RotateCw => "cw",
+            Input::RotateCcw => "ccw",
+            Input::Rotate180 => "180",
+            Input::Hold => "hold",
+            Input::HardDrop => "hard",
+        }
+    }
+
+    /// The input with the given name (as returned by [`Input::name`]).
+    pub fn from_name(name: &str) -> Option<Input> {
+        Input::ALL.iter().copied().find(|i| i.name() == name)
+    }
+
+    /// Send the input to the game. Returns false if it had no effect.
+    pub fn apply(&self, game: &mut Game) -> bool {
+        match self {
+            Input::Left => game.move_piece(-1, 0),
+            Input::Right => game.move_piece(1, 0),
+            Input::Down => game.move_piece(0, 1),
+            Input::RotateCw => game.rotate_piece(Rotation::Clockwise),
+            Input::RotateCcw => game.rotate_piece(Rotation::CounterClockwise),
+            Input::Rotate180 => game.rotate_piece(Rotation::Half),
+            Input::Hold => game.hold_piece(),
+            Input::HardDrop => {
+                game.hard_drop();
+                true
+            }
+        }
+    }
+
+    /// Move the piece as the input would (rotations use the SRS kicks). Returns `None` if it's not possible.
+    fn move_piece(&self, board: &Board, tetr: &Tetrimino) -> Option<Tetrimino> {
+        let slide = |dx, dy| {
+            let mut moved = tetr.clone();
+            moved.slide(dx, dy);
+            board.fits(&moved).then_some(moved)
+        };
+        let rotate = |rotation| {
+            tetr.rotated_srs(rotation, |t| board.fits(t))
+                .map(|(t, _)| t)
+        };
+        match self {
+            Input::Left => slide(-1, 0),
+            Input::Right => slide(1, 0),
+            Input::Down => slide(0, 1),
+            Input::RotateCw => rotate(Rotation::Clockwise),
+            Input::RotateCcw => rotate(Rotation::CounterClockwise),
+            Input::Rotate180 => rotate(Rotation::Half),
+            Input::Hold | Input::HardDrop => None,
+        }
+    }
+}
+
+/// Where to put a piece, and how to get it there.
+#[derive(Clone, Debug)]
+pub struct Placement {
+    /// The piece is the one from the hold slot (or the next one, if the slot is empty).
+    pub use_hold: bool,
+    /// The piece, where it will be locked.
+    pub piece: Tetrimino,
+    /// The inputs to play, ending with a hard drop.
+    pub inputs: Vec<Input>,
+    /// How good the resulting board is.
+    pub rating: f64,
+}
+
+/// All the resting places the piece can reach from where it is, by sliding, soft dropping and rotating,
+/// with the shortest sequence of inputs for each one (ending with a hard drop).
+/// Placements which cover the same cells are only listed once.
+pub fn reachable_placements(
+    board: &Board,
+    start: &Tetrimino,
+    allow_180: bool,
+) -> Vec<(Tetrimino, Vec<Input>)> {
+    if !board.fits(start) {
+        return vec![];
+    }
+    let mut moves = vec![
+        Input::Left,
+        Input::Right,
+        Input::Down,
+        Input::RotateCw,
+        Input::RotateCcw,
+    ];
+    if allow_180 {
+        moves.push(Input::Rotate180);
+    }
+    let key = |t: &Tetrimino| (t.x(0), t.y(0), t.rotation());
+
+    // breadth-first search, so the first path to a position is the shortest one
+    let mut nodes: Vec<(Tetrimino, Option<(usize, Input)>)> = vec![(start.clone(), None)];
+    let mut visited: HashMap<(i32, i32, u8), usize> = HashMap::from([(key(start), 0)]);
+    let mut landed_cells: HashSet<Vec<(i32, i32)>> = HashSet::new();
+    let mut placements = vec![];
+    let mut next = 0;
+    while next < nodes.len() {
+        let tetr = nodes[next].0.clone();
+        if board.drop_distance(&tetr) == 0 {
+            let mut cells: Vec<(i32, i32)> = tetr.cells().collect();
+            cells.sort();
+            if landed_cells.insert(cells) {
+                placements.push((tetr.clone(), path_to(&nodes, next)));
+            }
+        }
+        for &input in moves.iter() {
+            if let Some(moved) = input.move_piece(board, &tetr) {
+                visited.entry(key(&moved)).or_insert_with(|| {
+                    nodes.push((moved, Some((next, input))));
+                    nodes.len() - 1
+                });
+            }
+        }
+        next += 1;
+    }
+    placements
+}
+
+/// The inputs leading to a node of the search (the final soft drops are replaced by the hard drop).
+fn path_to(nodes: &[(Tetrimino, Option<(usize, Input)>)], mut idx: usize) -> Vec<Input> {
+    let mut inputs = vec![];
+    while let Some((parent, input)) = nodes[idx].1 {
+        inputs.push(input);
+        idx = parent;
+    }
+    inputs.reverse();
+    while inputs.last() == Some(&Input::Down) {
+        inputs.pop();
+    }
+    inputs.push(Input::HardDrop);
+    inputs
+}
+
+/// Find the best placement for the current piece (starting from where it is), or for the `alternative` one
+/// (the held piece, or the next one if the hold slot is empty), if given.
+/// Returns `None` if no piece can be placed (i.e. the game is over).
+pub fn best_placement(
+    board: &Board,
+    current: &Tetrimino,
+    alternative: Option<Tetrimino>,
+    allow_180: bool,
+    weights: &Weights,
+) -> Option<Placement> {
+    let alternative = alternative.map(|alt| (true, spawn_position(board, alt)));
+    let options = std::iter::once((false, current.clone())).chain(alternative);
+    let mut best: Option<Placement> = None;
+    for (use_hold, start) in options {
+        for (piece, mut inputs) in reachable_placements(board, &start, allow_180) {
+            let mut after = board.clone();
+            after.lock(&piece);
+            // locking completely above the visible area ends the game
+            let rating = if piece.cells().all(|(_, y)| y < board.hidden_rows()) {
+                f64::NEG_INFINITY
+            } else {
+                let lines = after.clear_full_rows().len() as u32;
+                weights.evaluate(&after, lines)
+            };
+            if best.as_ref().is_none_or(|b| rating > b.rating) {
+                if use_hold {
+                    inputs.insert(0, Input::Hold);
+                }
+                best = Some(Placement {
+                    use_hold,
+                    piece,
+                    inputs,
+                    rating,
+                });
+            }
+        }
+    }
+    best
+}
+
+/// The AI playing a [`Game`], one input at a time.
+pub struct AiPlayer {
+    weights: Weights,
+    input_delay: f64,
+    timer: f64,
+    plan: VecDeque<Input>,
+}
+
+impl AiPlayer {
+    pub fn new(weights: Weights) -> Self {
+        AiPlayer {
+            weights,
+            input_delay: 0.05,
+            timer: 0.0,
+            plan: VecDeque::new(),
+        }
+    }
+
+    /// Seconds between two inputs (0 = place each piece right away).
+    #[inline]
+    pub fn set_input_delay(&mut self, input_delay: f64) {
+        self.input_delay = input_delay.max(0.0);
+    }
+
+    /// Forget the current plan (e.g. when the human player took over for a while).
+    #[inline]
+    pub fn reset(&mut self) {
+        self.plan.clear();
+        self.timer = 0.0;
+    }
+
+    /// Play the inputs due in the elapsed time. Call it before [`Game::update`].
+    pub fn update(&mut self, game: &mut Game, elapsed_time: f64) {
+        self.update_and_log(game, elapsed_time, &mut Vec::new());
+    }
+
+    /// Same as [`AiPlayer::update`], also adding the inputs played to `log` (e.g. to record them).
+    pub fn update_and_log(&mut self, game: &mut Game, elapsed_time: f64, log: &mut Vec<Input>) {
+        if game.is_game_over() {
+            return;
+        }
+        if self.input_delay <= 0.0 {
+            self.play_piece_and_log(game, log);
+            return;
+        }
+        self.timer += elapsed_time;
+        while self.timer >= self.input_delay && !game.is_game_over() {
+            self.timer -= self.input_delay;
+            self.step(game, log);
+        }
+    }
+
+    /// Place the current piece right away.
+    pub fn play_piece(&mut self, game: &mut Game) {
+        self.play_piece_and_log(game, &mut Vec::new());
+    }
+
+    fn play_piece_and_log(&mut self, game: &mut Game, log: &mut Vec<Input>) {
+        self.plan.clear();
+        loop {
+            let input = self.step(game, log);
+            if input.is_none() || input == Some(Input::HardDrop) || game.is_game_over() {
+                break;
+            }
+        }
+    }
+
+    /// Play the next input of the plan (making a new one if needed). Returns the input played.
+    fn step(&mut self, game: &mut Game, log: &mut Vec<Input>) -> Option<Input> {
+        if self.plan.is_empty() {
+            self.plan = self.make_plan(game).into();
+        }
+        let input = self.plan.pop_front()?;
+        log.push(input);
+        if !input.apply(game) {
+            // the gravity moved the piece in the meantime: think again
+            self.plan.clear();
+        }
+        Some(input)
+    }
+
+    fn make_plan(&self, game: &Game) -> Vec<Input> {
+        let Some(tetr) = game.piece() else {
+            return vec![];
+        };
+        let alternative = if game.can_hold() {
+            let piece = game.held_piece().or_else(|| game.next_pieces().next());
+            piece.map(|idx| game.piece_set().piece(idx))
+        } else {
+            None
+        };
+        match best_placement(
+            game.board(),
+            tetr,
+            alternative,
+            game.allow_180(),
+            &self.weights,
+        ) {
+            // after holding, the new piece may not be the expected one (in Zen mode the board can be cleared):
+            // plan its moves once it's there
+            Some(placement) if placement.use_hold => vec![Input::Hold],
+            Some(placement) => placement.inputs,
+            None => vec![Input::HardDrop],
+        }
+    }
+}
+
+/// The result of a simulated game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimulationResult {
+    pub lines: u32,
+    pub pieces: u32,
+}
+
+/// Play a whole game (without gravity, and without the game loop), until the stack reaches the top
+/// or `max_pieces` pieces have been placed.
+pub fn simulate_game(
+    weights: &Weights,
+    generator: Box<dyn PieceGenerator>,
+    max_pieces: u32,
+) -> SimulationResult {
+    let pieces = PieceSet::standard();
+    let mut queue = PieceQueue::new(generator, 1);
+    let mut board = Board::default();
+    let mut hold: Option<usize> = None;
+    let mut current = queue.next_piece();
+    let mut result = SimulationResult::default();
+    while result.pieces < max_pieces {
+        let alternative = hold.or_else(|| queue.preview().next());
+        let alternative = alternative.map(|idx| pieces.piece(idx));
+        let start = spawn_position(&board, pieces.piece(current));
+        let Some(placement) = best_placement(&board, &start, alternative, false, weights) else {
+            break;
+        };
+        if placement.use_hold && hold.replace(current).is_none() {
+            // the next piece was used
+            queue.next_piece();
+        }
+        let piece = &placement.piece;
+        board.lock(piece);
+        if piece.cells().all(|(_, y)| y < board.hidden_rows()) {
+            break;
+        }
+        result.lines += board.clear_full_rows().len() as u32;
+        result.pieces += 1;
+        current = queue.next_piece();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::*;
+
+    #[test]
+    fn placements_cover_all_columns_and_rotations() {
+        let board = Board::default();
+        // T: 8 positions when flat (x2 orientations) + 9 when vertical (x2)
+        let start = spawn_position(&board, Tetrimino::from_index(2));
+        assert_eq!(34, reachable_placements(&board, &start, false).len());
+        // O: 9 positions, the rotations don't give new ones
+        let start = spawn_position(&board, Tetrimino::from_index(0));
+        let placements = reachable_placements(&board, &start, false);
+        assert_eq!(9, placements.len());
+        assert!(placements
+            .iter()
+            .all(|(_, inputs)| inputs.last() == Some(&Input::HardDrop)));
+    }
+
+    #[test]
+    fn the_ai_completes_lines() {
+        // a well in the last column: the I piece goes there, vertically
+        let mut board = Board::default();
+        for y in (board.height() - 4)..board.height() {
+            for x in 0..board.width() - 1 {
+                board.set_cell(x, y, Some(0));
+            }
+        }
+        let start = spawn_position(&board, Tetrimino::from_index(1));
+        let placement = best_placement(&board, &start, None, false, &Weights::default()).unwrap();
+        let mut after = board.clone();
+        after.lock(&placement.piece);
+        assert_eq!(4, after.clear_full_rows().len());
+    }
+
+    #[test]
+    fn the_ai_plays_a_game_with_inputs() {
+        let mut game = Game::new(GameMode::Marathon, 1, Box::new(BagGenerator::new(7, 5)));
+        let mut ai = AiPlayer::new(Weights::default());
+        ai.set_input_delay(0.01);
+        for _ in 0..3000 {
+            ai.update(&mut game, 1.0 / 60.0);
+            game.update(1.0 / 60.0);
+        }
+        assert_ne!(Some(Outcome::ToppedOut), game.outcome());
+        assert!(game.lines() >= 10, "Only {} lines", game.lines());
+    }
+
+    #[test]
+    fn simulated_games_are_deterministic() {
+        let weights = Weights::default();
+        let first = simulate_game(&weights, Box::new(BagGenerator::new(7, 3)), 200);
+        let second = simulate_game(&weights, Box::new(BagGenerator::new(7, 3)), 200);
+        assert_eq!(first, second);
+        assert_eq!(200, first.pieces);
+        assert!(first.lines >= 60, "Only {} lines", first.lines);
+    }
+}
@@ -0,0 +1,70 @@
+//! Headless benchmark of the TetRusT AI: plays some games and reports the average lines cleared.
+//!
+//! Usage: `benchmark [games] [max pieces per game] [seed] [weights or checkpoint file]`
+//!
+//! Without a file, the default weights are used.
+
+use std::time::Instant;
+use tetrust::*;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(msg) = run(&args) {
+        println!("ERROR: {msg}");
+        println!(
+            "Usage: {} [games] [max pieces per game] [seed] [weights or checkpoint file]",
+            args[0]
+        );
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let arg = |idx: usize, default: u64| -> Result<u64, String> {
+        match args.get(idx) {
+            Some(text) => text.parse().map_err(|_| format!("invalid number: {text}")),
+            None => Ok(default),
+        }
+    };
+    let games = arg(1, 10)?;
+    let max_pieces = arg(2, 2000)? as u32;
+    let seed = arg(3, 1)?;
+
+    let weights = match args.get(4) {
+        Some(path) => load_weights(path)?,
+        None => Weights::default(),
+    };
+    let start = Instant::now();
+    let mut total_lines = 0;
+    let mut total_pieces = 0;
+    for game in 0..games {
+        let generator = BagGenerator::new(TETRIMINO_COUNT, seed + game);
+        let result = simulate_game(&weights, Box::new(generator), max_pieces);
+        println!(
+            "Game {:3}: {:6} lines, {:6} pieces",
+            game + 1,
+            result.lines,
+            result.pieces
+        );
+        total_lines += result.lines as u64;
+        total_pieces += result.pieces as u64;
+    }
+    let games = games.max(1) as f64;
+    println!(
+        "Average: {:.1} lines, {:.1} pieces ({:.2} s)",
+        total_lines as f64 / games,
+        total_pieces as f64 / games,
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Load the weights from a weights file, or the best ones from a trainer checkpoint.
+fn load_weights(path: &str) -> Result<Weights, String> {
+    Weights::load(path).or_else(|err| {
+        let checkpoint = Checkpoint::load(path).map_err(|_| err)?;
+        match checkpoint.best.first() {
+            Some(best) => Ok(Weights::from_array(best.weights)),
+            None => Err(format!("{path}: empty checkpoint")),
+        }
+    })
+}
@@ -0,0 +1,78 @@
+//! Headless genetic trainer for the weights of the TetRusT AI.
+//!
+//! Usage: `trainer [generations] [population] [checkpoint file] [seed]`
+//!
+//! If the checkpoint file exists, the training continues from it; after each generation, the best weight vectors
+//! are saved there (use it with `benchmark` to try them).
+
+use std::time::Instant;
+use tetrust::*;
+
+/// How many of the best individuals are saved in the checkpoint.
+const CHECKPOINT_SIZE: usize = 10;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(msg) = run(&args) {
+        println!("ERROR: {msg}");
+        println!(
+            "Usage: {} [generations] [population] [checkpoint file] [seed]",
+            args[0]
+        );
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let arg = |idx: usize, default: u64| -> Result<u64, String> {
+        match args.get(idx) {
+            Some(text) => text.parse().map_err(|_| format!("invalid number: {text}")),
+            None => Ok(default),
+        }
+    };
+    let generations = arg(1, 50)? as u32;
+    let config = TrainingConfig {
+        population: arg(2, 100)? as usize,
+        seed: arg(4, 1)?,
+        ..TrainingConfig::default()
+    };
+    let path = args.get(3).map_or("tetrust_ai.checkpoint", |p| p.as_str());
+
+    let checkpoint = if std::path::Path::new(path).exists() {
+        let checkpoint = Checkpoint::load(path)?;
+        println!(
+            "Continuing from generation {} ({} individuals loaded)",
+            checkpoint.generation,
+            checkpoint.best.len()
+        );
+        checkpoint
+    } else {
+        Checkpoint::default()
+    };
+    let mut trainer = Trainer::new(config, &checkpoint.best);
+    trainer.set_generation(checkpoint.generation);
+
+    for _ in 0..generations {
+        let start = Instant::now();
+        trainer.evaluate();
+        let population = trainer.population();
+        let average = population.iter().map(|i| i.fitness).sum::<f64>() / population.len() as f64;
+        println!(
+            "Generation {:4}: best {:8.0}, average {:8.1} ({:.1} s)",
+            trainer.generation() + 1,
+            population[0].fitness,
+            average,
+            start.elapsed().as_secs_f64()
+        );
+        let checkpoint = Checkpoint {
+            generation: trainer.generation() + 1,
+            best: population.iter().take(CHECKPOINT_SIZE).cloned().collect(),
+        };
+        checkpoint.save(path)?;
+        println!(
+            "Best weights:\n{}",
+            Weights::from_array(population[0].weights).to_text()
+        );
+        trainer.next_generation();
+    }
+    Ok(())
+}
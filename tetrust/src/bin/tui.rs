@@ -0,0 +1,206 @@
+//! TetRusT in a text terminal, without SDL (e.g. over SSH).
+//!
+//! Usage: `tui [mode] [start level] [piece set]`
+//!
+//! The mode is MARATHON, SPRINT, ULTRA or ZEN. Arrows: move, soft drop and rotate, Z: rotate
+//! counterclockwise, A: 180 degrees, Space: hard drop, C: hold, P: pause, R: restart, Q: quit.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tetrust::*;
+
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(msg) = run(&args) {
+        println!("ERROR: {msg}");
+        println!(
+            "Usage: {} [MARATHON|SPRINT|ULTRA|ZEN] [start level] [piece set]",
+            args[0]
+        );
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mode = match args.get(1) {
+        Some(name) => GameMode::from_name(&name.to_uppercase())
+            .ok_or_else(|| format!("unknown mode: {name}"))?,
+        None => GameMode::Marathon,
+    };
+    let start_level = match args.get(2) {
+        Some(text) => text
+            .parse::<u32>()
+            .map_err(|_| format!("invalid level: {text}"))?
+            .clamp(1, MARATHON_MAX_LEVEL),
+        None => 1,
+    };
+    let pieces = match args.get(3) {
+        Some(name) => match PieceSet::builtin(&name.to_uppercase()) {
+            Some(set) => Arc::new(set),
+            None => Arc::new(PieceSet::load(name)?),
+        },
+        None => PieceSet::standard(),
+    };
+
+    let result = {
+        let _raw_mode = RawMode::enable()?;
+        play(mode, start_level, pieces)?
+    };
+    if let Some(result) = result {
+        println!(
+            "{}: {} points, {} lines, level {}, {}",
+            result.title(),
+            result.score,
+            result.lines,
+            result.level,
+            format_time(result.time)
+        );
+    }
+    Ok(())
+}
+
+/// Play until the player quits. Returns the result of the last game, if it ended.
+fn play(
+    mode: GameMode,
+    start_level: u32,
+    pieces: Arc<PieceSet>,
+) -> Result<Option<GameResult>, String> {
+    let new_game = || {
+        let generator = Randomizer::Bag.create(pieces.len(), fastrand::u64(..));
+        Game::with_piece_set(mode, start_level, generator, pieces.clone())
+    };
+    let mut game = new_game();
+    let mut keys = TermKeys::new();
+    let mut screen = TermScreen::new(TERM_GAME_SIZE.0, TERM_GAME_SIZE.1);
+    let input = read_stdin();
+    let mut pending = Vec::new();
+    let mut paused = false;
+    let mut stdout = std::io::stdout();
+    let mut last_frame = Instant::now();
+    loop {
+        // the keys typed since the last frame
+        let mut eof = false;
+        let mut received = false;
+        loop {
+            match input.try_recv() {
+                Ok(bytes) => {
+                    pending.extend(bytes);
+                    received = true;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    eof = true;
+                    break;
+                }
+            }
+        }
+        // an escape alone for a whole frame is the Escape key, not the start of a sequence
+        let (inputs, used) = decode_input(&pending, !received);
+        pending.drain(..used);
+        for input in inputs {
+            match input {
+                TermInput::Interrupt | TermInput::Key(Keycode::Q) => return Ok(game.result()),
+                TermInput::Key(Keycode::Escape) | TermInput::Key(Keycode::P) => {
+                    paused = !paused && !game.is_game_over();
+                    game.release_keys();
+                }
+                TermInput::Key(Keycode::R) => {
+                    game = new_game();
+                    keys = TermKeys::new();
+                    paused = false;
+                }
+                TermInput::Key(key) if !paused => keys.key(&mut game, key),
+                TermInput::Key(_) => {}
+            }
+        }
+        if eof {
+            return Ok(game.result());
+        }
+
+        let now = Instant::now();
+        let elapsed_time = (now - last_frame).as_secs_f64();
+        last_frame = now;
+        if !paused {
+            keys.update(&mut game, elapsed_time);
+            game.update(elapsed_time);
+        }
+
+        screen.clear();
+        paint_game(&mut screen, &game, 0, 0);
+        let (board_x, board_y) = (13, 10);
+        if paused {
+            screen.text(board_x + 6, board_y, " PAUSED ", WHITE);
+        } else if let Some(result) = game.result() {
+            screen.text(
+                board_x + 2,
+                board_y,
+                &format!("{:^16}", result.title()),
+                WHITE,
+            );
+            screen.text(board_x + 2, board_y + 1, "R: again Q: quit", LIGHT_GREY);
+        }
+        stdout
+            .write_all(screen.render().as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| e.to_string())?;
+
+        std::thread::sleep(FRAME_DURATION.saturating_sub(now.elapsed()));
+    }
+}
+
+/// Read the standard input in a thread, so that the game loop doesn't wait for the keys.
+fn read_stdin() -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buffer = [0u8; 64];
+        while let Ok(count @ 1..) = stdin.read(&mut buffer) {
+            if sender.send(buffer[..count].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// The terminal in raw mode (no echo, no line buffering), on the alternate screen and without
+/// a cursor, until dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        Ok(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run `stty` on the terminal of the standard input.
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("cannot run stty: {e}"))?;
+    if !output.status.success() {
+        return Err("the standard input is not a terminal".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
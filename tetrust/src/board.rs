@@ -0,0 +1,384 @@
+//! The playfield: a grid of locked cells.
+
+use crate::tetrimino::*;
+
+/// Standard playfield width.
+pub const BOARD_WIDTH: i32 = 10;
+/// Standard number of visible rows.
+pub const BOARD_HEIGHT: i32 = 20;
+/// Rows above the visible area, where the pieces spawn.
+pub const HIDDEN_ROWS: i32 = 2;
+/// The content of the garbage cells, sent by the opponent in versus games.
+pub const GARBAGE_CELL: u8 = u8::MAX;
+/// The names of the cells in the text form of a board: the standard pieces, by color index.
+const CELL_NAMES: &[u8; TETRIMINO_COUNT] = b"OITSZJL";
+
+/// The playfield. Row 0 is the top (hidden) row; the visible rows start at [`Board::hidden_rows`].
+/// Each cell is either empty (`None`) or holds the color index of the piece which was locked there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    width: i32,
+    height: i32,
+    hidden_rows: i32,
+    cells: Vec<Option<u8>>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(BOARD_WIDTH, BOARD_HEIGHT, HIDDEN_ROWS)
+    }
+}
+
+impl Board {
+    /// Create an empty board, with the given visible size, plus some hidden rows above it.
+    pub fn new(width: i32, visible_height: i32, hidden_rows: i32) -> Self {
+        assert!(width > 0);
+        assert!(visible_height > 0);
+        assert!(hidden_rows >= 0);
+        let height = visible_height + hidden_rows;
+        Board {
+            width,
+            height,
+            hidden_rows,
+            cells: vec![None; (width * height) as usize],
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    /// Total height, including the hidden rows.
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn hidden_rows(&self) -> i32 {
+        self.hidden_rows
+    }
+
+    #[inline]
+    pub fn visible_height(&self) -> i32 {
+        self.height - self.hidden_rows
+    }
+
+    /// The content of a cell, or `None` if it's empty or outside the board.
+    #[inline]
+    pub fn cell(&self, x: i32, y: i32) -> Option<u8> {
+        if self.contains(x, y) {
+            self.cells[(y * self.width + x) as usize]
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn set_cell(&mut self, x: i32, y: i32, cell: Option<u8>) {
+        assert!(self.contains(x, y), "Cell outside the board: {x}, {y}");
+        self.cells[(y * self.width + x) as usize] = cell;
+    }
+
+    #[inline]
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    /// True if the cell is inside the board and empty.
+    #[inline]
+    pub fn is_free(&self, x: i32, y: i32) -> bool {
+        self.contains(x, y) && self.cells[(y * self.width + x) as usize].is_none()
+    }
+
+    /// True if the tetrimino is inside the board and doesn't overlap any locked cell.
+    pub fn fits(&self, tetr: &Tetrimino) -> bool {
+        tetr.cells().all(|(x, y)| self.is_free(x, y))
+    }
+
+    /// Lock the tetrimino into the board.
+    pub fn lock(&mut self, tetr: &Tetrimino) {
+        for (x, y) in tetr.cells() {
+            self.set_cell(x, y, Some(tetr.color_idx() as u8));
+        }
+    }
+
+    /// Move the tetrimino down, as far as it fits. Returns the number of rows it was moved.
+    pub fn drop_distance(&self, tetr: &Tetrimino) -> i32 {
+        let mut dropped = tetr.clone();
+        let mut distance = 0;
+        loop {
+            dropped.slide(0, 1);
+            if !self.fits(&dropped) {
+                return distance;
+            }
+            distance += 1;
+        }
+    }
+
+    #[inline]
+    pub fn is_row_full(&self, y: i32) -> bool {
+        (0..self.width).all(|x| self.cell(x, y).is_some())
+    }
+
+    #[inline]
+    pub fn is_row_empty(&self, y: i32) -> bool {
+        (0..self.width).all(|x| self.cell(x, y).is_none())
+    }
+
+    /// True if there are no locked cells at all.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|c| c.is_none())
+    }
+
+    /// Remove all the locked cells.
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+    }
+
+    /// Remove all the full rows, moving the rows above them down.
+    /// Returns the indices of the removed rows (as they were before removing them), top to bottom.
+    pub fn clear_full_rows(&mut self) -> Vec<i32> {
+        let full_rows: Vec<i32> = (0..self.height).filter(|&y| self.is_row_full(y)).collect();
+        if !full_rows.is_empty() {
+            let w = self.width as usize;
+            let mut kept: Vec<Option<u8>> = Vec::with_capacity(self.cells.len());
+            kept.resize(full_rows.len() * w, None);
+            for (y, row) in self.cells.chunks(w).enumerate() {
+                if !full_rows.contains(&(y as i32)) {
+                    kept.extend_from_slice(row);
+                }
+            }
+            self.cells = kept;
+        }
+        full_rows
+    }
+
+    /// A board from a picture, one line per row, top to bottom (there are no hidden rows):
+    /// `.` is an empty cell, `#` a garbage cell, and `O` `I` `T` `S` `Z` `J` `L` the cells
+    /// of the standard pieces. The blank lines and the spaces around the rows are ignored.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let width = rows.first().ok_or("empty board")?.len();
+        let mut board = Board::new(width as i32, rows.len() as i32, 0);
+        for (y, row) in rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(format!("board row {}: not {width} cells wide", y + 1));
+            }
+            for (x, ch) in row.bytes().enumerate() {
+                let cell = match ch {
+                    b'.' => None,
+                    b'#' => Some(GARBAGE_CELL),
+                    _ => match CELL_NAMES.iter().position(|&name| name == ch) {
+                        Some(color_idx) => Some(color_idx as u8),
+                        None => return Err(format!("board row {}: invalid cell", y + 1)),
+                    },
+                };
+                board.set_cell(x as i32, y as i32, cell);
+            }
+        }
+        Ok(board)
+    }
+
+    /// The picture of the board, as read by [`Board::from_text`] (including the hidden rows).
+    /// The cells of the pieces which are not standard are shown as `*`.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.cells.chunks(self.width as usize) {
+            text.extend(row.iter().map(|cell| {
+                match cell {
+                    None => '.',
+                    Some(GARBAGE_CELL) => '#',
+                    Some(idx) => CELL_NAMES
+                        .get(*idx as usize)
+                        .map_or('*', |&name| name as char),
+                }
+            }));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The number of locked cells.
+    pub fn cell_count(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    /// Push the stack up, adding `lines` rows of garbage at the bottom: full, except for the `hole_x` column.
+    /// Returns false if locked cells were pushed out of the top of the board.
+    pub fn add_garbage(&mut self, lines: i32, hole_x: i32) -> bool {
+        let lines = lines.clamp(0, self.height);
+        let overflow = (0..lines).any(|y| !self.is_row_empty(y));
+        self.cells.drain(0..(lines * self.width) as usize);
+        for _ in 0..lines {
+            for x in 0..self.width {
+                self.cells.push((x != hole_x).then_some(GARBAGE_CELL));
+            }
+        }
+        !overflow
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_do_not_fit_outside_or_over_locked_cells() {
+        let mut board = Board::new(4, 4, 0);
+        let mut tetr = Tetrimino::from_index(0); // O, in columns 1-2, rows 0-1
+        assert!(board.fits(&tetr));
+        tetr.slide(2, 0);
+        assert!(!board.fits(&tetr));
+        tetr.slide(-2, 0);
+        board.set_cell(1, 1, Some(3));
+        assert!(!board.fits(&tetr));
+    }
+
+    /// A board from its picture (which must be valid).
+    fn board(text: &str) -> Board {
+        Board::from_text(text).unwrap()
+    }
+
+    #[test]
+    fn boards_are_read_from_text() {
+        let text = "
+            ..T.
+            .TTT
+            #IIII
+        ";
+        assert!(Board::from_text(text).unwrap_err().contains("row 3"));
+        assert!(Board::from_text("..X.").is_err());
+        assert!(Board::from_text(" \n").is_err());
+        let board = board("..T.\nJTTT\n#.##");
+        assert_eq!(
+            (4, 3, 0),
+            (board.width(), board.height(), board.hidden_rows())
+        );
+        assert_eq!(Some(2), board.cell(2, 0));
+        assert_eq!(Some(5), board.cell(0, 1));
+        assert_eq!(Some(GARBAGE_CELL), board.cell(0, 2));
+        assert_eq!(None, board.cell(1, 2));
+        assert_eq!(8, board.cell_count());
+        assert_eq!("..T.\nJTTT\n#.##\n", board.to_text());
+    }
+
+    #[test]
+    fn full_rows_are_removed_and_the_rest_move_down() {
+        let mut board = board(
+            "
+            ....
+            OO.O
+            IIII
+            TTTZ
+            ",
+        );
+        assert_eq!(vec![2, 3], board.clear_full_rows());
+        assert_eq!("....\n....\n....\nOO.O\n", board.to_text());
+    }
+
+    #[test]
+    fn drop_distance_stops_on_the_stack() {
+        let mut board = Board::new(BOARD_WIDTH, BOARD_HEIGHT, 0);
+        let tetr = Tetrimino::from_index(1); // I, horizontal, on row 0
+        assert_eq!(BOARD_HEIGHT - 1, board.drop_distance(&tetr));
+        board.set_cell(2, 10, Some(1));
+        assert_eq!(9, board.drop_distance(&tetr));
+    }
+
+    /// Property: on random boards, with random pieces of all the sets, moved and rotated at
+    /// random (only where they fit), the locked pieces never overlap the locked cells, and line
+    /// clears remove exactly the full rows, keeping the other ones in order.
+    #[test]
+    fn locking_and_clearing_conserve_the_cells() {
+        let rng = fastrand::Rng::with_seed(2024);
+        let sets: Vec<PieceSet> = BUILTIN_PIECE_SETS
+            .iter()
+            .map(|(name, _)| PieceSet::builtin(name).unwrap())
+            .collect();
+        for _ in 0..300 {
+            // a random stack, mostly full rows with holes
+            let mut board = Board::new(rng.i32(4..=10), rng.i32(8..=20), HIDDEN_ROWS);
+            for y in board.height() - rng.i32(0..6)..board.height() {
+                for x in 0..board.width() {
+                    if rng.u32(0..8) > 0 {
+                        board.set_cell(x, y, Some(GARBAGE_CELL));
+                    }
+                }
+            }
+            let set = &sets[rng.usize(..sets.len())];
+            let mut tetr = set.piece(rng.usize(..set.len()));
+            tetr.slide((board.width() - tetr.shape().box_size()) / 2, 0);
+            if !board.fits(&tetr) {
+                continue;
+            }
+            for _ in 0..rng.usize(0..10) {
+                let rotation = match rng.u8(0..3) {
+                    0 => None,
+                    1 => Some(Rotation::Clockwise),
+                    _ => Some(Rotation::Half),
+                };
+                let moved = match rotation {
+                    Some(rotation) => match tetr.rotated_srs(rotation, |t| board.fits(t)) {
+                        Some((rotated, _)) => rotated,
+                        None => continue,
+                    },
+                    None => {
+                        let mut moved = tetr.clone();
+                        moved.slide(rng.i32(-1..=1), 0);
+                        moved
+                    }
+                };
+                if board.fits(&moved) {
+                    tetr = moved;
+                }
+            }
+            tetr.slide(0, board.drop_distance(&tetr));
+            assert!(board.fits(&tetr));
+            assert!(tetr.cells().all(|(x, y)| board.cell(x, y).is_none()));
+
+            let before = board.cell_count();
+            board.lock(&tetr);
+            assert_eq!(before + tetr.len(), board.cell_count());
+
+            let rows = |board: &Board| -> Vec<String> {
+                board.to_text().lines().map(str::to_string).collect()
+            };
+            let locked = board.clone();
+            let cleared = board.clear_full_rows();
+            let width = board.width() as usize;
+            assert_eq!(
+                locked.cell_count() - cleared.len() * width,
+                board.cell_count()
+            );
+            // the rows which were not full, in the same order, at the bottom
+            let kept: Vec<String> = rows(&locked)
+                .into_iter()
+                .enumerate()
+                .filter(|(y, _)| !cleared.contains(&(*y as i32)))
+                .map(|(_, row)| row)
+                .collect();
+            assert_eq!(kept, rows(&board)[cleared.len()..]);
+            assert!(rows(&board)[..cleared.len()]
+                .iter()
+                .all(|row| !row.contains(|c| c != '.')));
+        }
+    }
+
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut board = Board::new(4, 4, 0);
+        board.set_cell(1, 3, Some(2));
+        assert!(board.add_garbage(2, 3));
+        assert_eq!(Some(2), board.cell(1, 1));
+        assert!(board.is_row_empty(0));
+        assert_eq!(Some(GARBAGE_CELL), board.cell(0, 3));
+        assert_eq!(None, board.cell(3, 2));
+        assert!(!board.add_garbage(3, 0));
+    }
+}
@@ -0,0 +1,279 @@
+//! Visual effects: short animations painted with the game (line clears, hard drops, particles).
+//! They never change the game itself, and each kind can be turned off (e.g. for the players
+//! sensitive to flashes or motion).
+
+use crate::board::*;
+use crate::render::*;
+use game_framework::*;
+
+/// How long the cleared rows flash, in seconds.
+const LINE_FLASH_DURATION: f64 = 0.1;
+/// How long the rows above the cleared ones take to fall in their place, after the flash.
+const LINE_COLLAPSE_DURATION: f64 = 0.12;
+const SHAKE_DURATION: f64 = 0.15;
+/// How far a hard drop pushes the board down, in cells.
+const SHAKE_AMPLITUDE: f64 = 0.3;
+const PARTICLE_LIFETIME: f64 = 0.6;
+const PARTICLES_PER_CELL: usize = 2;
+/// In cells per second squared.
+const PARTICLE_GRAVITY: f64 = 40.0;
+
+/// Which effects are shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EffectSettings {
+    /// The cleared rows flash, then the stack above them collapses.
+    pub line_clears: bool,
+    /// Hard drops shake the board.
+    pub shake: bool,
+    /// The cleared cells burst into particles.
+    pub particles: bool,
+}
+
+impl EffectSettings {
+    /// No effects at all.
+    pub const NONE: EffectSettings = EffectSettings {
+        line_clears: false,
+        shake: false,
+        particles: false,
+    };
+
+    #[inline]
+    pub fn any(&self) -> bool {
+        self.line_clears || self.shake || self.particles
+    }
+}
+
+impl Default for EffectSettings {
+    fn default() -> Self {
+        EffectSettings {
+            line_clears: true,
+            shake: true,
+            particles: true,
+        }
+    }
+}
+
+/// A bit of a cleared cell, in board coordinates.
+#[derive(Clone, Debug)]
+struct Particle {
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+    color: RGB,
+    age: f64,
+}
+
+/// The effects running on a board.
+#[derive(Clone, Debug)]
+pub struct Effects {
+    settings: EffectSettings,
+    /// The rows cleared last (as they were before the clear), and the time since.
+    cleared_rows: Vec<i32>,
+    clear_age: f64,
+    shake_timer: f64,
+    particles: Vec<Particle>,
+    /// (the global generator is left alone: it may be seeding the games)
+    rng: fastrand::Rng,
+}
+
+impl Effects {
+    pub fn new(settings: EffectSettings) -> Self {
+        Effects {
+            settings,
+            cleared_rows: vec![],
+            clear_age: 0.0,
+            shake_timer: 0.0,
+            particles: vec![],
+            rng: fastrand::Rng::with_seed(0),
+        }
+    }
+
+    #[inline]
+    pub fn settings(&self) -> EffectSettings {
+        self.settings
+    }
+
+    /// Turn effects on or off (those turned off stop right away).
+    pub fn set_settings(&mut self, settings: EffectSettings) {
+        self.settings = settings;
+        if !settings.line_clears {
+            self.cleared_rows.clear();
+        }
+        if !settings.shake {
+            self.shake_timer = 0.0;
+        }
+        if !settings.particles {
+            self.particles.clear();
+        }
+    }
+
+    /// Start the effects of a line clear: must be called before the full rows are cleared.
+    pub fn clear_rows(&mut self, board: &Board) {
+        if !self.settings.line_clears && !self.settings.particles {
+            return;
+        }
+        let rows: Vec<i32> = (0..board.height())
+            .filter(|&y| board.is_row_full(y))
+            .collect();
+        if rows.is_empty() {
+            return;
+        }
+        if self.settings.particles {
+            for &y in rows.iter() {
+                for x in 0..board.width() {
+                    let color = piece_color(board.cell(x, y).unwrap_or_default() as usize);
+                    for _ in 0..PARTICLES_PER_CELL {
+                        self.particles.push(Particle {
+                            x: x as f64 + self.rng.f64(),
+                            y: y as f64 + self.rng.f64(),
+                            vx: self.rng.f64() * 8.0 - 4.0,
+                            vy: -2.0 - self.rng.f64() * 8.0,
+                            color,
+                            age: 0.0,
+                        });
+                    }
+                }
+            }
+        }
+        if self.settings.line_clears {
+            self.cleared_rows = rows;
+            self.clear_age = 0.0;
+        }
+    }
+
+    /// Start the shake of a hard drop (only if the piece actually dropped).
+    pub fn hard_drop(&mut self, distance: i32) {
+        if self.settings.shake && distance > 0 {
+            self.shake_timer = SHAKE_DURATION;
+        }
+    }
+
+    pub fn update(&mut self, elapsed_time: f64) {
+        if !self.cleared_rows.is_empty() {
+            self.clear_age += elapsed_time;
+            if self.clear_age >= LINE_FLASH_DURATION + LINE_COLLAPSE_DURATION {
+                self.cleared_rows.clear();
+            }
+        }
+        self.shake_timer = (self.shake_timer - elapsed_time).max(0.0);
+        for particle in self.particles.iter_mut() {
+            particle.age += elapsed_time;
+            particle.vy += PARTICLE_GRAVITY * elapsed_time;
+            particle.x += particle.vx * elapsed_time;
+            particle.y += particle.vy * elapsed_time;
+        }
+        self.particles.retain(|p| p.age < PARTICLE_LIFETIME);
+    }
+
+    /// Are there effects running?
+    pub fn is_active(&self) -> bool {
+        !self.cleared_rows.is_empty() || self.shake_timer > 0.0 || !self.particles.is_empty()
+    }
+
+    /// The view of the board, moved down by the shake.
+    pub fn shaken_view(&self, view: BoardView) -> BoardView {
+        if self.shake_timer <= 0.0 {
+            return view;
+        }
+        // a damped bounce
+        let t = 1.0 - self.shake_timer / SHAKE_DURATION;
+        let offset = SHAKE_AMPLITUDE * (1.0 - t) * (t * 3.0 * std::f64::consts::PI).cos();
+        BoardView {
+            y: view.y + (offset * view.cell_size as f64).round() as i32,
+            ..view
+        }
+    }
+
+    /// How far up a row of the board (after the clear) is painted, in pixels: the rows above
+    /// the cleared ones stay in their old place while they flash, then fall down.
+    pub fn row_offset(&self, board: &Board, y: i32, cell_size: i32) -> i32 {
+        if self.cleared_rows.is_empty() {
+            return 0;
+        }
+        // the row which was at this place before the clear
+        let mut new_y = board.height() - 1;
+        let mut fallen = 0;
+        for old_y in (0..board.height()).rev() {
+            if self.cleared_rows.contains(&old_y) {
+                continue;
+            }
+            if new_y == y {
+                fallen = y - old_y;
+                break;
+            }
+            new_y -= 1;
+        }
+        let collapse =
+            ((self.clear_age - LINE_FLASH_DURATION) / LINE_COLLAPSE_DURATION).clamp(0.0, 1.0);
+        // (accelerating, as if falling)
+        (fallen as f64 * cell_size as f64 * (1.0 - collapse * collapse)).round() as i32
+    }
+
+    /// Paint the flashing rows and the particles (after the board).
+    pub fn paint(&self, painter: &mut dyn Painter, board: &Board, view: &BoardView) {
+        if self.clear_age < LINE_FLASH_DURATION {
+            let (w, _) = view.size(board);
+            let brightness = 100 - (self.clear_age / LINE_FLASH_DURATION * 60.0) as u32;
+            for &y in self
+                .cleared_rows
+                .iter()
+                .filter(|&&y| y >= board.hidden_rows())
+            {
+                let (x, sy) = view.cell_pos(board, 0, y);
+                painter.fill_rect(x, sy, w, view.cell_size, shade(WHITE, brightness));
+            }
+        }
+        let size = (view.cell_size / 4).max(1);
+        for particle in self.particles.iter() {
+            let x = view.x + (particle.x * view.cell_size as f64) as i32;
+            let y =
+                view.y + ((particle.y - board.hidden_rows() as f64) * view.cell_size as f64) as i32;
+            let fade = 100 - (particle.age / PARTICLE_LIFETIME * 100.0) as u32;
+            painter.fill_rect(x, y, size, size, shade(particle.color, fade));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_clear_effects_end() {
+        let mut board = Board::default();
+        let bottom = board.height() - 1;
+        for x in 0..board.width() {
+            board.set_cell(x, bottom, Some(1));
+        }
+        board.set_cell(0, bottom - 1, Some(2));
+        let mut effects = Effects::new(EffectSettings::default());
+        effects.clear_rows(&board);
+        board.clear_full_rows();
+        assert!(effects.is_active());
+        // the row above the cleared one is painted one cell up, until it collapses
+        assert_eq!(10, effects.row_offset(&board, bottom, 10));
+        effects.update(LINE_FLASH_DURATION + LINE_COLLAPSE_DURATION);
+        assert_eq!(0, effects.row_offset(&board, bottom, 10));
+        effects.update(PARTICLE_LIFETIME);
+        assert!(!effects.is_active());
+    }
+
+    #[test]
+    fn disabled_effects_do_nothing() {
+        let mut board = Board::default();
+        for x in 0..board.width() {
+            board.set_cell(x, board.height() - 1, Some(1));
+        }
+        let mut effects = Effects::new(EffectSettings::NONE);
+        effects.clear_rows(&board);
+        effects.hard_drop(10);
+        assert!(!effects.is_active());
+        let view = BoardView {
+            x: 0,
+            y: 0,
+            cell_size: 10,
+        };
+        assert_eq!(view, effects.shaken_view(view));
+    }
+}
@@ -0,0 +1,258 @@
+//! Board evaluation for the AI: a weighted sum of features of the board.
+//! - see: https://codemyroad.wordpress.com/2013/04/14/tetris-ai-the-near-perfect-player/
+//! - see: https://imake.ninja/el-tetris-an-exploration-of-tetris/
+//!
+//! The weights can be saved as text, one `name value` line per feature, after a header:
+//! ```text
+//! weights 1
+//! aggregate_height -0.510066
+//! holes -0.35663
+//! ```
+
+use crate::board::*;
+
+/// How many features are evaluated.
+pub const FEATURE_COUNT: usize = 8;
+/// The names of the features, in the order of [`BoardFeatures::to_array`].
+pub const FEATURE_NAMES: [&str; FEATURE_COUNT] = [
+    "aggregate_height",
+    "max_height",
+    "holes",
+    "bumpiness",
+    "wells",
+    "row_transitions",
+    "column_transitions",
+    "lines",
+];
+
+const WEIGHTS_VERSION: u32 = 1;
+
+/// The features of a board, after a piece was locked.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BoardFeatures {
+    /// Sum of the heights of the columns.
+    pub aggregate_height: i32,
+    /// Height of the highest column.
+    pub max_height: i32,
+    /// Empty cells with a filled cell above them.
+    pub holes: i32,
+    /// Sum of the height differences between adjacent columns.
+    pub bumpiness: i32,
+    /// Sum of the depths of the wells (columns lower than both their neighbors, or the wall).
+    pub wells: i32,
+    /// Changes between filled and empty cells along the rows (the walls count as filled).
+    pub row_transitions: i32,
+    /// Changes between filled and empty cells along the columns (the floor counts as filled).
+    pub column_transitions: i32,
+    /// Lines cleared by the piece.
+    pub lines: i32,
+}
+
+impl BoardFeatures {
+    /// Compute the features of a board, after a piece was locked and `lines` were cleared.
+    pub fn of(board: &Board, lines: u32) -> Self {
+        let heights = column_heights(board);
+        let wall = board.height();
+        let wells = (0..heights.len())
+            .map(|x| {
+                let left = if x > 0 { heights[x - 1] } else { wall };
+                let right = heights.get(x + 1).copied().unwrap_or(wall);
+                (left.min(right) - heights[x]).max(0)
+            })
+            .sum();
+        let filled = |x: i32, y: i32| !board.is_free(x, y);
+        let row_transitions = (0..board.height())
+            .map(|y| {
+                (0..=board.width())
+                    .filter(|&x| filled(x - 1, y) != filled(x, y))
+                    .count() as i32
+            })
+            .sum();
+        let column_transitions = (0..board.width())
+            .map(|x| {
+                (1..=board.height())
+                    .filter(|&y| filled(x, y - 1) != filled(x, y))
+                    .count() as i32
+            })
+            .sum();
+        BoardFeatures {
+            aggregate_height: heights.iter().sum(),
+            max_height: heights.iter().copied().max().unwrap_or(0),
+            holes: count_holes(board, &heights),
+            bumpiness: heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum(),
+            wells,
+            row_transitions,
+            column_transitions,
+            lines: lines as i32,
+        }
+    }
+
+    pub fn to_array(&self) -> [f64; FEATURE_COUNT] {
+        [
+            self.aggregate_height as f64,
+            self.max_height as f64,
+            self.holes as f64,
+            self.bumpiness as f64,
+            self.wells as f64,
+            self.row_transitions as f64,
+            self.column_transitions as f64,
+            self.lines as f64,
+        ]
+    }
+}
+
+/// The weights of the board features (positive = good).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub aggregate_height: f64,
+    pub max_height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    /// Hand-tuned weights, which only use 4 of the features.
+    fn default() -> Self {
+        Weights::from_array([-0.510066, 0.0, -0.35663, -0.184483, 0.0, 0.0, 0.0, 0.760666])
+    }
+}
+
+impl Weights {
+    pub fn from_array(w: [f64; FEATURE_COUNT]) -> Self {
+        Weights {
+            aggregate_height: w[0],
+            max_height: w[1],
+            holes: w[2],
+            bumpiness: w[3],
+            wells: w[4],
+            row_transitions: w[5],
+            column_transitions: w[6],
+            lines: w[7],
+        }
+    }
+
+    pub fn to_array(&self) -> [f64; FEATURE_COUNT] {
+        [
+            self.aggregate_height,
+            self.max_height,
+            self.holes,
+            self.bumpiness,
+            self.wells,
+            self.row_transitions,
+            self.column_transitions,
+            self.lines,
+        ]
+    }
+
+    /// Rate a board, after a piece was locked and `lines` were cleared.
+    pub fn evaluate(&self, board: &Board, lines: u32) -> f64 {
+        let features = BoardFeatures::of(board, lines).to_array();
+        self.to_array()
+            .iter()
+            .zip(features)
+            .map(|(w, f)| w * f)
+            .sum()
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("weights {WEIGHTS_VERSION}\n");
+        for (name, value) in FEATURE_NAMES.iter().zip(self.to_array()) {
+            text.push_str(&format!("{name} {value}\n"));
+        }
+        text
+    }
+
+    /// Parse the weights; the missing features get a weight of 0.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, line)) if line.trim() == format!("weights {WEIGHTS_VERSION}") => {}
+            _ => return Err("not a weights file (or unsupported version)".to_string()),
+        }
+        let mut weights = [0.0; FEATURE_COUNT];
+        for (idx, line) in lines {
+            let err = |msg: &str| format!("weights line {}: {msg}", idx + 1);
+            let mut tokens = line.split_whitespace();
+            let name = tokens.next().unwrap_or_default();
+            let feature = FEATURE_NAMES
+                .iter()
+                .position(|&n| n == name)
+                .ok_or_else(|| err("unknown feature"))?;
+            let value = tokens
+                .next()
+                .and_then(|t| t.parse::<f64>().ok())
+                .filter(|v| v.is_finite());
+            weights[feature] = value.ok_or_else(|| err("invalid value"))?;
+        }
+        Ok(Weights::from_array(weights))
+    }
+}
+
+/// The height of each column: from the floor to its highest filled cell.
+pub fn column_heights(board: &Board) -> Vec<i32> {
+    (0..board.width())
+        .map(|x| {
+            let top = (0..board.height()).find(|&y| board.cell(x, y).is_some());
+            top.map_or(0, |y| board.height() - y)
+        })
+        .collect()
+}
+
+/// Empty cells below the top of their column.
+pub fn count_holes(board: &Board, heights: &[i32]) -> i32 {
+    let mut holes = 0;
+    for (x, &h) in heights.iter().enumerate() {
+        for y in (board.height() - h)..board.height() {
+            holes += board.cell(x as i32, y).is_none() as i32;
+        }
+    }
+    holes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_of_a_small_board() {
+        // ....
+        // #...
+        // #.#.
+        // ##.#
+        let mut board = Board::new(4, 4, 0);
+        for (x, y) in [(0, 1), (0, 2), (2, 2), (0, 3), (1, 3), (3, 3)] {
+            board.set_cell(x, y, Some(0));
+        }
+        let features = BoardFeatures::of(&board, 1);
+        assert_eq!(3 + 1 + 2 + 1, features.aggregate_height);
+        assert_eq!(3, features.max_height);
+        assert_eq!(1, features.holes);
+        assert_eq!(2 + 1 + 1, features.bumpiness);
+        // column 1 (between 3 and 2) and column 3 (between 2 and the wall)
+        assert_eq!(1 + 1, features.wells);
+        assert_eq!(2 + 2 + 4 + 2, features.row_transitions);
+        assert_eq!(1 + 1 + 3 + 1, features.column_transitions);
+        assert_eq!(1, features.lines);
+    }
+
+    #[test]
+    fn weights_text_roundtrip() {
+        let weights = Weights::from_array([0.5, -1.25, 3.0, 0.0, -0.1, 0.2, 1e-9, -7.0]);
+        assert_eq!(weights, Weights::from_text(&weights.to_text()).unwrap());
+        assert!(Weights::from_text("weights 1\nholes nope\n").is_err());
+        assert!(Weights::from_text("weights 1\nunknown 1\n").is_err());
+        assert!(Weights::from_text("something else\n").is_err());
+    }
+}
@@ -0,0 +1,1081 @@
+//! The game itself: a falling piece over the board, gravity, locking and line clears.
+
+use crate::board::*;
+use crate::effects::*;
+use crate::generator::*;
+use crate::handling::*;
+use crate::keymap::*;
+use crate::mode::*;
+use crate::render::*;
+use crate::scoring::*;
+use crate::tetrimino::*;
+use crate::versus::attack_lines;
+use game_framework::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// How many upcoming pieces are shown.
+pub const NEXT_QUEUE_LEN: usize = 5;
+/// How long the name of a special clear (e.g. "T-SPIN DOUBLE") is shown, in seconds.
+const CLEAR_NAME_DURATION: f64 = 2.0;
+
+/// One game of TetRusT.
+pub struct Game {
+    mode: GameMode,
+    pieces: Arc<PieceSet>,
+    board: Board,
+    piece: Option<Tetrimino>,
+    queue: PieceQueue,
+    hold: Option<usize>,
+    hold_used: bool,
+    show_ghost: bool,
+    scoring: Scoring,
+    last_move_rotation: bool,
+    last_tst_kick: bool,
+    last_clear: Option<LineClear>,
+    clear_name_timer: f64,
+    gravity_timer: f64,
+    lock_timer: f64,
+    move_resets: u32,
+    lowest_row: i32,
+    soft_drop: bool,
+    handling: Handling,
+    shift: AutoShift,
+    time: f64,
+    outcome: Option<Outcome>,
+    allow_180: bool,
+    key_map: KeyMap,
+    /// Incoming garbage, waiting to be added to the board: (lines, hole column).
+    garbage: VecDeque<(u32, i32)>,
+    /// Garbage lines to send to the opponent.
+    attack: u32,
+    /// The number of pieces the game has (`None` = endless), and how many were taken from the queue.
+    piece_limit: Option<u32>,
+    pieces_drawn: u32,
+    pieces_placed: u32,
+    view: BoardView,
+    effects: Effects,
+}
+
+impl Game {
+    /// Start a new game of the given mode, on an empty standard board, with the pieces from the given generator.
+    pub fn new(mode: GameMode, start_level: u32, generator: Box<dyn PieceGenerator>) -> Self {
+        Self::with_piece_set(mode, start_level, generator, PieceSet::standard())
+    }
+
+    /// Start a new game with another piece set (the generator must give indices of pieces of this set).
+    pub fn with_piece_set(
+        mode: GameMode,
+        start_level: u32,
+        generator: Box<dyn PieceGenerator>,
+        pieces: Arc<PieceSet>,
+    ) -> Self {
+        let mut game = Game {
+            mode,
+            pieces,
+            board: Board::default(),
+            piece: None,
+            queue: PieceQueue::new(generator, NEXT_QUEUE_LEN),
+            hold: None,
+            hold_used: false,
+            show_ghost: true,
+            scoring: Scoring::new(start_level),
+            last_move_rotation: false,
+            last_tst_kick: false,
+            last_clear: None,
+            clear_name_timer: 0.0,
+            gravity_timer: 0.0,
+            lock_timer: 0.0,
+            move_resets: 0,
+            lowest_row: 0,
+            soft_drop: false,
+            handling: Handling::default(),
+            shift: AutoShift::default(),
+            time: 0.0,
+            outcome: None,
+            allow_180: false,
+            key_map: KeyMap::default(),
+            garbage: VecDeque::new(),
+            attack: 0,
+            piece_limit: None,
+            pieces_drawn: 0,
+            pieces_placed: 0,
+            view: BoardView {
+                x: 0,
+                y: 0,
+                cell_size: 8,
+            },
+            effects: Effects::new(EffectSettings::default()),
+        };
+        let first = game.draw_piece().unwrap();
+        game.spawn_piece(first);
+        game
+    }
+
+    #[inline]
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    #[inline]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The pieces of the game.
+    #[inline]
+    pub fn piece_set(&self) -> &PieceSet {
+        &self.pieces
+    }
+
+    /// The falling piece (there is none after the game is over).
+    #[inline]
+    pub fn piece(&self) -> Option<&Tetrimino> {
+        self.piece.as_ref()
+    }
+
+    /// The upcoming pieces (as indices for [`PieceSet::piece`]).
+    #[inline]
+    pub fn next_pieces(&self) -> impl Iterator<Item = usize> + '_ {
+        self.queue.preview().take(self.pieces_left() as usize)
+    }
+
+    /// The number of pieces locked so far.
+    #[inline]
+    pub fn pieces_placed(&self) -> u32 {
+        self.pieces_placed
+    }
+
+    /// Give the game a fixed number of pieces (counting the falling one): once they are all
+    /// placed, the game ends.
+    pub fn set_piece_limit(&mut self, limit: Option<u32>) {
+        self.piece_limit = limit;
+    }
+
+    /// Start from another board (e.g. with some garbage), keeping the falling piece.
+    pub fn set_board(&mut self, board: Board) {
+        self.board = board;
+        if let Some(tetr) = self.piece.take() {
+            self.spawn_piece(tetr.color_idx());
+        }
+    }
+
+    /// The piece in the hold slot (as an index for [`PieceSet::piece`]).
+    #[inline]
+    pub fn held_piece(&self) -> Option<usize> {
+        self.hold
+    }
+
+    /// True if the falling piece can be swapped with the hold slot.
+    #[inline]
+    pub fn can_hold(&self) -> bool {
+        !self.hold_used && self.piece.is_some()
+    }
+
+    /// Where the piece would land, if it was hard dropped.
+    pub fn ghost_piece(&self) -> Option<Tetrimino> {
+        self.piece.as_ref().map(|tetr| {
+            let mut ghost = tetr.clone();
+            ghost.slide(0, self.board.drop_distance(tetr));
+            ghost
+        })
+    }
+
+    #[inline]
+    pub fn show_ghost(&self) -> bool {
+        self.show_ghost
+    }
+
+    /// Show/hide the ghost piece.
+    #[inline]
+    pub fn set_show_ghost(&mut self, show_ghost: bool) {
+        self.show_ghost = show_ghost;
+    }
+
+    #[inline]
+    pub fn level(&self) -> u32 {
+        self.scoring.level()
+    }
+
+    #[inline]
+    pub fn lines(&self) -> u32 {
+        self.scoring.lines()
+    }
+
+    #[inline]
+    pub fn score(&self) -> u64 {
+        self.scoring.score()
+    }
+
+    #[inline]
+    pub fn scoring(&self) -> &Scoring {
+        &self.scoring
+    }
+
+    /// What happened when the last piece was locked.
+    #[inline]
+    pub fn last_clear(&self) -> Option<&LineClear> {
+        self.last_clear.as_ref()
+    }
+
+    /// The name of the last special clear (one word per line), plus the combo, for a short
+    /// time after the clear.
+    pub fn clear_text(&self) -> Option<String> {
+        let clear = self.last_clear.filter(|_| self.clear_name_timer > 0.0)?;
+        let mut text = clear.name().replace(' ', "\n");
+        if clear.is_difficult() && self.scoring.back_to_back() {
+            text.insert_str(0, "B2B\n");
+        }
+        if self.scoring.combo() > 0 {
+            text.push_str(&format!("\nCOMBO {}", self.scoring.combo()));
+        }
+        Some(text)
+    }
+
+    /// Seconds played so far.
+    #[inline]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Seconds left, for the modes with a time limit.
+    pub fn time_left(&self) -> Option<f64> {
+        (self.mode == GameMode::Ultra).then(|| (ULTRA_DURATION - self.time).max(0.0))
+    }
+
+    /// Lines left to clear, for the modes with a line goal.
+    pub fn lines_left(&self) -> Option<u32> {
+        (self.mode == GameMode::Sprint).then(|| SPRINT_LINES.saturating_sub(self.lines()))
+    }
+
+    /// How the game ended (`None` while it's still in progress).
+    #[inline]
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// The final stats, once the game has ended.
+    pub fn result(&self) -> Option<GameResult> {
+        self.outcome.map(|outcome| GameResult {
+            mode: self.mode,
+            outcome,
+            score: self.score(),
+            lines: self.lines(),
+            level: self.level(),
+            time: self.time,
+        })
+    }
+
+    /// True when the game has ended, either by reaching the goal or by topping out.
+    #[inline]
+    pub fn is_game_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    #[inline]
+    pub fn view(&self) -> BoardView {
+        self.view
+    }
+
+    /// Set where the board is painted.
+    #[inline]
+    pub fn set_view(&mut self, view: BoardView) {
+        self.view = view;
+    }
+
+    /// The visual effects (line clears, shake, particles).
+    #[inline]
+    pub fn effects(&self) -> &Effects {
+        &self.effects
+    }
+
+    /// Turn the visual effects on or off.
+    pub fn set_effects(&mut self, settings: EffectSettings) {
+        self.effects.set_settings(settings);
+    }
+
+    /// Seconds it takes the piece to fall one row, at the current level (as in the Tetris guideline).
+    pub fn gravity_interval(&self) -> f64 {
+        let level = (self.level().min(20) - 1) as f64;
+        (0.8 - level * 0.007).powf(level)
+    }
+
+    /// Move the piece sideways (or down). Returns true if it was moved.
+    pub fn move_piece(&mut self, delta_x: i32, delta_y: i32) -> bool {
+        self.try_move(delta_x, delta_y)
+    }
+
+    /// Rotate the piece, with SRS wall kicks. Returns true if it was rotated.
+    pub fn rotate_piece(&mut self, rotation: Rotation) -> bool {
+        if rotation == Rotation::Half && !self.allow_180 {
+            return false;
+        }
+        let Some(tetr) = self.piece.as_ref() else {
+            return false;
+        };
+        match tetr.rotated_srs(rotation, |t| self.board.fits(t)) {
+            Some((rotated, kick)) => {
+                self.piece = Some(rotated);
+                self.piece_moved();
+                self.last_move_rotation = true;
+                self.last_tst_kick = is_tst_kick(rotation, kick);
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub fn handling(&self) -> &Handling {
+        &self.handling
+    }
+
+    #[inline]
+    pub fn set_handling(&mut self, handling: &Handling) {
+        self.handling = handling.clone();
+    }
+
+    /// A sideways key was pressed (`direction` = -1 for left, 1 for right): move once, then auto-shift.
+    pub fn press_shift(&mut self, direction: i32) {
+        let direction = self.shift.press(direction);
+        self.move_piece(direction, 0);
+    }
+
+    /// A sideways key was released.
+    #[inline]
+    pub fn release_shift(&mut self, direction: i32) {
+        self.shift.release(direction);
+    }
+
+    /// Forget the held keys (e.g. after pausing the game).
+    pub fn release_keys(&mut self) {
+        self.shift.release_all();
+        self.soft_drop = false;
+    }
+
+    #[inline]
+    pub fn allow_180(&self) -> bool {
+        self.allow_180
+    }
+
+    /// Enable/disable the 180 degree rotations.
+    #[inline]
+    pub fn set_allow_180(&mut self, allow_180: bool) {
+        self.allow_180 = allow_180;
+    }
+
+    /// Turn soft dropping on/off (the piece falls faster while it's on).
+    #[inline]
+    pub fn set_soft_drop(&mut self, soft_drop: bool) {
+        self.soft_drop = soft_drop;
+    }
+
+    #[inline]
+    pub fn key_map(&self) -> &KeyMap {
+        &self.key_map
+    }
+
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = key_map;
+    }
+
+    /// Queue garbage from the opponent. It is added to the board when the next piece locks without clearing lines
+    /// (clearing lines cancels it first).
+    pub fn receive_garbage(&mut self, lines: u32, hole_x: i32) {
+        if lines > 0 {
+            self.garbage.push_back((lines, hole_x));
+        }
+    }
+
+    /// The number of incoming garbage lines, not added to the board yet.
+    pub fn pending_garbage(&self) -> u32 {
+        self.garbage.iter().map(|&(lines, _)| lines).sum()
+    }
+
+    /// The garbage lines to send to the opponent (they are only returned once).
+    pub fn take_attack(&mut self) -> u32 {
+        std::mem::take(&mut self.attack)
+    }
+
+    /// Drop the piece all the way down and lock it.
+    pub fn hard_drop(&mut self) {
+        if let Some(tetr) = self.piece.as_mut() {
+            let distance = self.board.drop_distance(tetr);
+            if distance > 0 {
+                tetr.slide(0, distance);
+                self.last_move_rotation = false;
+            }
+            self.scoring.hard_drop(distance);
+            self.effects.hard_drop(distance);
+            self.lock_piece();
+        }
+    }
+
+    /// Start an action, as when its key is pressed.
+    pub fn press(&mut self, action: Action) {
+        match action {
+            Action::Left => self.press_shift(-1),
+            Action::Right => self.press_shift(1),
+            Action::SoftDrop => self.set_soft_drop(true),
+            Action::RotateCw => {
+                self.rotate_piece(Rotation::Clockwise);
+            }
+            Action::RotateCcw => {
+                self.rotate_piece(Rotation::CounterClockwise);
+            }
+            Action::Rotate180 => {
+                self.rotate_piece(Rotation::Half);
+            }
+            Action::HardDrop => self.hard_drop(),
+            Action::Hold => {
+                self.hold_piece();
+            }
+        }
+    }
+
+    /// End an action, as when its key is released (only the moves and the soft drop last).
+    pub fn release(&mut self, action: Action) {
+        match action {
+            Action::Left => self.release_shift(-1),
+            Action::Right => self.release_shift(1),
+            Action::SoftDrop => self.set_soft_drop(false),
+            _ => {}
+        }
+    }
+
+    /// Put the piece in the hold slot, and continue with the piece which was there (or the next one).
+    /// This can only be done once per piece. Returns true if it was done.
+    pub fn hold_piece(&mut self) -> bool {
+        if self.hold_used || self.is_game_over() {
+            return false;
+        }
+        let Some(tetr) = self.piece.take() else {
+            return false;
+        };
+        if self.hold.is_none() && self.pieces_left() == 0 {
+            self.piece = Some(tetr);
+            return false;
+        }
+        let next = match self.hold.replace(tetr.color_idx()) {
+            Some(held) => held,
+            None => self.draw_piece().unwrap(),
+        };
+        self.spawn_piece(next);
+        self.hold_used = true;
+        true
+    }
+
+    /// Advance the game by the given time: gravity and locking.
+    pub fn update(&mut self, elapsed_time: f64) {
+        self.effects.update(elapsed_time);
+        if self.is_game_over() {
+            return;
+        }
+        self.time += elapsed_time;
+        self.clear_name_timer -= elapsed_time;
+        if self.check_completed() {
+            return;
+        }
+
+        // auto-shift
+        let direction = self.shift.direction();
+        for _ in 0..self.shift.update(elapsed_time, &self.handling) {
+            if !self.move_piece(direction, 0) {
+                break;
+            }
+        }
+
+        if self.is_on_ground() {
+            self.gravity_timer = 0.0;
+            self.lock_timer += elapsed_time;
+            if self.lock_timer >= self.handling.lock_delay {
+                self.lock_piece();
+            }
+        } else {
+            let factor = if self.soft_drop {
+                self.handling.soft_drop_factor.max(1.0)
+            } else {
+                1.0
+            };
+            let interval = self.gravity_interval() / factor;
+            self.gravity_timer += elapsed_time;
+            while self.gravity_timer >= interval {
+                self.gravity_timer -= interval;
+                if !self.move_piece(0, 1) {
+                    self.gravity_timer = 0.0;
+                    break;
+                }
+                if self.soft_drop {
+                    self.scoring.soft_drop(1);
+                }
+            }
+        }
+    }
+
+    //-------------------------------------------------------
+    //  Internal stuff
+
+    fn is_on_ground(&self) -> bool {
+        self.piece
+            .as_ref()
+            .is_some_and(|tetr| self.board.drop_distance(tetr) == 0)
+    }
+
+    /// Move a copy of the piece, and keep it only if the result fits.
+    fn try_move(&mut self, delta_x: i32, delta_y: i32) -> bool {
+        let Some(tetr) = self.piece.as_ref() else {
+            return false;
+        };
+        let mut moved = tetr.clone();
+        moved.slide(delta_x, delta_y);
+        if self.board.fits(&moved) {
+            self.piece = Some(moved);
+            self.piece_moved();
+            self.last_move_rotation = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moving the piece on the ground gives it more time before locking, but only a limited number of times,
+    /// unless it reaches a lower row.
+    fn piece_moved(&mut self) {
+        let Some(tetr) = self.piece.as_ref() else {
+            return;
+        };
+        let bottom = tetr.cells().map(|(_, y)| y).max().unwrap();
+        if bottom > self.lowest_row {
+            self.lowest_row = bottom;
+            self.move_resets = 0;
+            self.lock_timer = 0.0;
+        } else if self.lock_timer > 0.0 && self.move_resets < self.handling.move_reset_limit {
+            self.move_resets += 1;
+            self.lock_timer = 0.0;
+        }
+    }
+
+    /// The end of the game, because of the mode's goal or of a top out.
+    fn finish(&mut self, outcome: Outcome) {
+        self.outcome = Some(outcome);
+        self.piece = None;
+        self.soft_drop = false;
+    }
+
+    /// Finish the game if the goal of the mode was reached. Returns true if it was.
+    fn check_completed(&mut self) -> bool {
+        let completed = self
+            .mode
+            .is_completed(self.lines(), self.level(), self.time);
+        if completed {
+            self.finish(Outcome::Completed);
+        }
+        completed
+    }
+
+    /// The stack reached the top: the game is over, except in Zen mode, which just starts from an empty board.
+    /// Returns true if the game can go on.
+    fn top_out(&mut self) -> bool {
+        if self.mode.can_top_out() {
+            self.finish(Outcome::ToppedOut);
+            false
+        } else {
+            self.board.clear();
+            true
+        }
+    }
+
+    /// The number of pieces still in the queue.
+    fn pieces_left(&self) -> u32 {
+        self.piece_limit
+            .map_or(u32::MAX, |limit| limit.saturating_sub(self.pieces_drawn))
+    }
+
+    /// Take the next piece from the queue, if there are any left.
+    fn draw_piece(&mut self) -> Option<usize> {
+        if self.pieces_left() == 0 {
+            return None;
+        }
+        self.pieces_drawn += 1;
+        Some(self.queue.next_piece())
+    }
+
+    fn spawn_piece(&mut self, piece_idx: usize) {
+        // "block out": no room for the new piece
+        let piece = self.pieces.piece(piece_idx);
+        if !self.board.fits(&spawn_position(&self.board, piece.clone())) && !self.top_out() {
+            return;
+        }
+        let tetr = spawn_position(&self.board, piece);
+        self.lowest_row = tetr.cells().map(|(_, y)| y).max().unwrap();
+        self.piece = Some(tetr);
+        self.gravity_timer = 0.0;
+        self.lock_timer = 0.0;
+        self.move_resets = 0;
+        self.last_move_rotation = false;
+        self.last_tst_kick = false;
+    }
+
+    /// Use an attack to cancel the incoming garbage first, the rest is sent to the opponent.
+    fn send_attack(&mut self, mut attack: u32) {
+        while attack > 0 {
+            let Some(front) = self.garbage.front_mut() else {
+                break;
+            };
+            let cancelled = attack.min(front.0);
+            front.0 -= cancelled;
+            attack -= cancelled;
+            if front.0 == 0 {
+                self.garbage.pop_front();
+            }
+        }
+        self.attack += attack;
+    }
+
+    /// Add the incoming garbage to the board. Returns false if it pushed the stack out of the top.
+    fn add_garbage(&mut self) -> bool {
+        while let Some((lines, hole_x)) = self.garbage.pop_front() {
+            if !self.board.add_garbage(lines as i32, hole_x) && !self.top_out() {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn lock_piece(&mut self) {
+        let Some(tetr) = self.piece.take() else {
+            return;
+        };
+        let tspin = detect_tspin(
+            &self.board,
+            &tetr,
+            self.last_move_rotation,
+            self.last_tst_kick,
+        );
+        self.board.lock(&tetr);
+        self.pieces_placed += 1;
+        if tetr.cells().all(|(_, y)| y < self.board.hidden_rows()) {
+            // "lock out": the piece was locked completely above the visible area
+            if !self.top_out() {
+                return;
+            }
+        }
+        self.effects.clear_rows(&self.board);
+        let cleared = self.board.clear_full_rows();
+        let clear = LineClear {
+            lines: cleared.len() as u32,
+            tspin,
+            perfect_clear: !cleared.is_empty() && self.board.is_empty(),
+        };
+        let back_to_back = clear.is_difficult() && self.scoring.back_to_back();
+        self.scoring.piece_locked(&clear);
+        if clear.lines > 0 {
+            self.send_attack(attack_lines(&clear, self.scoring.combo(), back_to_back));
+        } else if !self.add_garbage() {
+            return;
+        }
+        if clear.lines > 0 || clear.tspin != TSpin::None {
+            self.clear_name_timer = CLEAR_NAME_DURATION;
+        }
+        self.last_clear = Some(clear);
+        if self.check_completed() {
+            return;
+        }
+        self.hold_used = false;
+        // without pieces in the queue, the held one is the last
+        match self.draw_piece().or_else(|| self.hold.take()) {
+            Some(next) => self.spawn_piece(next),
+            None => self.finish(Outcome::OutOfPieces),
+        }
+    }
+}
+
+/// Where a new piece appears: centered at the top, and one row lower if there is room
+/// (if the piece doesn't fit there, the game is over).
+pub fn spawn_position(board: &Board, piece: Tetrimino) -> Tetrimino {
+    let mut tetr = piece;
+    tetr.slide((board.width() - tetr.shape().box_size()) / 2, 0);
+    let mut dropped = tetr.clone();
+    dropped.slide(0, 1);
+    if board.fits(&tetr) && board.fits(&dropped) {
+        dropped
+    } else {
+        tetr
+    }
+}
+
+impl GraphicsLoop for Game {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat,
+                ..
+            } => {
+                if let Some(action) = self.key_map.action(*key).filter(|_| !repeat) {
+                    self.press(action);
+                }
+            }
+            Event::KeyUp {
+                keycode: Some(key), ..
+            } => {
+                if let Some(action) = self.key_map.action(*key) {
+                    self.release(action);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        let board = &self.board;
+        let view = &self.effects.shaken_view(self.view);
+        view.paint_board_shifted(painter, board, |y| {
+            self.effects.row_offset(board, y, view.cell_size)
+        });
+        if let Some(tetr) = self.piece.as_ref() {
+            let color = piece_color(tetr.color_idx());
+            if self.show_ghost {
+                if let Some(ghost) = self.ghost_piece() {
+                    view.paint_ghost(painter, board, &ghost, color);
+                }
+            }
+            view.paint_piece(painter, board, tetr, color);
+        }
+        self.effects.paint(painter, board, view);
+        let view = &self.view;
+
+        // the incoming garbage, as a bar growing from the bottom, on the left of the board
+        let (w, h) = view.size(board);
+        let garbage = (self.pending_garbage() as i32 * view.cell_size).min(h);
+        if garbage > 0 {
+            painter.fill_rect(view.x - 6, view.y + h - garbage, 3, garbage, LIGHT_RED);
+        }
+
+        // the side panels: hold + stats on the left, next queue on the right
+        let small_cell = (view.cell_size * 2 / 3).max(2);
+        let panel_width = small_cell * 4 + 8;
+        let left_x = view.x - panel_width - 8;
+        painter.draw_text(left_x, view.y, "HOLD", 2, LIGHT_GREY);
+        if let Some(held) = self.hold {
+            let color = if self.hold_used {
+                GREY
+            } else {
+                piece_color(held)
+            };
+            let piece = self.pieces.piece(held);
+            paint_preview(painter, &piece, left_x, view.y + 16, small_cell, color);
+        }
+        // the name of the last special clear (one word per line), plus the combo
+        if let Some(text) = self.clear_text() {
+            painter.draw_text(left_x, view.y + 16 + small_cell * 3, &text, 2, LIGHT_YELLOW);
+        }
+
+        let right_x = view.x + w + 8;
+        painter.draw_text(right_x, view.y, "NEXT", 2, LIGHT_GREY);
+        let mut y = view.y + 16;
+        for next in self.next_pieces() {
+            let piece = self.pieces.piece(next);
+            paint_preview(painter, &piece, right_x, y, small_cell, piece_color(next));
+            y += small_cell * 3;
+        }
+        let lines = match self.lines_left() {
+            Some(left) => format!("LEFT\n{left}"),
+            None => format!("LINES\n{}", self.lines()),
+        };
+        let time = format_time(self.time_left().unwrap_or(self.time));
+        let stats = format!(
+            "SCORE\n{}\n\nLEVEL\n{}\n\n{lines}\n\nTIME\n{time}",
+            self.score(),
+            self.level(),
+        );
+        painter.draw_text(right_x, y + 8, &stats, 2, LIGHT_GREY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game() -> Game {
+        Game::new(GameMode::Marathon, 1, Box::new(BagGenerator::new(7, 1)))
+    }
+
+    #[test]
+    fn hard_drop_locks_the_piece_on_the_floor() {
+        let mut game = new_game();
+        game.hard_drop();
+        let bottom = game.board().height() - 1;
+        assert!(!game.board().is_row_empty(bottom));
+        assert!(game.piece().is_some());
+        assert!(!game.is_game_over());
+    }
+
+    #[test]
+    fn the_piece_locks_after_the_lock_delay() {
+        let mut game = new_game();
+        for _ in 0..300 {
+            game.update(0.1);
+        }
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn moving_on_the_ground_resets_the_lock_delay() {
+        let mut game = new_game();
+        let distance = game.board().drop_distance(game.piece().unwrap());
+        game.move_piece(0, distance);
+        let lock_delay = game.handling().lock_delay;
+        game.update(lock_delay * 0.75);
+        assert!(game.move_piece(1, 0) || game.move_piece(-1, 0));
+        game.update(lock_delay * 0.75);
+        assert!(game.board().is_empty());
+        game.update(lock_delay * 0.5);
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn lock_delay_resets_are_limited() {
+        let mut game = new_game();
+        game.set_handling(&Handling {
+            move_reset_limit: 3,
+            ..Handling::default()
+        });
+        let distance = game.board().drop_distance(game.piece().unwrap());
+        game.move_piece(0, distance);
+        let lock_delay = game.handling().lock_delay;
+        let mut direction = 1;
+        for _ in 0..3 {
+            game.update(lock_delay * 0.5);
+            if !game.move_piece(direction, 0) {
+                direction = -direction;
+                assert!(game.move_piece(direction, 0));
+            }
+        }
+        assert!(game.board().is_empty());
+        // no more resets: the piece locks, even if it keeps moving
+        game.update(lock_delay * 0.5);
+        game.move_piece(-direction, 0);
+        game.update(lock_delay * 0.6);
+        assert!(!game.board().is_empty());
+    }
+
+    #[test]
+    fn held_keys_shift_the_piece_to_the_wall() {
+        let mut game = new_game();
+        game.set_handling(&Handling {
+            das: 0.1,
+            arr: 0.0,
+            ..Handling::default()
+        });
+        game.handle_event(&Event::key_down(Keycode::Left));
+        game.update(0.05);
+        let x = game.piece().unwrap().cells().map(|(x, _)| x).min().unwrap();
+        assert!(x > 0);
+        game.update(0.06);
+        let x = game.piece().unwrap().cells().map(|(x, _)| x).min().unwrap();
+        assert_eq!(0, x);
+    }
+
+    #[test]
+    fn rotations_kick_off_the_walls() {
+        let mut game = new_game();
+        // an I piece, vertical, against the left wall
+        game.piece = Some(Tetrimino::from_index(1));
+        game.rotate_piece(Rotation::CounterClockwise);
+        while game.move_piece(-1, 0) {}
+        assert_eq!(0, game.piece().unwrap().x(0));
+        // rotating back to horizontal would go through the wall, without the kicks
+        assert!(game.rotate_piece(Rotation::Clockwise));
+        let tetr = game.piece().unwrap();
+        assert_eq!(0, tetr.rotation());
+        assert!(tetr.cells().all(|(x, _)| x >= 0));
+    }
+
+    #[test]
+    fn half_rotations_must_be_enabled() {
+        let mut game = new_game();
+        game.piece = Some(Tetrimino::from_index(2));
+        game.move_piece(0, 5);
+        assert!(!game.rotate_piece(Rotation::Half));
+        game.set_allow_180(true);
+        assert!(game.rotate_piece(Rotation::Half));
+        assert_eq!(2, game.piece().unwrap().rotation());
+    }
+
+    #[test]
+    fn pieces_come_from_the_queue() {
+        let generator = SequenceGenerator::new(&[1, 2, 3, 4, 5, 6, 0]);
+        let mut game = Game::new(GameMode::Marathon, 1, Box::new(generator));
+        assert_eq!('I', game.piece().unwrap().name());
+        assert_eq!(vec![2, 3, 4, 5, 6], game.next_pieces().collect::<Vec<_>>());
+        game.hard_drop();
+        assert_eq!('T', game.piece().unwrap().name());
+        assert_eq!(vec![3, 4, 5, 6, 0], game.next_pieces().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn hold_swaps_the_piece_once_per_drop() {
+        let generator = SequenceGenerator::new(&[1, 2, 3, 4, 5, 6, 0]);
+        let mut game = Game::new(GameMode::Marathon, 1, Box::new(generator));
+        assert!(game.hold_piece());
+        assert_eq!(Some(1), game.held_piece());
+        assert_eq!('T', game.piece().unwrap().name());
+        assert!(!game.hold_piece());
+        game.hard_drop();
+        assert!(game.hold_piece());
+        assert_eq!(Some(3), game.held_piece());
+        assert_eq!('I', game.piece().unwrap().name());
+    }
+
+    #[test]
+    fn ghost_piece_is_on_the_floor() {
+        let game = new_game();
+        let ghost = game.ghost_piece().unwrap();
+        assert_eq!(0, game.board().drop_distance(&ghost));
+        assert_eq!(game.piece().unwrap().x(0), ghost.x(0));
+    }
+
+    #[test]
+    fn clearing_lines_scores_points() {
+        // an I piece completing the bottom row
+        let generator = SequenceGenerator::new(&[1]);
+        let mut game = Game::new(GameMode::Marathon, 1, Box::new(generator));
+        let bottom = game.board().height() - 1;
+        for x in 4..BOARD_WIDTH {
+            game.board.set_cell(x, bottom, Some(0));
+        }
+        while game.move_piece(-1, 0) {}
+        let distance = game.board().drop_distance(game.piece().unwrap());
+        game.hard_drop();
+        assert_eq!(1, game.lines());
+        // hard drop + single + perfect clear
+        assert_eq!(2 * distance as u64 + 100 + 800, game.score());
+        assert_eq!(1, game.last_clear().unwrap().lines);
+        assert!(game.last_clear().unwrap().perfect_clear);
+        assert!(game.board().is_row_empty(bottom));
+    }
+
+    #[test]
+    fn attacks_cancel_the_incoming_garbage() {
+        let generator = SequenceGenerator::new(&[1]);
+        let mut game = Game::new(GameMode::Versus, 1, Box::new(generator));
+        game.receive_garbage(3, 5);
+        game.receive_garbage(2, 6);
+        let bottom = game.board().height() - 1;
+        // three singles (not perfect clears): the third one is a combo of 2, which sends 1 line
+        game.board.set_cell(9, bottom - 10, Some(0));
+        for _ in 0..3 {
+            for x in 4..BOARD_WIDTH {
+                game.board.set_cell(x, bottom, Some(0));
+            }
+            while game.move_piece(-1, 0) {}
+            game.hard_drop();
+        }
+        assert_eq!(2, game.scoring().combo());
+        assert_eq!(4, game.pending_garbage());
+        assert_eq!(0, game.take_attack());
+        // a piece locked without clearing lines brings the garbage in
+        game.hard_drop();
+        assert_eq!(0, game.pending_garbage());
+        assert_eq!(Some(1), game.board().cell(3, bottom - 4));
+        assert_eq!(None, game.board().cell(5, bottom - 2));
+        assert_eq!(Some(GARBAGE_CELL), game.board().cell(6, bottom - 2));
+        assert_eq!(None, game.board().cell(6, bottom));
+    }
+
+    /// Property: whatever the player does, the falling piece never overlaps the locked cells,
+    /// and each piece adds its cells to the board, which line clears remove.
+    #[test]
+    fn random_play_keeps_the_board_consistent() {
+        let rng = fastrand::Rng::with_seed(7);
+        for _ in 0..20 {
+            let mut game = Game::new(
+                GameMode::Marathon,
+                1,
+                Box::new(BagGenerator::new(7, rng.u64(..))),
+            );
+            game.set_allow_180(true);
+            while !game.is_game_over() && game.pieces_placed() < 100 {
+                match rng.u8(0..8) {
+                    0 => game.hard_drop(),
+                    1 => {
+                        game.hold_piece();
+                    }
+                    2 => {
+                        game.rotate_piece(Rotation::Clockwise);
+                    }
+                    3 => {
+                        game.rotate_piece(Rotation::Half);
+                    }
+                    4 => game.set_soft_drop(rng.bool()),
+                    _ => {
+                        game.move_piece(rng.i32(-1..=1), 0);
+                    }
+                }
+                game.update(rng.f64() * 0.2);
+                if let Some(piece) = game.piece() {
+                    assert!(game.board().fits(piece));
+                }
+                let placed = game.pieces_placed() as usize * 4;
+                let cleared = game.lines() as usize * BOARD_WIDTH as usize;
+                assert_eq!(placed - cleared, game.board().cell_count());
+            }
+        }
+    }
+
+    #[test]
+    fn stacking_to_the_top_ends_the_game() {
+        let mut game = new_game();
+        for _ in 0..100 {
+            game.hard_drop();
+        }
+        assert!(game.is_game_over());
+        assert_eq!(Some(Outcome::ToppedOut), game.outcome());
+        assert!(game.piece().is_none());
+    }
+
+    #[test]
+    fn zen_mode_never_ends() {
+        let mut game = Game::new(GameMode::Zen, 1, Box::new(BagGenerator::new(7, 1)));
+        for _ in 0..100 {
+            game.hard_drop();
+        }
+        assert!(!game.is_game_over());
+        assert!(game.piece().is_some());
+    }
+
+    #[test]
+    fn sprint_ends_after_40_lines() {
+        let generator = SequenceGenerator::new(&[1]);
+        let mut game = Game::new(GameMode::Sprint, 1, Box::new(generator));
+        let bottom = game.board().height() - 1;
+        for cleared in 0..SPRINT_LINES {
+            assert_eq!(Some(SPRINT_LINES - cleared), game.lines_left());
+            game.update(0.5);
+            for x in 4..BOARD_WIDTH {
+                game.board.set_cell(x, bottom, Some(0));
+            }
+            while game.move_piece(-1, 0) {}
+            game.hard_drop();
+        }
+        assert_eq!(Some(Outcome::Completed), game.outcome());
+        assert!(game.time() > 0.0);
+    }
+
+    #[test]
+    fn ultra_ends_when_the_time_is_up() {
+        let mut game = Game::new(GameMode::Ultra, 1, Box::new(BagGenerator::new(7, 1)));
+        game.update(ULTRA_DURATION - 1.0);
+        assert!(!game.is_game_over());
+        assert_eq!(Some(1.0), game.time_left());
+        game.update(1.0);
+        assert_eq!(Some(Outcome::Completed), game.outcome());
+    }
+}
@@ -0,0 +1,218 @@
+//! Piece generators (randomizers) and the next-queue.
+
+use std::collections::VecDeque;
+
+/// Source of the pieces to play, as indices for [`PieceSet::piece`](crate::PieceSet::piece).
+pub trait PieceGenerator {
+    fn next_piece(&mut self) -> usize;
+}
+
+/// The modern "7-bag": all the pieces are shuffled in a bag, and dealt one by one until the bag is empty.
+pub struct BagGenerator {
+    rng: fastrand::Rng,
+    piece_count: usize,
+    bag: Vec<usize>,
+}
+
+impl BagGenerator {
+    pub fn new(piece_count: usize, seed: u64) -> Self {
+        assert!(piece_count > 0);
+        BagGenerator {
+            rng: fastrand::Rng::with_seed(seed),
+            piece_count,
+            bag: Vec::with_capacity(piece_count),
+        }
+    }
+}
+
+impl PieceGenerator for BagGenerator {
+    fn next_piece(&mut self) -> usize {
+        if self.bag.is_empty() {
+            self.bag.extend(0..self.piece_count);
+            self.rng.shuffle(&mut self.bag);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// The classic NES randomizer: roll once more (without the reroll option) if the piece repeats the last one.
+pub struct NesGenerator {
+    rng: fastrand::Rng,
+    piece_count: usize,
+    last: Option<usize>,
+}
+
+impl NesGenerator {
+    pub fn new(piece_count: usize, seed: u64) -> Self {
+        assert!(piece_count > 0);
+        NesGenerator {
+            rng: fastrand::Rng::with_seed(seed),
+            piece_count,
+            last: None,
+        }
+    }
+}
+
+impl PieceGenerator for NesGenerator {
+    fn next_piece(&mut self) -> usize {
+        // the NES rolls one extra value, which also means "reroll"
+        let mut piece = self.rng.usize(0..=self.piece_count);
+        if piece == self.piece_count || Some(piece) == self.last {
+            piece = self.rng.usize(0..self.piece_count);
+        }
+        self.last = Some(piece);
+        piece
+    }
+}
+
+/// Plain random pieces, from a seed (so the sequence is always the same for the same seed).
+pub struct RandomGenerator {
+    rng: fastrand::Rng,
+    piece_count: usize,
+}
+
+impl RandomGenerator {
+    pub fn new(piece_count: usize, seed: u64) -> Self {
+        assert!(piece_count > 0);
+        RandomGenerator {
+            rng: fastrand::Rng::with_seed(seed),
+            piece_count,
+        }
+    }
+}
+
+impl PieceGenerator for RandomGenerator {
+    fn next_piece(&mut self) -> usize {
+        self.rng.usize(0..self.piece_count)
+    }
+}
+
+/// A fixed sequence of pieces, repeated forever.
+pub struct SequenceGenerator {
+    pieces: Vec<usize>,
+    pos: usize,
+}
+
+impl SequenceGenerator {
+    pub fn new(pieces: &[usize]) -> Self {
+        assert!(!pieces.is_empty());
+        SequenceGenerator {
+            pieces: pieces.to_vec(),
+            pos: 0,
+        }
+    }
+}
+
+impl PieceGenerator for SequenceGenerator {
+    fn next_piece(&mut self) -> usize {
+        let piece = self.pieces[self.pos];
+        self.pos = (self.pos + 1) % self.pieces.len();
+        piece
+    }
+}
+
+/// The kinds of randomizers which can be chosen by the player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Randomizer {
+    Bag,
+    Nes,
+    Random,
+}
+
+impl Randomizer {
+    pub const ALL: &'static [Randomizer] = &[Randomizer::Bag, Randomizer::Nes, Randomizer::Random];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Randomizer::Bag => "7-BAG",
+            Randomizer::Nes => "NES",
+            Randomizer::Random => "RANDOM",
+        }
+    }
+
+    /// The randomizer with the given name (as returned by [`Randomizer::name`]).
+    pub fn from_name(name: &str) -> Option<Randomizer> {
+        Randomizer::ALL.iter().copied().find(|r| r.name() == name)
+    }
+
+    pub fn create(&self, piece_count: usize, seed: u64) -> Box<dyn PieceGenerator> {
+        match self {
+            Randomizer::Bag => Box::new(BagGenerator::new(piece_count, seed)),
+            Randomizer::Nes => Box::new(NesGenerator::new(piece_count, seed)),
+            Randomizer::Random => Box::new(RandomGenerator::new(piece_count, seed)),
+        }
+    }
+}
+
+/// The next-queue: a preview of the next pieces, fed by a generator.
+pub struct PieceQueue {
+    generator: Box<dyn PieceGenerator>,
+    queue: VecDeque<usize>,
+}
+
+impl PieceQueue {
+    /// Create a queue which always shows `preview_len` pieces.
+    pub fn new(mut generator: Box<dyn PieceGenerator>, preview_len: usize) -> Self {
+        let queue = (0..preview_len).map(|_| generator.next_piece()).collect();
+        PieceQueue { generator, queue }
+    }
+
+    /// Take the first piece from the queue (and refill it).
+    pub fn next_piece(&mut self) -> usize {
+        self.queue.push_back(self.generator.next_piece());
+        self.queue.pop_front().unwrap()
+    }
+
+    /// The upcoming pieces, in order.
+    pub fn preview(&self) -> impl Iterator<Item = usize> + '_ {
+        self.queue.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(generator: &mut dyn PieceGenerator, count: usize) -> Vec<usize> {
+        (0..count).map(|_| generator.next_piece()).collect()
+    }
+
+    #[test]
+    fn each_bag_contains_all_the_pieces() {
+        let mut generator = BagGenerator::new(7, 42);
+        for _ in 0..10 {
+            let mut bag = take(&mut generator, 7);
+            bag.sort();
+            assert_eq!(vec![0, 1, 2, 3, 4, 5, 6], bag);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_pieces() {
+        for randomizer in Randomizer::ALL {
+            let first = take(randomizer.create(7, 1234).as_mut(), 50);
+            let second = take(randomizer.create(7, 1234).as_mut(), 50);
+            assert_eq!(first, second);
+            assert!(first.iter().all(|&p| p < 7));
+        }
+    }
+
+    #[test]
+    fn nes_generator_rarely_repeats() {
+        let pieces = take(&mut NesGenerator::new(7, 7), 7000);
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        // the probability of a repeat is 1/49 (vs 1/7 for plain random)
+        assert!(repeats < 300, "Too many repeats: {repeats}");
+    }
+
+    #[test]
+    fn queue_previews_the_next_pieces() {
+        let generator = SequenceGenerator::new(&[3, 1, 4, 1, 5]);
+        let mut queue = PieceQueue::new(Box::new(generator), 3);
+        assert_eq!(vec![3, 1, 4], queue.preview().collect::<Vec<_>>());
+        assert_eq!(3, queue.next_piece());
+        assert_eq!(vec![1, 4, 1], queue.preview().collect::<Vec<_>>());
+        let rest: Vec<usize> = (0..6).map(|_| queue.next_piece()).collect();
+        assert_eq!(vec![1, 4, 1, 5, 3, 1], rest);
+    }
+}
@@ -0,0 +1,164 @@
+//! Handling settings (DAS, ARR, soft drop, lock delay) and the auto-shift of the pieces.
+
+/// How the pieces react to the player's input. All the times are in seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handling {
+    /// Delayed auto-shift: how long sideways keys must be held before the piece starts sliding.
+    pub das: f64,
+    /// Auto-repeat rate: the time between moves while sliding (0 = slide to the wall instantly).
+    pub arr: f64,
+    /// How much faster than the gravity the pieces fall while soft dropping.
+    pub soft_drop_factor: f64,
+    /// How long a piece can stay on the ground before it is locked.
+    pub lock_delay: f64,
+    /// How many times moving/rotating a piece on the ground can restart the lock delay
+    /// (the counter restarts whenever the piece reaches a lower row).
+    pub move_reset_limit: u32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: 0.167,
+            arr: 0.033,
+            soft_drop_factor: 20.0,
+            lock_delay: 0.5,
+            move_reset_limit: 15,
+        }
+    }
+}
+
+/// Sideways auto-shift, based on which of the left/right keys are held.
+/// When both are held, the last one pressed wins.
+#[derive(Clone, Debug, Default)]
+pub struct AutoShift {
+    left_held: bool,
+    right_held: bool,
+    direction: i32,
+    das_timer: f64,
+    arr_timer: f64,
+}
+
+impl AutoShift {
+    /// A sideways key was pressed (`direction` = -1 for left, 1 for right).
+    /// Returns the direction, for moving the piece once right away.
+    pub fn press(&mut self, direction: i32) -> i32 {
+        if direction < 0 {
+            self.left_held = true;
+        } else {
+            self.right_held = true;
+        }
+        self.start(direction.signum());
+        self.direction
+    }
+
+    /// A sideways key was released. If the other one is still held, the shift continues in its direction
+    /// (the DAS starts again).
+    pub fn release(&mut self, direction: i32) {
+        if direction < 0 {
+            self.left_held = false;
+        } else {
+            self.right_held = false;
+        }
+        match (self.left_held, self.right_held) {
+            (true, false) if self.direction != -1 => self.start(-1),
+            (false, true) if self.direction != 1 => self.start(1),
+            (false, false) => self.direction = 0,
+            _ => {}
+        }
+    }
+
+    /// Forget all the held keys (e.g. when the game is paused).
+    pub fn release_all(&mut self) {
+        *self = AutoShift::default();
+    }
+
+    /// The current direction of the shift (0 = none).
+    #[inline]
+    pub fn direction(&self) -> i32 {
+        self.direction
+    }
+
+    /// Advance the timers. Returns how many cells the piece should be moved, in the current direction
+    /// (`i32::MAX` means "as far as possible").
+    pub fn update(&mut self, elapsed_time: f64, handling: &Handling) -> i32 {
+        if self.direction == 0 {
+            return 0;
+        }
+        let before = self.das_timer;
+        self.das_timer += elapsed_time;
+        if self.das_timer < handling.das {
+            return 0;
+        }
+        if handling.arr <= 0.0 {
+            return i32::MAX;
+        }
+        // the first auto-repeat happens when the DAS is charged; the time beyond it counts for the ARR
+        let mut moves = 0;
+        if before < handling.das {
+            moves += 1;
+            self.arr_timer = self.das_timer - handling.das;
+        } else {
+            self.arr_timer += elapsed_time;
+        }
+        while self.arr_timer >= handling.arr {
+            self.arr_timer -= handling.arr;
+            moves += 1;
+        }
+        moves
+    }
+
+    fn start(&mut self, direction: i32) {
+        self.direction = direction;
+        self.das_timer = 0.0;
+        self.arr_timer = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handling(das: f64, arr: f64) -> Handling {
+        Handling {
+            das,
+            arr,
+            ..Handling::default()
+        }
+    }
+
+    #[test]
+    fn auto_shift_is_frame_rate_independent() {
+        // (binary fractions, so there are no rounding errors)
+        let handling = handling(0.125, 0.03125);
+        // the same 0.25 seconds, at 8 FPS vs 1024 FPS
+        let mut slow = AutoShift::default();
+        slow.press(1);
+        let slow_moves: i32 = (0..2).map(|_| slow.update(0.125, &handling)).sum();
+        let mut fast = AutoShift::default();
+        fast.press(1);
+        let fast_moves: i32 = (0..256).map(|_| fast.update(1.0 / 1024.0, &handling)).sum();
+        assert_eq!(slow_moves, fast_moves);
+        assert_eq!(5, fast_moves);
+    }
+
+    #[test]
+    fn no_moves_before_das_and_instant_arr() {
+        let handling = handling(0.15, 0.0);
+        let mut shift = AutoShift::default();
+        assert_eq!(-1, shift.press(-1));
+        assert_eq!(0, shift.update(0.1, &handling));
+        assert_eq!(i32::MAX, shift.update(0.1, &handling));
+    }
+
+    #[test]
+    fn last_pressed_key_wins() {
+        let mut shift = AutoShift::default();
+        shift.press(-1);
+        assert_eq!(1, shift.press(1));
+        shift.release(1);
+        assert_eq!(-1, shift.direction());
+        shift.release(-1);
+        assert_eq!(0, shift.direction());
+    }
+}
@@ -0,0 +1,276 @@
+//! Per-mode high-score tables, saved to a text file.
+//!
+//! The file has a header (`highscores 1`), then one line per entry, with these fields separated by tabs:
+//! mode, name, score, lines, time (in seconds) and date.
+//! Loading is tolerant: lines which cannot be parsed are skipped, and a missing or unreadable file
+//! just gives empty tables.
+
+use crate::mode::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Entries kept for each mode.
+pub const MAX_HIGH_SCORES: usize = 10;
+/// Maximum length of the player names.
+pub const MAX_NAME_LEN: usize = 10;
+
+const HIGH_SCORES_VERSION: u32 = 1;
+
+/// One entry of a high-score table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u64,
+    pub lines: u32,
+    /// Seconds played.
+    pub time: f64,
+    /// When the game was played, as "YYYY-MM-DD".
+    pub date: String,
+}
+
+impl HighScore {
+    /// True if this entry ranks above the other one: by time in the timed modes, by score in the others.
+    /// (Equal entries don't rank above each other, so the oldest one stays first.)
+    fn is_better_than(&self, other: &HighScore, mode: GameMode) -> bool {
+        if mode.is_timed() {
+            self.time < other.time
+        } else {
+            self.score > other.score
+        }
+    }
+}
+
+/// The high-score tables of all the modes, best entries first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HighScores {
+    tables: HashMap<GameMode, Vec<HighScore>>,
+}
+
+impl HighScores {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The entries of a mode, best first.
+    pub fn table(&self, mode: GameMode) -> &[HighScore] {
+        self.tables.get(&mode).map_or(&[], |table| table.as_slice())
+    }
+
+    /// The position the result would get in its table, or `None` if it doesn't qualify.
+    /// In the timed modes, only completed games qualify.
+    pub fn rank(&self, result: &GameResult) -> Option<usize> {
+        if result.mode.is_timed() && result.outcome != Outcome::Completed {
+            return None;
+        }
+        if !result.mode.is_timed() && result.score == 0 {
+            return None;
+        }
+        self.position(result.mode, &entry(result, "", ""))
+    }
+
+    /// Add a result to its table (if it qualifies). Returns its position.
+    pub fn insert(&mut self, result: &GameResult, name: &str, date: &str) -> Option<usize> {
+        let rank = self.rank(result)?;
+        self.add(result.mode, entry(result, name, date));
+        Some(rank)
+    }
+
+    /// Load the tables from a file. This never fails: missing files and invalid lines are ignored.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .map(|text| Self::from_text(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        game_framework::storage::save_file(path, &self.to_text())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("highscores {HIGH_SCORES_VERSION}\n");
+        for &mode in GameMode::ALL {
+            for e in self.table(mode) {
+                text.push_str(&format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\n",
+                    mode.name(),
+                    e.name,
+                    e.score,
+                    e.lines,
+                    e.time,
+                    e.date
+                ));
+            }
+        }
+        text
+    }
+
+    /// Parse the tables, skipping the lines which are not valid.
+    /// The entries are sorted again, in case the file was edited by hand.
+    pub fn from_text(text: &str) -> Self {
+        let mut high_scores = HighScores::new();
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        if lines.next().map(str::trim) != Some(&format!("highscores {HIGH_SCORES_VERSION}")) {
+            return high_scores;
+        }
+        for (mode, e) in lines.filter_map(parse_line) {
+            if high_scores.position(mode, &e).is_some() {
+                high_scores.add(mode, e);
+            }
+        }
+        high_scores
+    }
+
+    //-------------------------------------------------------
+    //  Internal stuff
+
+    /// Where the entry would go in the table of the mode (`None` = below the last place).
+    fn position(&self, mode: GameMode, e: &HighScore) -> Option<usize> {
+        let table = self.table(mode);
+        let pos = table
+            .iter()
+            .position(|other| e.is_better_than(other, mode))
+            .unwrap_or(table.len());
+        (pos < MAX_HIGH_SCORES).then_some(pos)
+    }
+
+    fn add(&mut self, mode: GameMode, e: HighScore) {
+        if let Some(pos) = self.position(mode, &e) {
+            let table = self.tables.entry(mode).or_default();
+            table.insert(pos, e);
+            table.truncate(MAX_HIGH_SCORES);
+        }
+    }
+}
+
+/// Make the name safe for the file (and for the font): no tabs or control characters, limited length.
+pub fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|ch| if ch.is_control() { ' ' } else { ch })
+        .collect();
+    let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+    if name.is_empty() {
+        "???".to_string()
+    } else {
+        name
+    }
+}
+
+fn entry(result: &GameResult, name: &str, date: &str) -> HighScore {
+    HighScore {
+        name: sanitize_name(name),
+        score: result.score,
+        lines: result.lines,
+        time: result.time,
+        date: date.to_string(),
+    }
+}
+
+fn parse_line(line: &str) -> Option<(GameMode, HighScore)> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [mode, name, score, lines, time, date] = fields[..] else {
+        return None;
+    };
+    let time: f64 = time.parse().ok()?;
+    if !time.is_finite() || time < 0.0 {
+        return None;
+    }
+    let e = HighScore {
+        name: sanitize_name(name),
+        score: score.parse().ok()?,
+        lines: lines.parse().ok()?,
+        time,
+        date: date.trim().to_string(),
+    };
+    Some((GameMode::from_name(mode)?, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(mode: GameMode, score: u64, time: f64) -> GameResult {
+        GameResult {
+            mode,
+            outcome: Outcome::Completed,
+            score,
+            lines: 40,
+            level: 5,
+            time,
+        }
+    }
+
+    #[test]
+    fn tables_are_sorted_and_limited() {
+        let mut high_scores = HighScores::new();
+        for score in 1..=15 {
+            high_scores.insert(
+                &result(GameMode::Marathon, score * 100, 60.0),
+                "A",
+                "2024-01-01",
+            );
+        }
+        let table = high_scores.table(GameMode::Marathon);
+        assert_eq!(MAX_HIGH_SCORES, table.len());
+        assert_eq!(1500, table[0].score);
+        assert_eq!(600, table[MAX_HIGH_SCORES - 1].score);
+        assert_eq!(
+            None,
+            high_scores.rank(&result(GameMode::Marathon, 500, 60.0))
+        );
+        assert_eq!(
+            Some(1),
+            high_scores.rank(&result(GameMode::Marathon, 1450, 60.0))
+        );
+        assert!(high_scores.table(GameMode::Ultra).is_empty());
+    }
+
+    #[test]
+    fn sprint_is_ranked_by_time_and_must_be_completed() {
+        let mut high_scores = HighScores::new();
+        high_scores.insert(&result(GameMode::Sprint, 100, 90.0), "SLOW", "");
+        assert_eq!(
+            Some(0),
+            high_scores.insert(&result(GameMode::Sprint, 50, 60.0), "FAST", "")
+        );
+        assert_eq!("FAST", high_scores.table(GameMode::Sprint)[0].name);
+        let topped_out = GameResult {
+            outcome: Outcome::ToppedOut,
+            ..result(GameMode::Sprint, 100, 10.0)
+        };
+        assert_eq!(None, high_scores.rank(&topped_out));
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        let mut high_scores = HighScores::new();
+        high_scores.insert(&result(GameMode::Sprint, 1234, 83.25), "Anna", "2024-05-01");
+        high_scores.insert(
+            &result(GameMode::Ultra, 99999, 120.0),
+            "Bob\tby",
+            "2024-05-02",
+        );
+        let loaded = HighScores::from_text(&high_scores.to_text());
+        assert_eq!(high_scores, loaded);
+        assert_eq!("Bob by", loaded.table(GameMode::Ultra)[0].name);
+    }
+
+    #[test]
+    fn corrupted_lines_are_skipped() {
+        let text = "highscores 1\n\
+                    SPRINT\tOK\t100\t40\t70.5\t2024-01-01\n\
+                    SPRINT\tBAD TIME\t100\t40\tNaN\t2024-01-01\n\
+                    NOPE\tBAD MODE\t100\t40\t70.5\t2024-01-01\n\
+                    ULTRA\ttoo few fields\n\
+                    \u{0}garbage\n";
+        let high_scores = HighScores::from_text(text);
+        assert_eq!(1, high_scores.table(GameMode::Sprint).len());
+        assert!(high_scores.table(GameMode::Ultra).is_empty());
+        // unknown versions are ignored
+        assert_eq!(HighScores::new(), HighScores::from_text("highscores 99\n"));
+        assert_eq!(
+            HighScores::new(),
+            HighScores::load(Path::new("/no/such/file"))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Key bindings for the game actions.
+
+use game_framework::Keycode;
+
+/// The things a player can do with the keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    HardDrop,
+    Hold,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Left,
+        Action::Right,
+        Action::SoftDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::HardDrop,
+        Action::Hold,
+    ];
+
+    /// A short name, e.g. for the replay files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::SoftDrop => "soft",
+            Action::RotateCw => "cw",
+            Action::RotateCcw => "ccw",
+            Action::Rotate180 => "180",
+            Action::HardDrop => "hard",
+            Action::Hold => "hold",
+        }
+    }
+
+    /// The action with the given name (as returned by [`Action::name`]).
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+/// Which keys trigger which actions (a key can only have one action, an action can have many keys).
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    bindings: Vec<(Keycode, Action)>,
+}
+
+impl Default for KeyMap {
+    /// The keys for playing alone.
+    fn default() -> Self {
+        KeyMap::new(&[
+            (Keycode::Left, Action::Left),
+            (Keycode::Right, Action::Right),
+            (Keycode::Down, Action::SoftDrop),
+            (Keycode::Up, Action::RotateCw),
+            (Keycode::X, Action::RotateCw),
+            (Keycode::Z, Action::RotateCcw),
+            (Keycode::LCtrl, Action::RotateCcw),
+            (Keycode::A, Action::Rotate180),
+            (Keycode::Space, Action::HardDrop),
+            (Keycode::C, Action::Hold),
+            (Keycode::LShift, Action::Hold),
+        ])
+    }
+}
+
+impl KeyMap {
+    pub fn new(bindings: &[(Keycode, Action)]) -> Self {
+        KeyMap {
+            bindings: bindings.to_vec(),
+        }
+    }
+
+    /// The keys of the player on the left side of the keyboard, in versus games.
+    pub fn player_1() -> Self {
+        KeyMap::new(&[
+            (Keycode::A, Action::Left),
+            (Keycode::D, Action::Right),
+            (Keycode::S, Action::SoftDrop),
+            (Keycode::W, Action::RotateCw),
+            (Keycode::Q, Action::RotateCcw),
+            (Keycode::E, Action::Rotate180),
+            (Keycode::Space, Action::HardDrop),
+            (Keycode::LShift, Action::Hold),
+        ])
+    }
+
+    /// The keys of the player on the right side of the keyboard, in versus games.
+    pub fn player_2() -> Self {
+        KeyMap::new(&[
+            (Keycode::Left, Action::Left),
+            (Keycode::Right, Action::Right),
+            (Keycode::Down, Action::SoftDrop),
+            (Keycode::Up, Action::RotateCw),
+            (Keycode::RCtrl, Action::RotateCcw),
+            (Keycode::Slash, Action::Rotate180),
+            (Keycode::Return, Action::HardDrop),
+            (Keycode::KpEnter, Action::HardDrop),
+            (Keycode::RShift, Action::Hold),
+        ])
+    }
+
+    /// The action of a key, if it has one.
+    pub fn action(&self, key: Keycode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|&(_, action)| action)
+    }
+
+    /// The keys which trigger an action.
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = Keycode> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|&(key, _)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versus_key_maps_do_not_overlap() {
+        let (p1, p2) = (KeyMap::player_1(), KeyMap::player_2());
+        assert!(p1.bindings.iter().all(|(key, _)| p2.action(*key).is_none()));
+        assert_eq!(Some(Action::HardDrop), p2.action(Keycode::KpEnter));
+        assert_eq!(2, KeyMap::default().keys(Action::Hold).count());
+    }
+}
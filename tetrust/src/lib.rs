@@ -1,9 +1,44 @@
 //! Main lib for TetRusT - Tetris-like game.
 
-mod painter;
-mod sdl_wrapper;
+mod ai;
+mod board;
+mod effects;
+mod evaluation;
+mod game;
+mod generator;
+mod handling;
+mod highscores;
+mod keymap;
+mod mode;
+mod network;
+mod puzzle;
+mod render;
+mod replay;
+mod scoring;
+mod settings;
+mod terminal;
 mod tetrimino;
+mod training;
+mod versus;
 
-pub use painter::*;
-pub use sdl_wrapper::*;
+pub use ai::*;
+pub use board::*;
+pub use effects::*;
+pub use evaluation::*;
+pub use game::*;
+pub use game_framework::*;
+pub use generator::*;
+pub use handling::*;
+pub use highscores::*;
+pub use keymap::*;
+pub use mode::*;
+pub use network::*;
+pub use puzzle::*;
+pub use render::*;
+pub use replay::*;
+pub use scoring::*;
+pub use settings::*;
+pub use terminal::*;
 pub use tetrimino::*;
+pub use training::*;
+pub use versus::*;
@@ -3,6 +3,10 @@
 // This magic line prevents the opening of a terminal when launching a release build
 #![cfg_attr(not(any(test, debug_assertions)), windows_subsystem = "windows")]
 
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
 use tetrust::*;
 
 const SCR_WIDTH: i32 = 400;
@@ -11,22 +15,18 @@ const PIX_SIZE: i32 = 3;
 const SLEEP_KIND: SleepKind = SleepKind::YIELD;
 
 fn main() {
-    let mut example = ExampleProgram {};
-
-    // TEST: print ALL tetriminoes
-    for i in 0..7 {
-        print_tetrimino(i);
-    }
-
-    // main game loop
-    let sdl_config = SdlConfiguration::new(
-        "Ray Caster Demo",
-        SCR_WIDTH,
-        SCR_HEIGHT,
-        PIX_SIZE,
-        SLEEP_KIND,
-    );
-    let res = tetrust::run_sdl_loop(&sdl_config, &mut example);
+    // main game loop (possibly recording or playing a replay)
+    let args: Vec<String> = std::env::args().collect();
+    let res = ReplayMode::from_args(&args).and_then(|replay_mode| {
+        // all the randomness comes from this seed, so the replays are deterministic
+        let seed = replay_mode.seed().unwrap_or_else(|| fastrand::u64(..));
+        fastrand::seed(seed);
+        let sdl_config =
+            SdlConfiguration::new("TetRusT", SCR_WIDTH, SCR_HEIGHT, PIX_SIZE, SLEEP_KIND);
+        let settings = Rc::new(RefCell::new(Settings::default()));
+        let mut scenes = SceneStack::new(SCR_WIDTH, SCR_HEIGHT, Box::new(title_menu(&settings)));
+        tetrust::run_sdl_loop_with_replays(&sdl_config, &mut scenes, &replay_mode, seed)
+    });
     if let Err(msg) = res {
         println!("ERROR: {msg}");
     } else {
@@ -35,69 +35,1701 @@ fn main() {
 }
 
 //----------------------------
-// TEST: print tetriminoes
-fn print_tetrimino(idx: usize) {
-    let mut tetr = Tetrimino::from_index(idx);
-    let mut rots: Vec<String> = vec![];
-
-    println!(
-        "--> Tetrimino {} (color = {})",
-        tetr.name(),
-        tetr.color_idx()
-    );
+// Scenes
+
+/// Settings shared by all the scenes.
+type SharedSettings = Rc<RefCell<Settings>>;
+
+fn title_menu(settings: &SharedSettings) -> MenuScene {
+    let s1 = settings.clone();
+    let s2 = settings.clone();
+    let s3 = settings.clone();
+    let s4 = settings.clone();
+    let s5 = settings.clone();
+    let s6 = settings.clone();
+    let s7 = settings.clone();
+    MenuScene::new("TETRUST", SCR_WIDTH, SCR_HEIGHT)
+        .item("Start", move || {
+            Transition::Replace(Box::new(ModeSelectScene::new(&s1)))
+        })
+        .item("Puzzles", move || {
+            Transition::Replace(Box::new(PuzzleSelectScene::new(&s6)))
+        })
+        .item("Versus", move || {
+            Transition::Replace(Box::new(VersusScene::new(&s4)))
+        })
+        .item("Network", move || {
+            Transition::Replace(Box::new(NetLobbyScene::new(&s5)))
+        })
+        .item("Replays", move || {
+            Transition::Replace(Box::new(ReplaysScene::new(&s7)))
+        })
+        .item("High scores", move || {
+            Transition::Replace(Box::new(HighScoresScene::new(&s2)))
+        })
+        .item("Settings", move || {
+            Transition::Push(Box::new(SettingsScene::new(&s3)))
+        })
+        .item("Quit", || Transition::Quit)
+        .on_cancel(|| Transition::Quit)
+}
+
+fn pause_menu(settings: &SharedSettings) -> MenuScene {
+    let s1 = settings.clone();
+    let s2 = settings.clone();
+    MenuScene::new("PAUSED", SCR_WIDTH, SCR_HEIGHT)
+        .overlay()
+        .item("Resume", || Transition::Pop)
+        .item("Settings", move || {
+            Transition::Push(Box::new(SettingsScene::new(&s1)))
+        })
+        .item("Quit to title", move || {
+            Transition::Reset(Box::new(title_menu(&s2)))
+        })
+        .item("Quit game", || Transition::Quit)
+        .on_cancel(|| Transition::Pop)
+}
+
+/// Choose the game mode and the starting level.
+struct ModeSelectScene {
+    ui: Ui,
+    selected: usize,
+    start_level: f64,
+    /// The fastest sprint, to race against.
+    sprint_ghost: Option<GameReplay>,
+    settings: SharedSettings,
+}
 
-    // build strings for each rotation
-    for _ in 0..4 {
-        let mut bytes: [u8; 16] = ['.' as u8; 16];
-        // build a string with the tetrimino data
-        for i in 0..4 {
-            let x = tetr.x(i);
-            let y = tetr.y(i);
-            let idx = y * 4 + x + 4;
-            assert!(idx >= 0 && idx < 16, "Invalid index: {idx}");
-            bytes[idx as usize] = '#' as u8;
+impl ModeSelectScene {
+    fn new(settings: &SharedSettings) -> Self {
+        let settings_ref = settings.borrow();
+        let selected = GameMode::ALL
+            .iter()
+            .position(|&m| m == settings_ref.mode)
+            .unwrap_or(0);
+        ModeSelectScene {
+            ui: Ui::new(UiStyle::default()),
+            selected,
+            start_level: settings_ref.start_level.clamp(1, MARATHON_MAX_LEVEL) as f64,
+            sprint_ghost: load_sprint_ghost(),
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for ModeSelectScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Replace(Box::new(title_menu(&self.settings))),
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
         }
-        // save the string
-        let s = std::str::from_utf8(&bytes).unwrap();
-        rots.push(s.to_string());
-        // go to the next rotation
-        tetr.rotate_cw();
     }
 
-    // print tetriminoes
-    assert_eq!(4, rots.len());
-    for i in 0..=3 {
-        for j in 0..=3 {
-            let s = rots[j].as_str();
-            let ss = &s[(i * 4)..=(i * 4 + 3)];
-            print!("   {ss}");
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let names: Vec<&str> = GameMode::ALL.iter().map(|m| m.name()).collect();
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 4, 20, SCR_WIDTH / 2);
+        ui.title("GAME MODE");
+        ui.space(6);
+        let mut play = ui.list_select(&names, &mut self.selected, names.len());
+        ui.label(GameMode::ALL[self.selected].description());
+        ui.space(6);
+        ui.slider(
+            "Start level",
+            &mut self.start_level,
+            1.0,
+            MARATHON_MAX_LEVEL as f64,
+            1.0,
+        );
+        ui.space(6);
+        play |= ui.button("Play");
+        let ghost = self
+            .sprint_ghost
+            .as_ref()
+            .filter(|_| GameMode::ALL[self.selected] == GameMode::Sprint);
+        let race = match ghost {
+            Some(ghost) => ui.button(&format!("Race best ({})", format_time(ghost.duration()))),
+            None => false,
+        };
+        let back = ui.button("Back");
+        ui.end();
+        if play {
+            let mut settings = self.settings.borrow_mut();
+            settings.mode = GameMode::ALL[self.selected];
+            settings.start_level = self.start_level as u32;
+            drop(settings);
+            Transition::Replace(Box::new(GameplayScene::new(&self.settings)))
+        } else if race {
+            let scene = GameplayScene::with_ghost(&self.settings, ghost.cloned());
+            Transition::Replace(Box::new(scene))
+        } else if back {
+            Transition::Replace(Box::new(title_menu(&self.settings)))
+        } else {
+            Transition::None
         }
-        println!("");
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.ui.paint(painter);
     }
 }
 
-//----------------------------
-// TEMPORARY demo
-struct ExampleProgram {}
+/// Edit the settings; the changes are applied right away.
+struct SettingsScene {
+    ui: Ui,
+    settings: SharedSettings,
+    audio: Option<AudioHandle>,
+}
+
+impl SettingsScene {
+    fn new(settings: &SharedSettings) -> Self {
+        SettingsScene {
+            ui: Ui::new(UiStyle::default()),
+            settings: settings.clone(),
+            audio: None,
+        }
+    }
+}
+
+impl Scene for SettingsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Pop,
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let mut settings = self.settings.borrow_mut();
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 6, 24, SCR_WIDTH * 2 / 3);
+        ui.panel(
+            SCR_WIDTH / 6 - 8,
+            16,
+            SCR_WIDTH * 2 / 3 + 16,
+            SCR_HEIGHT - 32,
+        );
+        ui.title("SETTINGS");
+        ui.space(6);
+        if ui.slider("Volume", &mut settings.master_volume, 0.0, 1.0, 0.1) {
+            if let Some(audio) = &self.audio {
+                audio
+                    .borrow_mut()
+                    .set_master_volume(settings.master_volume as f32);
+            }
+        }
+        ui.checkbox("Ghost piece", &mut settings.ghost_piece);
+        ui.checkbox("180 rotations (A)", &mut settings.allow_180);
+        let randomizer = format!("Randomizer: {}", settings.randomizer.name());
+        if ui.button(&randomizer) {
+            // cycle through all the randomizers
+            let all = Randomizer::ALL;
+            let idx = all
+                .iter()
+                .position(|&r| r == settings.randomizer)
+                .unwrap_or(0);
+            settings.randomizer = all[(idx + 1) % all.len()];
+        }
+        let pieces = format!("Pieces: {}", settings.piece_set);
+        if ui.button(&pieces) {
+            let all = piece_set_names();
+            let idx = all.iter().position(|n| *n == settings.piece_set);
+            settings.piece_set = all[idx.map_or(0, |idx| (idx + 1) % all.len())].clone();
+        }
+        let handling = ui.button("Handling...");
+        let effects = ui.button("Effects...");
+        ui.space(6);
+        let back = ui.button("Back");
+        ui.end();
+        if back {
+            Transition::Pop
+        } else if handling {
+            Transition::Push(Box::new(HandlingScene::new(&self.settings)))
+        } else if effects {
+            Transition::Push(Box::new(EffectsScene::new(&self.settings)))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.5);
+        self.ui.paint(painter);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        self.audio = Some(audio);
+    }
+}
+
+/// Edit the handling settings (the times are shown in milliseconds).
+struct HandlingScene {
+    ui: Ui,
+    settings: SharedSettings,
+}
 
-impl GraphicsLoop for ExampleProgram {
-    fn handle_event(&mut self, _event: &sdl2::event::Event) -> bool {
+impl HandlingScene {
+    fn new(settings: &SharedSettings) -> Self {
+        HandlingScene {
+            ui: Ui::new(UiStyle::default()),
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for HandlingScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Pop,
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let handling = &mut self.settings.borrow_mut().handling;
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 6, 24, SCR_WIDTH * 2 / 3);
+        ui.panel(
+            SCR_WIDTH / 6 - 8,
+            16,
+            SCR_WIDTH * 2 / 3 + 16,
+            SCR_HEIGHT - 32,
+        );
+        ui.title("HANDLING");
+        ui.space(6);
+        let mut das = handling.das * 1000.0;
+        ui.slider("DAS", &mut das, 0.0, 500.0, 1.0);
+        handling.das = das / 1000.0;
+        let mut arr = handling.arr * 1000.0;
+        ui.slider("ARR", &mut arr, 0.0, 200.0, 1.0);
+        handling.arr = arr / 1000.0;
+        ui.slider("Soft drop", &mut handling.soft_drop_factor, 1.0, 40.0, 1.0);
+        let mut lock_delay = handling.lock_delay * 1000.0;
+        ui.slider("Lock delay", &mut lock_delay, 100.0, 2000.0, 50.0);
+        handling.lock_delay = lock_delay / 1000.0;
+        let mut move_resets = handling.move_reset_limit as f64;
+        ui.slider("Move resets", &mut move_resets, 0.0, 30.0, 1.0);
+        handling.move_reset_limit = move_resets as u32;
+        ui.space(6);
+        if ui.button("Defaults") {
+            *handling = Handling::default();
+        }
+        let back = ui.button("Back");
+        ui.end();
+        if back {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.ui.paint(painter);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// Turn the visual effects on or off (e.g. for the players sensitive to flashes or motion).
+struct EffectsScene {
+    ui: Ui,
+    settings: SharedSettings,
+}
+
+impl EffectsScene {
+    fn new(settings: &SharedSettings) -> Self {
+        EffectsScene {
+            ui: Ui::new(UiStyle::default()),
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for EffectsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Pop,
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let effects = &mut self.settings.borrow_mut().effects;
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 6, 24, SCR_WIDTH * 2 / 3);
+        ui.panel(
+            SCR_WIDTH / 6 - 8,
+            16,
+            SCR_WIDTH * 2 / 3 + 16,
+            SCR_HEIGHT - 32,
+        );
+        ui.title("EFFECTS");
+        ui.space(6);
+        ui.checkbox("Line clear flash", &mut effects.line_clears);
+        ui.checkbox("Hard drop shake", &mut effects.shake);
+        ui.checkbox("Particles", &mut effects.particles);
+        ui.space(6);
+        if ui.button("All off") {
+            *effects = EffectSettings::NONE;
+        }
+        let back = ui.button("Back");
+        ui.end();
+        if back {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.ui.paint(painter);
+    }
+
+    fn is_overlay(&self) -> bool {
         true
     }
+}
+
+/// The game itself, with a pause menu on Escape, and the results when it ends.
+/// Every game is recorded, and the replay is saved when it ends.
+struct GameplayScene {
+    recorder: GameRecorder,
+    /// A replay raced against, with the same pieces (shown beside the game).
+    ghost: Option<ReplayPlayback>,
+    settings: SharedSettings,
+    finished: bool,
+    /// The AI playing instead of the player (toggled with F2).
+    ai: Option<AiPlayer>,
+    /// Games where the AI played are not eligible for the high scores, nor to be the sprint ghost.
+    ai_used: bool,
+    /// Neither are the games with other pieces than the standard ones.
+    standard_pieces: bool,
+}
+
+const CELL_SIZE: i32 = 13;
+
+impl GameplayScene {
+    fn new(settings: &SharedSettings) -> Self {
+        Self::with_ghost(settings, None)
+    }
+
+    /// A game against a replay: same mode, same level and same pieces.
+    fn with_ghost(settings: &SharedSettings, ghost: Option<GameReplay>) -> Self {
+        let settings_ref = settings.borrow();
+        let ghost = ghost.and_then(|replay| match load_piece_set(&replay.piece_set) {
+            Ok(pieces) => Some(ReplayPlayback::new(replay, pieces)),
+            Err(msg) => {
+                println!("ERROR: {msg}");
+                None
+            }
+        });
+        let (mut replay, pieces) = match &ghost {
+            Some(ghost) => {
+                let ghost_replay = ghost.replay();
+                let replay = GameReplay::new(
+                    ghost_replay.mode,
+                    ghost_replay.start_level,
+                    ghost_replay.randomizer,
+                    ghost_replay.seed,
+                );
+                let pieces = ghost.pieces().clone();
+                (replay, pieces)
+            }
+            None => {
+                let replay = GameReplay::new(
+                    settings_ref.mode,
+                    settings_ref.start_level,
+                    settings_ref.randomizer,
+                    fastrand::u64(..),
+                );
+                let pieces = load_piece_set(&settings_ref.piece_set).unwrap_or_else(|msg| {
+                    println!("ERROR: {msg}");
+                    PieceSet::standard()
+                });
+                (replay, pieces)
+            }
+        };
+        replay.piece_set = pieces.name().to_string();
+        replay.handling = settings_ref.handling.clone();
+        replay.allow_180 = settings_ref.allow_180;
+        let mut recorder = GameRecorder::new(replay, pieces);
+        recorder.game_mut().set_show_ghost(settings_ref.ghost_piece);
+        recorder.game_mut().set_effects(settings_ref.effects);
+        let mut ghost = ghost;
+        match ghost.as_mut() {
+            Some(ghost) => {
+                recorder.game_mut().set_view(half_screen_view(0));
+                ghost.game_mut().set_view(half_screen_view(1));
+                ghost.game_mut().set_effects(settings_ref.effects);
+            }
+            None => {
+                let (w, h) = (BOARD_WIDTH * CELL_SIZE, BOARD_HEIGHT * CELL_SIZE);
+                recorder.game_mut().set_view(BoardView {
+                    x: (SCR_WIDTH - w) / 2,
+                    y: (SCR_HEIGHT - h) / 2,
+                    cell_size: CELL_SIZE,
+                });
+            }
+        }
+        drop(settings_ref);
+        GameplayScene {
+            settings: settings.clone(),
+            finished: false,
+            ai: None,
+            ai_used: false,
+            standard_pieces: recorder.game().piece_set().name() == BUILTIN_PIECE_SETS[0].0,
+            recorder,
+            ghost,
+        }
+    }
+
+    /// Save the replay of the game, and make it the sprint ghost if it's the fastest sprint.
+    fn save_replay(&self, result: &GameResult) {
+        let replay = self.recorder.replay();
+        save_replay(replay);
+        let fastest = result.mode == GameMode::Sprint
+            && result.outcome == Outcome::Completed
+            && !self.ai_used
+            && self.standard_pieces
+            && load_sprint_ghost().is_none_or(|ghost| replay.ticks < ghost.ticks);
+        if let Some(path) = sprint_ghost_path().filter(|_| fastest) {
+            if let Err(msg) = storage::save_file(&path, &replay.to_text()) {
+                println!("ERROR: {msg}");
+            }
+        }
+    }
+}
+
+impl Scene for GameplayScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Push(Box::new(pause_menu(&self.settings))),
+            Event::KeyDown {
+                keycode: Some(Keycode::F2),
+                repeat: false,
+                ..
+            } => {
+                self.ai = match self.ai {
+                    Some(_) => None,
+                    None => Some(AiPlayer::new(Weights::default())),
+                };
+                self.ai_used = true;
+                self.recorder.release_keys();
+                Transition::None
+            }
+            // the player's inputs are ignored while the AI is playing
+            _ if self.ai.is_some() => Transition::None,
+            _ if !self.recorder.handle_event(event) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        if let Some(ai) = self.ai.as_mut() {
+            self.recorder.play_ai(ai, elapsed_time);
+        }
+        if !self.recorder.update_state(elapsed_time) {
+            return Transition::Quit;
+        }
+        if let Some(ghost) = self.ghost.as_mut() {
+            ghost.seek(self.recorder.tick());
+        }
+        match self.recorder.game().result() {
+            Some(result) if !self.finished => {
+                self.finished = true;
+                self.save_replay(&result);
+                let results = ResultsScene::new(
+                    result,
+                    !self.ai_used && self.standard_pieces,
+                    &self.settings,
+                );
+                Transition::Push(Box::new(results))
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.recorder.paint(painter);
+        if let Some(ghost) = self.ghost.as_ref() {
+            ghost.paint(&mut FadedPainter::new(painter, 45));
+            let cx = SCR_WIDTH / 4;
+            font::draw_text_centered(painter, cx, 8, "YOU", 2, LIGHT_YELLOW);
+            let best = format!("BEST {}", format_time(ghost.replay().duration()));
+            font::draw_text_centered(painter, cx + SCR_WIDTH / 2, 8, &best, 2, GREY);
+        }
+        if self.ai.is_some() {
+            painter.draw_text(4, 4, "AI PLAYING (F2)", 2, LIGHT_GREEN);
+        }
+    }
+
+    fn resume(&mut self) {
+        // the settings may have been changed from the pause menu
+        let settings = self.settings.borrow();
+        self.recorder
+            .set_handling(&settings.handling, settings.allow_180);
+        self.recorder
+            .game_mut()
+            .set_show_ghost(settings.ghost_piece);
+        self.recorder.game_mut().set_effects(settings.effects);
+        self.recorder.release_keys();
+        if let Some(ai) = self.ai.as_mut() {
+            ai.reset();
+        }
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        let volume = self.settings.borrow().master_volume as f32;
+        audio.borrow_mut().set_master_volume(volume);
+    }
+}
+
+/// Two players on the same keyboard, side by side.
+struct VersusScene {
+    versus: Versus,
+    settings: SharedSettings,
+    finished: bool,
+}
+
+/// The board cells are smaller in versus games, to fit both players on the screen.
+const VERSUS_CELL_SIZE: i32 = 9;
+
+impl VersusScene {
+    fn new(settings: &SharedSettings) -> Self {
+        let settings_ref = settings.borrow();
+        let mut versus = Versus::new(
+            settings_ref.start_level,
+            settings_ref.randomizer,
+            fastrand::u64(..),
+        );
+        versus.set_handling(&settings_ref.handling, settings_ref.allow_180);
+        for idx in 0..2 {
+            let game = versus.player_mut(idx);
+            game.set_show_ghost(settings_ref.ghost_piece);
+            game.set_effects(settings_ref.effects);
+            game.set_view(half_screen_view(idx));
+        }
+        drop(settings_ref);
+        VersusScene {
+            versus,
+            settings: settings.clone(),
+            finished: false,
+        }
+    }
+}
+
+/// A board in the middle of the left (0) or right (1) half of the screen, with small cells.
+fn half_screen_view(idx: usize) -> BoardView {
+    let w = BOARD_WIDTH * VERSUS_CELL_SIZE;
+    BoardView {
+        x: SCR_WIDTH / 2 * idx as i32 + (SCR_WIDTH / 2 - w) / 2,
+        y: 40,
+        cell_size: VERSUS_CELL_SIZE,
+    }
+}
+
+impl Scene for VersusScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Push(Box::new(pause_menu(&self.settings))),
+            _ if !self.versus.handle_event(event) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        if !self.versus.update_state(elapsed_time) {
+            return Transition::Quit;
+        }
+        if self.versus.is_over() && !self.finished {
+            self.finished = true;
+            for idx in 0..2 {
+                save_replay(self.versus.replay(idx));
+            }
+            let results = VersusResultsScene {
+                winner: self.versus.winner(),
+                settings: self.settings.clone(),
+            };
+            return Transition::Push(Box::new(results));
+        }
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.versus.paint(painter);
+        let keys = ["WASD Q E SPACE LSHIFT", "ARROWS RCTRL / ENTER RSHIFT"];
+        for (idx, keys) in keys.iter().enumerate() {
+            let cx = SCR_WIDTH / 4 + SCR_WIDTH / 2 * idx as i32;
+            let name = format!("PLAYER {}", idx + 1);
+            font::draw_text_centered(painter, cx, 8, &name, 2, LIGHT_YELLOW);
+            font::draw_text_centered(painter, cx, 24, keys, 1, GREY);
+        }
+        painter.draw_vert_line(SCR_WIDTH / 2, 0, SCR_HEIGHT - 1, GREY);
+    }
+
+    fn resume(&mut self) {
+        let settings = self.settings.borrow();
+        self.versus
+            .set_handling(&settings.handling, settings.allow_180);
+        for idx in 0..2 {
+            let game = self.versus.player_mut(idx);
+            game.set_show_ghost(settings.ghost_piece);
+            game.set_effects(settings.effects);
+        }
+        self.versus.release_keys();
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        let volume = self.settings.borrow().master_volume as f32;
+        audio.borrow_mut().set_master_volume(volume);
+    }
+}
+
+/// The winner of a versus game, over the final boards.
+struct VersusResultsScene {
+    winner: Option<usize>,
+    settings: SharedSettings,
+}
+
+impl Scene for VersusResultsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::KpEnter | Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(title_menu(&self.settings))),
+            Event::KeyDown {
+                keycode: Some(Keycode::R),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(VersusScene::new(&self.settings))),
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.6);
+        let cx = SCR_WIDTH / 2;
+        let title = match self.winner {
+            Some(idx) => format!("PLAYER {} WINS", idx + 1),
+            None => "DRAW".to_string(),
+        };
+        font::draw_text_centered(painter, cx, SCR_HEIGHT / 2 - 24, &title, 4, LIGHT_GREEN);
+        let help = "ENTER: TITLE   R: REMATCH";
+        font::draw_text_centered(painter, cx, SCR_HEIGHT / 2 + 8, help, 2, LIGHT_YELLOW);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// Choose a puzzle.
+struct PuzzleSelectScene {
+    ui: Ui,
+    puzzles: Rc<Vec<Puzzle>>,
+    selected: usize,
+    settings: SharedSettings,
+}
+
+impl PuzzleSelectScene {
+    fn new(settings: &SharedSettings) -> Self {
+        PuzzleSelectScene {
+            ui: Ui::new(UiStyle::default()),
+            puzzles: Rc::new(load_puzzles()),
+            selected: 0,
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for PuzzleSelectScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Replace(Box::new(title_menu(&self.settings))),
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let names: Vec<&str> = self.puzzles.iter().map(|p| p.name.as_str()).collect();
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 4, 20, SCR_WIDTH / 2);
+        ui.title("PUZZLES");
+        ui.space(6);
+        let mut play = ui.list_select(&names, &mut self.selected, 10);
+        ui.label(&self.puzzles[self.selected].goal.description());
+        ui.space(6);
+        play |= ui.button("Play");
+        let back = ui.button("Back");
+        ui.end();
+        if play {
+            let scene = PuzzleScene::new(self.puzzles.clone(), self.selected, &self.settings);
+            Transition::Replace(Box::new(scene))
+        } else if back {
+            Transition::Replace(Box::new(title_menu(&self.settings)))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.ui.paint(painter);
+    }
+}
+
+/// Playing a puzzle.
+struct PuzzleScene {
+    puzzle_game: PuzzleGame,
+    puzzles: Rc<Vec<Puzzle>>,
+    idx: usize,
+    settings: SharedSettings,
+    finished: bool,
+}
+
+impl PuzzleScene {
+    fn new(puzzles: Rc<Vec<Puzzle>>, idx: usize, settings: &SharedSettings) -> Self {
+        let mut puzzle_game = PuzzleGame::new(&puzzles[idx]);
+        puzzle_game.set_handling(&settings.borrow().handling, settings.borrow().allow_180);
+        let game = puzzle_game.game_mut();
+        game.set_show_ghost(settings.borrow().ghost_piece);
+        game.set_effects(settings.borrow().effects);
+        let (w, h) = (BOARD_WIDTH * CELL_SIZE, BOARD_HEIGHT * CELL_SIZE);
+        game.set_view(BoardView {
+            x: (SCR_WIDTH - w) / 2,
+            y: (SCR_HEIGHT - h) / 2 + 6,
+            cell_size: CELL_SIZE,
+        });
+        PuzzleScene {
+            puzzle_game,
+            puzzles,
+            idx,
+            settings: settings.clone(),
+            finished: false,
+        }
+    }
+}
+
+impl Scene for PuzzleScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Push(Box::new(pause_menu(&self.settings))),
+            _ => {
+                self.puzzle_game.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        self.puzzle_game.update(elapsed_time);
+        let status = self.puzzle_game.status();
+        if status != PuzzleStatus::Playing && !self.finished {
+            self.finished = true;
+            save_replay(self.puzzle_game.replay());
+            let results = PuzzleResultsScene {
+                solved: status == PuzzleStatus::Solved,
+                puzzles: self.puzzles.clone(),
+                idx: self.idx,
+                settings: self.settings.clone(),
+            };
+            return Transition::Push(Box::new(results));
+        }
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.puzzle_game.paint(painter);
+    }
+
+    fn resume(&mut self) {
+        let settings = self.settings.borrow();
+        self.puzzle_game
+            .set_handling(&settings.handling, settings.allow_180);
+        let game = self.puzzle_game.game_mut();
+        game.set_show_ghost(settings.ghost_piece);
+        game.set_effects(settings.effects);
+        self.puzzle_game.release_keys();
+    }
+}
+
+/// The end of a puzzle, over the final board.
+struct PuzzleResultsScene {
+    solved: bool,
+    puzzles: Rc<Vec<Puzzle>>,
+    idx: usize,
+    settings: SharedSettings,
+}
+
+impl PuzzleResultsScene {
+    /// The puzzle after this one, if it was solved (otherwise, the same one again).
+    fn next_idx(&self) -> Option<usize> {
+        match self.solved {
+            true => Some(self.idx + 1).filter(|&idx| idx < self.puzzles.len()),
+            false => Some(self.idx),
+        }
+    }
+}
+
+impl Scene for PuzzleResultsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        let play = |idx: usize| {
+            let scene = PuzzleScene::new(self.puzzles.clone(), idx, &self.settings);
+            Transition::Reset(Box::new(scene))
+        };
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::KpEnter),
+                repeat: false,
+                ..
+            } => match self.next_idx() {
+                Some(idx) => play(idx),
+                None => Transition::Reset(Box::new(PuzzleSelectScene::new(&self.settings))),
+            },
+            Event::KeyDown {
+                keycode: Some(Keycode::R),
+                repeat: false,
+                ..
+            } => play(self.idx),
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(PuzzleSelectScene::new(&self.settings))),
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.6);
+        let cx = SCR_WIDTH / 2;
+        let (title, color) = match self.solved {
+            true => ("SOLVED!", LIGHT_GREEN),
+            false => ("FAILED", LIGHT_RED),
+        };
+        font::draw_text_centered(painter, cx, SCR_HEIGHT / 2 - 24, title, 4, color);
+        let help = match (self.solved, self.next_idx()) {
+            (true, Some(_)) => "ENTER: NEXT   R: RETRY   ESC: PUZZLES",
+            (true, None) => "ENTER: PUZZLES   R: RETRY",
+            (false, _) => "ENTER: RETRY   ESC: PUZZLES",
+        };
+        font::draw_text_centered(painter, cx, SCR_HEIGHT / 2 + 8, help, 2, LIGHT_YELLOW);
+    }
 
-    fn update_state(&mut self, _elapsed_time: f64) -> bool {
+    fn is_overlay(&self) -> bool {
         true
     }
+}
+
+/// Host a network game (and wait for the opponent), or join one.
+struct NetLobbyScene {
+    ui: Ui,
+    settings: SharedSettings,
+    address: String,
+    host: Option<TcpHost>,
+    message: String,
+}
+
+impl NetLobbyScene {
+    fn new(settings: &SharedSettings) -> Self {
+        NetLobbyScene {
+            ui: Ui::new(UiStyle::default()),
+            settings: settings.clone(),
+            address: format!("127.0.0.1:{DEFAULT_PORT}"),
+            host: None,
+            message: String::new(),
+        }
+    }
+
+    /// The port in the address field (the host listens on all the interfaces).
+    fn port(&self) -> u16 {
+        let port = self.address.rsplit_once(':').map(|(_, port)| port.trim());
+        port.and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PORT)
+    }
+
+    fn play(&self, versus: NetVersus) -> Transition {
+        Transition::Replace(Box::new(NetVersusScene::new(versus, &self.settings)))
+    }
+}
+
+impl Scene for NetLobbyScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Replace(Box::new(title_menu(&self.settings))),
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
+            }
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        if let Some(host) = self.host.as_ref() {
+            match host.accept() {
+                Ok(Some(transport)) => {
+                    let settings = self.settings.borrow();
+                    let versus = NetVersus::host(
+                        Box::new(transport),
+                        fastrand::u64(..),
+                        settings.start_level,
+                        settings.randomizer,
+                    );
+                    drop(settings);
+                    return self.play(versus);
+                }
+                Ok(None) => {}
+                Err(msg) => {
+                    self.message = msg;
+                    self.host = None;
+                }
+            }
+        }
+
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 6, 24, SCR_WIDTH * 2 / 3);
+        ui.title("NETWORK");
+        ui.space(6);
+        ui.text_input("Address", &mut self.address, 40);
+        ui.space(6);
+        let host = ui.button(if self.host.is_some() {
+            "Stop hosting"
+        } else {
+            "Host"
+        });
+        let join = ui.button("Join");
+        let back = ui.button("Back");
+        ui.space(6);
+        ui.label(&self.message);
+        ui.end();
+        if back {
+            return Transition::Replace(Box::new(title_menu(&self.settings)));
+        }
+        if host {
+            if self.host.take().is_some() {
+                self.message.clear();
+            } else {
+                match TcpHost::bind(&format!("0.0.0.0:{}", self.port())) {
+                    Ok(host) => {
+                        self.message = format!("Waiting on port {}...", host.port());
+                        self.host = Some(host);
+                    }
+                    Err(msg) => self.message = msg,
+                }
+            }
+        } else if join {
+            self.host = None;
+            match TcpTransport::connect(self.address.trim()) {
+                Ok(transport) => return self.play(NetVersus::join(Box::new(transport))),
+                Err(msg) => self.message = msg,
+            }
+        }
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.ui.paint(painter);
+    }
+}
+
+/// A versus game against a player on another computer. There is no pause: the game goes on for the opponent.
+struct NetVersusScene {
+    versus: NetVersus,
+    settings: SharedSettings,
+    /// The replay of the local game is saved when it ends.
+    saved: bool,
+}
+
+impl NetVersusScene {
+    fn new(mut versus: NetVersus, settings: &SharedSettings) -> Self {
+        let s = settings.borrow();
+        versus.set_game_options(&s.handling, s.ghost_piece, s.allow_180);
+        versus.set_effects(s.effects);
+        versus.set_views(half_screen_view(0), half_screen_view(1));
+        drop(s);
+        NetVersusScene {
+            versus,
+            settings: settings.clone(),
+            saved: false,
+        }
+    }
+}
+
+impl Scene for NetVersusScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        let playing = matches!(
+            self.versus.status(),
+            NetStatus::Handshake | NetStatus::Playing
+        );
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(title_menu(&self.settings))),
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::KpEnter),
+                repeat: false,
+                ..
+            } if !playing => Transition::Reset(Box::new(title_menu(&self.settings))),
+            _ if !self.versus.handle_event(event) => Transition::Quit,
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        if !self.versus.update_state(elapsed_time) {
+            return Transition::Quit;
+        }
+        let over = !matches!(
+            self.versus.status(),
+            NetStatus::Handshake | NetStatus::Playing
+        );
+        if let Some(replay) = self.versus.replay().filter(|_| over && !self.saved) {
+            self.saved = true;
+            save_replay(replay);
+        }
+        Transition::None
+    }
 
     fn paint(&self, painter: &mut dyn Painter) {
-        // TEMPORARY - draw random pixels
-        for y in 0..SCR_HEIGHT {
-            for x in 0..SCR_WIDTH {
-                let r: u8 = fastrand::u8(0..=255);
-                let g: u8 = fastrand::u8(0..=255);
-                let b: u8 = fastrand::u8(0..=255);
-                painter.draw_pixel(x, y, RGB::from(r, g, b));
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.versus.paint(painter);
+        for (idx, name) in ["YOU", "OPPONENT"].iter().enumerate() {
+            let cx = SCR_WIDTH / 4 + SCR_WIDTH / 2 * idx as i32;
+            font::draw_text_centered(painter, cx, 8, name, 2, LIGHT_YELLOW);
+        }
+        painter.draw_vert_line(SCR_WIDTH / 2, 0, SCR_HEIGHT - 1, GREY);
+
+        let (title, detail, color) = match self.versus.status() {
+            NetStatus::Handshake => ("CONNECTING", "", LIGHT_YELLOW),
+            NetStatus::Playing => return,
+            NetStatus::Won => ("YOU WIN", "", LIGHT_GREEN),
+            NetStatus::Lost => ("YOU LOSE", "", LIGHT_RED),
+            NetStatus::Disconnected(reason) => ("DISCONNECTED", reason.as_str(), LIGHT_RED),
+        };
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.6);
+        let cy = SCR_HEIGHT / 2;
+        font::draw_text_centered(painter, SCR_WIDTH / 2, cy - 24, title, 4, color);
+        font::draw_text_centered(painter, SCR_WIDTH / 2, cy + 4, detail, 1, WHITE);
+        let help = if self.versus.status() == &NetStatus::Handshake {
+            "ESCAPE: CANCEL"
+        } else {
+            "ENTER: TITLE"
+        };
+        font::draw_text_centered(painter, SCR_WIDTH / 2, cy + 20, help, 2, LIGHT_YELLOW);
+    }
+
+    fn attach_audio(&mut self, audio: AudioHandle) {
+        let volume = self.settings.borrow().master_volume as f32;
+        audio.borrow_mut().set_master_volume(volume);
+    }
+}
+
+/// Choose a replay to watch.
+struct ReplaysScene {
+    ui: Ui,
+    files: Vec<PathBuf>,
+    selected: usize,
+    error: Option<String>,
+    settings: SharedSettings,
+}
+
+impl ReplaysScene {
+    fn new(settings: &SharedSettings) -> Self {
+        ReplaysScene {
+            ui: Ui::new(UiStyle::default()),
+            files: replay_files(),
+            selected: 0,
+            error: None,
+            settings: settings.clone(),
+        }
+    }
+
+    fn watch(&self) -> Result<ReplayScene, String> {
+        let path = self.files.get(self.selected).ok_or("no replay")?;
+        let replay = GameReplay::load(&path.to_string_lossy())?;
+        let pieces = load_piece_set(&replay.piece_set)?;
+        Ok(ReplayScene::new(
+            ReplayPlayback::new(replay, pieces),
+            &self.settings,
+        ))
+    }
+}
+
+impl Scene for ReplaysScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => Transition::Replace(Box::new(title_menu(&self.settings))),
+            _ => {
+                self.ui.handle_event(event);
+                Transition::None
             }
         }
     }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        let names: Vec<String> = self
+            .files
+            .iter()
+            .map(|path| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let ui = &mut self.ui;
+        ui.begin(SCR_WIDTH / 8, 20, SCR_WIDTH * 3 / 4);
+        ui.title("REPLAYS");
+        ui.space(6);
+        let mut watch = false;
+        if names.is_empty() {
+            ui.label("No replays yet: play a game!");
+        } else {
+            watch = ui.list_select(&names, &mut self.selected, 10);
+        }
+        if let Some(msg) = &self.error {
+            ui.label(msg);
+        }
+        ui.space(6);
+        watch |= !names.is_empty() && ui.button("Watch");
+        let back = ui.button("Back");
+        ui.end();
+        if watch {
+            match self.watch() {
+                Ok(scene) => return Transition::Replace(Box::new(scene)),
+                Err(msg) => self.error = Some(msg),
+            }
+        }
+        if back {
+            Transition::Replace(Box::new(title_menu(&self.settings)))
+        } else {
+            Transition::None
+        }
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.ui.paint(painter);
+    }
+}
+
+/// The playback speeds, from Down to Up.
+const REPLAY_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+/// How far Left and Right go back or forward in the replay, in seconds.
+const REPLAY_SCRUB_STEP: f64 = 5.0;
+
+/// Watching a replay: Space pauses, Left / Right go back / forward, Up / Down change the speed,
+/// and Home goes back to the start.
+struct ReplayScene {
+    playback: ReplayPlayback,
+    speed_idx: usize,
+    settings: SharedSettings,
+}
+
+impl ReplayScene {
+    fn new(mut playback: ReplayPlayback, settings: &SharedSettings) -> Self {
+        // a bit smaller than in the game, to make room for the controls below
+        let cell_size = CELL_SIZE - 1;
+        playback.game_mut().set_effects(settings.borrow().effects);
+        playback.game_mut().set_view(BoardView {
+            x: (SCR_WIDTH - BOARD_WIDTH * cell_size) / 2,
+            y: 8,
+            cell_size,
+        });
+        ReplayScene {
+            playback,
+            speed_idx: 2,
+            settings: settings.clone(),
+        }
+    }
+
+    /// Jump forward (or back, if negative) by some seconds.
+    fn scrub(&mut self, seconds: f64) {
+        let ticks = (seconds / REPLAY_TICK).round() as i64;
+        let tick = (self.playback.tick() as i64 + ticks).max(0);
+        self.playback.seek(tick as u64);
+    }
+}
+
+impl Scene for ReplayScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        let Event::KeyDown {
+            keycode: Some(key), ..
+        } = event
+        else {
+            return Transition::None;
+        };
+        match *key {
+            Keycode::Escape => {
+                return Transition::Replace(Box::new(ReplaysScene::new(&self.settings)))
+            }
+            Keycode::Space => self.playback.set_paused(!self.playback.is_paused()),
+            Keycode::Left => self.scrub(-REPLAY_SCRUB_STEP),
+            Keycode::Right => self.scrub(REPLAY_SCRUB_STEP),
+            Keycode::Home => self.playback.seek(0),
+            Keycode::Up => self.speed_idx = (self.speed_idx + 1).min(REPLAY_SPEEDS.len() - 1),
+            Keycode::Down => self.speed_idx = self.speed_idx.saturating_sub(1),
+            _ => {}
+        }
+        self.playback.set_speed(REPLAY_SPEEDS[self.speed_idx]);
+        Transition::None
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> Transition {
+        self.playback.update(elapsed_time);
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        self.playback.paint(painter);
+
+        // the position in the replay
+        let (x, y, w) = (40, SCR_HEIGHT - 28, SCR_WIDTH - 80);
+        let duration = self.playback.replay().duration();
+        let progress = match duration > 0.0 {
+            true => (self.playback.time() / duration).min(1.0),
+            false => 1.0,
+        };
+        painter.draw_rect(x, y, w, 6, GREY);
+        painter.fill_rect(
+            x + 1,
+            y + 1,
+            ((w - 2) as f64 * progress) as i32,
+            4,
+            LIGHT_CYAN,
+        );
+        let status = match (self.playback.is_paused(), self.playback.is_finished()) {
+            (true, _) => "PAUSED".to_string(),
+            (_, true) => "END".to_string(),
+            _ => format!("X{}", self.playback.speed()),
+        };
+        let time = format!(
+            "{} / {}   {status}",
+            format_time(self.playback.time()),
+            format_time(duration)
+        );
+        font::draw_text_centered(painter, SCR_WIDTH / 2, y - 10, &time, 1, WHITE);
+        let help = "SPACE PAUSE  LEFT/RIGHT SCRUB  UP/DOWN SPEED  HOME RESTART  ESC BACK";
+        font::draw_text_centered(painter, SCR_WIDTH / 2, y + 12, help, 1, GREY);
+    }
+}
+
+/// Where the custom piece sets are (`None` if the config directory is unknown).
+fn piece_sets_dir() -> Option<PathBuf> {
+    storage::config_dir("tetrust").map(|dir| dir.join("pieces"))
+}
+
+/// The built-in piece sets, then the custom ones (the files in the piece sets directory, without extension).
+fn piece_set_names() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_PIECE_SETS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let files = piece_sets_dir().and_then(|dir| std::fs::read_dir(dir).ok());
+    let mut custom: Vec<String> = files
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let is_text = path.extension().is_some_and(|ext| ext == "txt");
+            is_text.then(|| path.file_stem()?.to_str().map(str::to_string))?
+        })
+        .collect();
+    custom.sort();
+    names.extend(custom);
+    names
+}
+
+fn load_piece_set(name: &str) -> Result<Arc<PieceSet>, String> {
+    if let Some(set) = PieceSet::builtin(name) {
+        return Ok(Arc::new(set));
+    }
+    let dir = piece_sets_dir().ok_or("unknown configuration directory")?;
+    let path = dir.join(format!("{name}.txt"));
+    PieceSet::load(&path.to_string_lossy()).map(Arc::new)
+}
+
+/// The built-in puzzles, then the ones in `puzzles.txt` in the configuration directory, if there is one.
+fn load_puzzles() -> Vec<Puzzle> {
+    let mut puzzles = PuzzleSet::builtin().puzzles;
+    let path = storage::config_dir("tetrust").map(|dir| dir.join("puzzles.txt"));
+    if let Some(path) = path.filter(|path| path.exists()) {
+        match PuzzleSet::load(&path.to_string_lossy()) {
+            Ok(set) => puzzles.extend(set.puzzles),
+            Err(msg) => println!("ERROR: {msg}"),
+        }
+    }
+    puzzles
+}
+
+/// Save a replay in the replays folder, named after its date, mode and seed.
+fn save_replay(replay: &GameReplay) {
+    let Some(dir) = replays_dir() else {
+        return;
+    };
+    let name = format!(
+        "{}_{}_{:016x}",
+        storage::today(),
+        replay.mode.name(),
+        replay.seed
+    );
+    // (the races against the ghost, the versus players and the puzzles share their seeds)
+    let path = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{name}.txt")),
+            _ => dir.join(format!("{name}_{n}.txt")),
+        })
+        .find(|path| !path.exists())
+        .unwrap();
+    if let Err(msg) = storage::save_file(&path, &replay.to_text()) {
+        println!("ERROR: {msg}");
+    }
+}
+
+/// Where the replays of the games are saved (`None` if the config directory is unknown).
+fn replays_dir() -> Option<PathBuf> {
+    storage::config_dir("tetrust").map(|dir| dir.join("replays"))
+}
+
+/// The replays, newest first.
+fn replay_files() -> Vec<PathBuf> {
+    let files = replays_dir().and_then(|dir| std::fs::read_dir(dir).ok());
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = files
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            let is_text = path.extension().is_some_and(|ext| ext == "txt");
+            is_text.then_some((entry.metadata().ok()?.modified().ok()?, path))
+        })
+        .collect();
+    files.sort_by(|a, b| b.cmp(a));
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Where the replay of the fastest sprint is saved (`None` if the config directory is unknown).
+fn sprint_ghost_path() -> Option<PathBuf> {
+    storage::config_dir("tetrust").map(|dir| dir.join("sprint_ghost.txt"))
+}
+
+fn load_sprint_ghost() -> Option<GameReplay> {
+    let path = sprint_ghost_path().filter(|path| path.exists())?;
+    GameReplay::load(&path.to_string_lossy())
+        .map_err(|msg| println!("ERROR: {msg}"))
+        .ok()
+}
+
+/// Where the high scores are saved (`None` if the config directory is unknown).
+fn high_scores_path() -> Option<PathBuf> {
+    storage::config_dir("tetrust").map(|dir| dir.join("highscores.txt"))
+}
+
+fn load_high_scores() -> HighScores {
+    high_scores_path()
+        .map(|path| HighScores::load(&path))
+        .unwrap_or_default()
+}
+
+/// Paint the high-score table of a mode, optionally highlighting one entry.
+fn paint_high_scores(
+    painter: &mut dyn Painter,
+    table: &[HighScore],
+    y: i32,
+    highlight: Option<usize>,
+) {
+    let x = (SCR_WIDTH - font::text_width(&format_high_score(0, None), 2)) / 2;
+    painter.draw_text(x, y, &format_high_score(0, None), 2, GREY);
+    for (idx, entry) in table.iter().enumerate() {
+        let color = if highlight == Some(idx) {
+            LIGHT_YELLOW
+        } else {
+            WHITE
+        };
+        let line = format_high_score(idx, Some(entry));
+        painter.draw_text(x, y + 12 * (idx as i32 + 1), &line, 2, color);
+    }
+    if table.is_empty() {
+        font::draw_text_centered(painter, SCR_WIDTH / 2, y + 24, "NO SCORES YET", 2, GREY);
+    }
+}
+
+/// A row of the high-score table (the header if there is no entry).
+fn format_high_score(idx: usize, entry: Option<&HighScore>) -> String {
+    match entry {
+        Some(e) => format!(
+            "{:>2} {:<10} {:>8} {:>3} {:>8} {}",
+            idx + 1,
+            e.name,
+            e.score,
+            e.lines,
+            format_time(e.time),
+            e.date
+        ),
+        None => format!(
+            "{:>2} {:<10} {:>8} {:>3} {:>8} {:<10}",
+            "#", "NAME", "SCORE", "LNS", "TIME", "DATE"
+        ),
+    }
+}
+
+/// The result screen, over the finished game. Qualifying games first ask for the player's name.
+struct ResultsScene {
+    ui: Ui,
+    result: GameResult,
+    settings: SharedSettings,
+    high_scores: HighScores,
+    /// The position in the high-score table (while entering the name: the one the game would get).
+    rank: Option<usize>,
+    entering_name: bool,
+    name: String,
+    error: Option<String>,
+}
+
+impl ResultsScene {
+    fn new(result: GameResult, eligible: bool, settings: &SharedSettings) -> Self {
+        let high_scores = load_high_scores();
+        let rank = high_scores.rank(&result).filter(|_| eligible);
+        ResultsScene {
+            ui: Ui::new(UiStyle::default()),
+            result,
+            settings: settings.clone(),
+            high_scores,
+            rank,
+            entering_name: rank.is_some(),
+            name: settings.borrow().player_name.clone(),
+            error: None,
+        }
+    }
+
+    /// Add the game to the high scores, with the entered name, and save them.
+    fn submit_name(&mut self) {
+        let name = sanitize_name(&self.name);
+        self.settings.borrow_mut().player_name = name.clone();
+        self.rank = self
+            .high_scores
+            .insert(&self.result, &name, &storage::today());
+        self.entering_name = false;
+        let saved = match high_scores_path() {
+            Some(path) => self.high_scores.save(&path),
+            None => Err("Unknown config directory".to_string()),
+        };
+        self.error = saved.err();
+    }
+}
+
+impl Scene for ResultsScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        if self.entering_name {
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    repeat: false,
+                    ..
+                } => self.submit_name(),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    repeat: false,
+                    ..
+                } => {
+                    // skip the high score
+                    self.entering_name = false;
+                    self.rank = None;
+                }
+                _ => self.ui.handle_event(event),
+            }
+            return Transition::None;
+        }
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return | Keycode::Escape),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(title_menu(&self.settings))),
+            Event::KeyDown {
+                keycode: Some(Keycode::R),
+                repeat: false,
+                ..
+            } => Transition::Reset(Box::new(GameplayScene::new(&self.settings))),
+            _ => Transition::None,
+        }
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        if self.entering_name {
+            let ui = &mut self.ui;
+            ui.begin(SCR_WIDTH / 6, 100, SCR_WIDTH * 2 / 3);
+            ui.panel(SCR_WIDTH / 6 - 8, 92, SCR_WIDTH * 2 / 3 + 16, 84);
+            ui.title("NEW HIGH SCORE!");
+            ui.space(6);
+            ui.text_input("Name", &mut self.name, MAX_NAME_LEN);
+            let ok = ui.button("OK");
+            ui.end();
+            if ok {
+                self.submit_name();
+            }
+        }
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        dim_screen(painter, SCR_WIDTH, SCR_HEIGHT, 0.6);
+        let result = &self.result;
+        let title_color = match result.outcome {
+            Outcome::Completed => LIGHT_GREEN,
+            Outcome::ToppedOut | Outcome::OutOfPieces => LIGHT_RED,
+        };
+        let cx = SCR_WIDTH / 2;
+        font::draw_text_centered(painter, cx, 12, result.mode.name(), 2, LIGHT_GREY);
+        font::draw_text_centered(painter, cx, 28, result.title(), 4, title_color);
+        let stats = format!(
+            "SCORE {}  LINES {}  LEVEL {}  TIME {}",
+            result.score,
+            result.lines,
+            result.level,
+            format_time(result.time)
+        );
+        font::draw_text_centered(painter, cx, 60, &stats, 2, WHITE);
+        if self.entering_name {
+            self.ui.paint(painter);
+            return;
+        }
+        paint_high_scores(painter, self.high_scores.table(result.mode), 84, self.rank);
+        if let Some(error) = &self.error {
+            let text = format!("NOT SAVED: {error}");
+            font::draw_text_centered(painter, cx, SCR_HEIGHT - 44, &text, 1, LIGHT_RED);
+        }
+        let help = "ENTER: TITLE   R: RETRY";
+        font::draw_text_centered(painter, cx, SCR_HEIGHT - 24, help, 2, LIGHT_YELLOW);
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// Browse the high-score tables of all the modes.
+struct HighScoresScene {
+    high_scores: HighScores,
+    mode_idx: usize,
+    settings: SharedSettings,
+}
+
+impl HighScoresScene {
+    fn new(settings: &SharedSettings) -> Self {
+        let mode = settings.borrow().mode;
+        HighScoresScene {
+            high_scores: load_high_scores(),
+            mode_idx: GameMode::ALL.iter().position(|&m| m == mode).unwrap_or(0),
+            settings: settings.clone(),
+        }
+    }
+}
+
+impl Scene for HighScoresScene {
+    fn handle_event(&mut self, event: &Event) -> Transition {
+        let count = GameMode::ALL.len();
+        if let Event::KeyDown {
+            keycode: Some(key), ..
+        } = event
+        {
+            match *key {
+                Keycode::Left => self.mode_idx = (self.mode_idx + count - 1) % count,
+                Keycode::Right => self.mode_idx = (self.mode_idx + 1) % count,
+                Keycode::Escape | Keycode::Return => {
+                    return Transition::Replace(Box::new(title_menu(&self.settings)))
+                }
+                _ => {}
+            }
+        }
+        Transition::None
+    }
+
+    fn update_state(&mut self, _elapsed_time: f64) -> Transition {
+        Transition::None
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        painter.fill_rect(0, 0, SCR_WIDTH, SCR_HEIGHT, BLACK);
+        let cx = SCR_WIDTH / 2;
+        let mode = GameMode::ALL[self.mode_idx];
+        font::draw_text_centered(painter, cx, 16, "HIGH SCORES", 4, LIGHT_YELLOW);
+        let title = format!("< {} >", mode.name());
+        font::draw_text_centered(painter, cx, 50, &title, 2, WHITE);
+        paint_high_scores(painter, self.high_scores.table(mode), 76, None);
+        let help = "LEFT/RIGHT: MODE   ENTER: BACK";
+        font::draw_text_centered(painter, cx, SCR_HEIGHT - 24, help, 2, GREY);
+    }
 }
@@ -0,0 +1,152 @@
+//! Game modes: each one with its own goal and end conditions.
+
+/// Lines to clear in Sprint mode.
+pub const SPRINT_LINES: u32 = 40;
+/// Duration of an Ultra game, in seconds.
+pub const ULTRA_DURATION: f64 = 120.0;
+/// The last level of a Marathon game.
+pub const MARATHON_MAX_LEVEL: u32 = 15;
+
+/// The game modes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// Play until clearing level 15.
+    Marathon,
+    /// Clear 40 lines, as fast as possible.
+    Sprint,
+    /// Score as much as possible in 2 minutes.
+    Ultra,
+    /// Endless and relaxed: there is no game over.
+    Zen,
+    /// Against another player: the last one standing wins (not in [`GameMode::ALL`], which are the solo modes).
+    Versus,
+    /// Reach the goal of a puzzle, with its pieces (not in [`GameMode::ALL`] either).
+    Puzzle,
+}
+
+/// How a game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The goal of the mode was reached (for Ultra: the time is up).
+    Completed,
+    /// The stack reached the top.
+    ToppedOut,
+    /// All the pieces were placed, without reaching the goal (for the games with a fixed number of pieces).
+    OutOfPieces,
+}
+
+/// The final stats of a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameResult {
+    pub mode: GameMode,
+    pub outcome: Outcome,
+    pub score: u64,
+    pub lines: u32,
+    pub level: u32,
+    /// Seconds played.
+    pub time: f64,
+}
+
+impl GameResult {
+    /// The headline of the result screen.
+    pub fn title(&self) -> &'static str {
+        match (self.mode, self.outcome) {
+            (_, Outcome::ToppedOut) => "GAME OVER",
+            (_, Outcome::OutOfPieces) => "OUT OF PIECES",
+            (GameMode::Ultra, Outcome::Completed) => "TIME UP",
+            (_, Outcome::Completed) => "CLEAR!",
+        }
+    }
+}
+
+impl GameMode {
+    pub const ALL: &'static [GameMode] = &[
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Zen,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "MARATHON",
+            GameMode::Sprint => "SPRINT",
+            GameMode::Ultra => "ULTRA",
+            GameMode::Zen => "ZEN",
+            GameMode::Versus => "VERSUS",
+            GameMode::Puzzle => "PUZZLE",
+        }
+    }
+
+    /// The mode with the given name (as returned by [`GameMode::name`]).
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        GameMode::ALL.iter().copied().find(|m| m.name() == name)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Clear level 15",
+            GameMode::Sprint => "Clear 40 lines, fast",
+            GameMode::Ultra => "Max score in 2 minutes",
+            GameMode::Zen => "No game over",
+            GameMode::Versus => "Two players",
+            GameMode::Puzzle => "Predefined challenges",
+        }
+    }
+
+    /// Check if the goal of the mode was reached, after `time` seconds of play.
+    pub fn is_completed(&self, lines: u32, level: u32, time: f64) -> bool {
+        match self {
+            GameMode::Marathon => level > MARATHON_MAX_LEVEL,
+            GameMode::Sprint => lines >= SPRINT_LINES,
+            GameMode::Ultra => time >= ULTRA_DURATION,
+            // the goal of a puzzle is checked by the puzzle itself
+            GameMode::Zen | GameMode::Versus | GameMode::Puzzle => false,
+        }
+    }
+
+    /// In Zen mode, topping out just clears the board.
+    #[inline]
+    pub fn can_top_out(&self) -> bool {
+        *self != GameMode::Zen
+    }
+
+    /// Sprint games are ranked by time, the others by score.
+    #[inline]
+    pub fn is_timed(&self) -> bool {
+        *self == GameMode::Sprint
+    }
+}
+
+/// Format a time as "M:SS.CC".
+pub fn format_time(seconds: f64) -> String {
+    let hundredths = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        (hundredths / 100) % 60,
+        hundredths % 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_goals() {
+        assert!(!GameMode::Sprint.is_completed(39, 4, 100.0));
+        assert!(GameMode::Sprint.is_completed(40, 5, 100.0));
+        assert!(!GameMode::Marathon.is_completed(149, 15, 0.0));
+        assert!(GameMode::Marathon.is_completed(150, 16, 0.0));
+        assert!(GameMode::Ultra.is_completed(0, 1, ULTRA_DURATION));
+        assert!(!GameMode::Zen.is_completed(10_000, 99, 1e6));
+    }
+
+    #[test]
+    fn times_are_formatted_with_hundredths() {
+        assert_eq!("0:00.00", format_time(0.0));
+        assert_eq!("1:05.25", format_time(65.25));
+        assert_eq!("12:00.00", format_time(720.0));
+    }
+}
@@ -0,0 +1,877 @@
+//! Versus games over the network: each side plays its own game, and the two instances exchange garbage attacks,
+//! snapshots of their boards and the end of their games.
+//!
+//! The messages are binary frames: a 2 bytes length (big endian), then the message type and its payload.
+//! The first message of each side is a `Hello`, with the protocol version; the host's `Hello` also sets the seed,
+//! the randomizer and the start level, so both players get the same pieces.
+//!
+//! The attacks don't need both games to run in lockstep: the garbage is queued when it arrives, so some lag only
+//! delays it a bit. If nothing arrives for [`CONNECTION_TIMEOUT`] seconds, the opponent is considered gone.
+
+use crate::board::*;
+use crate::effects::*;
+use crate::game::*;
+use crate::generator::*;
+use crate::handling::*;
+use crate::mode::*;
+use crate::render::*;
+use crate::replay::*;
+use crate::tetrimino::PieceSet;
+use game_framework::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Increased on each incompatible change of the messages.
+pub const PROTOCOL_VERSION: u16 = 1;
+/// The default TCP port.
+pub const DEFAULT_PORT: u16 = 7878;
+/// Seconds without messages after which the connection is considered lost.
+pub const CONNECTION_TIMEOUT: f64 = 5.0;
+/// Seconds between the board snapshots (which are also the keep-alive messages).
+const STATE_INTERVAL: f64 = 0.1;
+/// Identifies the `Hello` of TetRusT.
+const MAGIC: &[u8; 4] = b"TTRS";
+/// Larger frames are rejected.
+const MAX_FRAME_LEN: usize = 4096;
+/// More garbage than the height of a board at once makes no difference: the rest is dropped.
+const MAX_GARBAGE_LINES: u32 = BOARD_HEIGHT as u32;
+
+const MSG_HELLO: u8 = 1;
+const MSG_GARBAGE: u8 = 2;
+const MSG_STATE: u8 = 3;
+const MSG_GAME_OVER: u8 = 4;
+const MSG_BYE: u8 = 5;
+
+/// The messages between the two players.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// The handshake (the client's `Hello` repeats the values of the host).
+    Hello {
+        seed: u64,
+        start_level: u32,
+        randomizer: Randomizer,
+    },
+    /// An attack: garbage lines for the receiver.
+    Garbage { lines: u32, hole_x: i32 },
+    /// A snapshot of the sender's game: its visible rows (with the falling piece), lines and pending garbage.
+    State {
+        lines: u32,
+        pending_garbage: u32,
+        board: Board,
+    },
+    /// The sender topped out.
+    GameOver,
+    /// The sender left.
+    Bye,
+}
+
+impl Message {
+    /// Encode the message as a frame.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0, 0];
+        match self {
+            Message::Hello {
+                seed,
+                start_level,
+                randomizer,
+            } => {
+                let randomizer = Randomizer::ALL.iter().position(|r| r == randomizer);
+                data.push(MSG_HELLO);
+                data.extend_from_slice(MAGIC);
+                data.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+                data.extend_from_slice(&seed.to_be_bytes());
+                data.push((*start_level).min(255) as u8);
+                data.push(randomizer.unwrap_or(0) as u8);
+            }
+            Message::Garbage { lines, hole_x } => {
+                data.extend_from_slice(&[MSG_GARBAGE, (*lines).min(255) as u8, *hole_x as u8]);
+            }
+            Message::State {
+                lines,
+                pending_garbage,
+                board,
+            } => {
+                data.push(MSG_STATE);
+                data.extend_from_slice(&((*lines).min(65535) as u16).to_be_bytes());
+                data.push((*pending_garbage).min(255) as u8);
+                data.push(board.width() as u8);
+                data.push(board.height() as u8);
+                for y in 0..board.height() {
+                    for x in 0..board.width() {
+                        data.push(match board.cell(x, y) {
+                            None => 0,
+                            Some(GARBAGE_CELL) => 255,
+                            Some(color_idx) => color_idx.min(253) + 1,
+                        });
+                    }
+                }
+            }
+            Message::GameOver => data.push(MSG_GAME_OVER),
+            Message::Bye => data.push(MSG_BYE),
+        }
+        let len = (data.len() - 2) as u16;
+        data[..2].copy_from_slice(&len.to_be_bytes());
+        data
+    }
+
+    /// Decode the first frame of the data. Returns the message and the length of its frame,
+    /// or `None` if the frame is not complete yet.
+    pub fn decode(data: &[u8]) -> Result<Option<(Message, usize)>, String> {
+        if data.len() < 2 {
+            return Ok(None);
+        }
+        let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+        if len == 0 || len > MAX_FRAME_LEN {
+            return Err(format!("invalid message length: {len}"));
+        }
+        let Some(frame) = data.get(2..2 + len) else {
+            return Ok(None);
+        };
+        let (kind, payload) = (frame[0], &frame[1..]);
+        let err = || format!("invalid message (type {kind}, {} bytes)", payload.len());
+        let message = match kind {
+            MSG_HELLO => {
+                if payload.len() < 6 || &payload[..4] != MAGIC {
+                    return Err("not a TetRusT player".to_string());
+                }
+                let version = u16::from_be_bytes([payload[4], payload[5]]);
+                if version != PROTOCOL_VERSION {
+                    return Err(format!(
+                        "protocol version {version} (this version of TetRusT uses {PROTOCOL_VERSION})"
+                    ));
+                }
+                if payload.len() != 16 {
+                    return Err(err());
+                }
+                Message::Hello {
+                    seed: u64::from_be_bytes(payload[6..14].try_into().unwrap()),
+                    start_level: payload[14] as u32,
+                    randomizer: *Randomizer::ALL.get(payload[15] as usize).ok_or_else(err)?,
+                }
+            }
+            MSG_GARBAGE if payload.len() == 2 => {
+                let hole_x = payload[1] as i32;
+                if hole_x >= BOARD_WIDTH {
+                    return Err(err());
+                }
+                Message::Garbage {
+                    lines: payload[0] as u32,
+                    hole_x,
+                }
+            }
+            MSG_STATE if payload.len() >= 5 => {
+                let (width, height) = (payload[3] as i32, payload[4] as i32);
+                let cells = &payload[5..];
+                if width == 0 || height == 0 || cells.len() != (width * height) as usize {
+                    return Err(err());
+                }
+                let mut board = Board::new(width, height, 0);
+                for (idx, &cell) in cells.iter().enumerate() {
+                    let cell = match cell {
+                        0 => None,
+                        255 => Some(GARBAGE_CELL),
+                        color => Some(color - 1),
+                    };
+                    board.set_cell(idx as i32 % width, idx as i32 / width, cell);
+                }
+                Message::State {
+                    lines: u16::from_be_bytes([payload[0], payload[1]]) as u32,
+                    pending_garbage: payload[2] as u32,
+                    board,
+                }
+            }
+            MSG_GAME_OVER if payload.is_empty() => Message::GameOver,
+            MSG_BYE if payload.is_empty() => Message::Bye,
+            _ => return Err(err()),
+        };
+        Ok(Some((message, 2 + len)))
+    }
+}
+
+//-------------------------------------------------------
+//  Transports
+
+/// A way to exchange bytes with the other player, without blocking.
+pub trait Transport {
+    /// Send the data (or queue it, to be sent as soon as possible).
+    fn send(&mut self, data: &[u8]) -> Result<(), String>;
+
+    /// Append the data received so far to the buffer. Fails once the other side is gone.
+    fn receive(&mut self, buffer: &mut Vec<u8>) -> Result<(), String>;
+}
+
+/// A TCP connection, in non-blocking mode.
+pub struct TcpTransport {
+    stream: TcpStream,
+    outgoing: Vec<u8>,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        Ok(TcpTransport {
+            stream,
+            outgoing: Vec::new(),
+        })
+    }
+
+    /// Connect to a host (waiting at most a few seconds).
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let err = |e: std::io::Error| format!("{address}: {e}");
+        let mut addresses = address.to_socket_addrs().map_err(err)?;
+        let address = addresses
+            .next()
+            .ok_or_else(|| format!("{address}: unknown host"))?;
+        let stream = TcpStream::connect_timeout(&address, Duration::from_secs(3)).map_err(err)?;
+        Self::new(stream)
+    }
+
+    /// Write as much of the outgoing data as the socket accepts.
+    fn flush(&mut self) -> Result<(), String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err("connection closed".to_string()),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        self.outgoing.extend_from_slice(data);
+        self.flush()
+    }
+
+    fn receive(&mut self, buffer: &mut Vec<u8>) -> Result<(), String> {
+        self.flush()?;
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("connection closed".to_string()),
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Waits for a player to join, without blocking.
+pub struct TcpHost {
+    listener: TcpListener,
+}
+
+impl TcpHost {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("{address}: {e}"))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(TcpHost { listener })
+    }
+
+    /// The port it listens to (useful after binding port 0).
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |a| a.port())
+    }
+
+    /// The connection of the player who joined, if any.
+    pub fn accept(&self) -> Result<Option<TcpTransport>, String> {
+        match self.listener.accept() {
+            Ok((stream, _)) => TcpTransport::new(stream).map(Some),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// One direction of a loopback connection.
+#[derive(Default)]
+struct LoopbackChannel {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+/// An in-process connection, for tests: see [`loopback_pair`].
+pub struct LoopbackTransport {
+    outgoing: Rc<RefCell<LoopbackChannel>>,
+    incoming: Rc<RefCell<LoopbackChannel>>,
+}
+
+/// Two connected transports: what is sent by one is received by the other.
+/// Dropping one of them closes the connection.
+pub fn loopback_pair() -> (LoopbackTransport, LoopbackTransport) {
+    let a = Rc::new(RefCell::new(LoopbackChannel::default()));
+    let b = Rc::new(RefCell::new(LoopbackChannel::default()));
+    (
+        LoopbackTransport {
+            outgoing: a.clone(),
+            incoming: b.clone(),
+        },
+        LoopbackTransport {
+            outgoing: b,
+            incoming: a,
+        },
+    )
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        if self.incoming.borrow().closed {
+            return Err("connection closed".to_string());
+        }
+        self.outgoing.borrow_mut().data.extend(data);
+        Ok(())
+    }
+
+    fn receive(&mut self, buffer: &mut Vec<u8>) -> Result<(), String> {
+        let mut incoming = self.incoming.borrow_mut();
+        if incoming.closed && incoming.data.is_empty() {
+            return Err("connection closed".to_string());
+        }
+        buffer.extend(incoming.data.drain(..));
+        Ok(())
+    }
+}
+
+impl Drop for LoopbackTransport {
+    fn drop(&mut self) {
+        self.outgoing.borrow_mut().closed = true;
+    }
+}
+
+//-------------------------------------------------------
+//  Connection
+
+/// Messages over a transport, with a timeout.
+pub struct Connection {
+    transport: Box<dyn Transport>,
+    buffer: Vec<u8>,
+    silence: f64,
+}
+
+impl Connection {
+    pub fn new(transport: Box<dyn Transport>) -> Self {
+        Connection {
+            transport,
+            buffer: Vec::new(),
+            silence: 0.0,
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        self.transport.send(&message.encode())
+    }
+
+    /// The messages received so far. Fails if the other side is gone, is silent for too long, or sends garbage
+    /// (the other kind).
+    pub fn receive(&mut self, elapsed_time: f64) -> Result<Vec<Message>, String> {
+        self.transport.receive(&mut self.buffer)?;
+        let mut messages = vec![];
+        let mut used = 0;
+        while let Some((message, len)) = Message::decode(&self.buffer[used..])? {
+            messages.push(message);
+            used += len;
+        }
+        self.buffer.drain(..used);
+        if messages.is_empty() {
+            self.silence += elapsed_time;
+            if self.silence >= CONNECTION_TIMEOUT {
+                return Err("connection timed out".to_string());
+            }
+        } else {
+            self.silence = 0.0;
+        }
+        Ok(messages)
+    }
+}
+
+//-------------------------------------------------------
+//  The game
+
+/// The state of a network game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetStatus {
+    /// Waiting for the `Hello` of the other side.
+    Handshake,
+    Playing,
+    Won,
+    Lost,
+    /// The connection was lost, or the other side left (with the reason).
+    Disconnected(String),
+}
+
+/// A versus game against a player on another computer: the local game, plus the last known board of the opponent.
+pub struct NetVersus {
+    connection: Connection,
+    /// The game settings, chosen by the host (`None` for the client, until the handshake).
+    hello: Option<Message>,
+    status: NetStatus,
+    /// The local game, recorded (with the garbage received).
+    game: Option<GameRecorder>,
+    handling: Handling,
+    show_ghost: bool,
+    allow_180: bool,
+    effects: EffectSettings,
+    rng: fastrand::Rng,
+    state_timer: f64,
+    opponent: Board,
+    opponent_lines: u32,
+    opponent_pending: u32,
+    view: BoardView,
+    opponent_view: BoardView,
+}
+
+impl NetVersus {
+    /// Start as the host, who chooses the pieces and the level.
+    pub fn host(
+        transport: Box<dyn Transport>,
+        seed: u64,
+        start_level: u32,
+        randomizer: Randomizer,
+    ) -> Self {
+        let hello = Message::Hello {
+            seed,
+            start_level,
+            randomizer,
+        };
+        let mut versus = Self::new(transport, Some(hello.clone()));
+        versus.send(&hello);
+        versus
+    }
+
+    /// Start as the client, who waits for the settings of the host.
+    pub fn join(transport: Box<dyn Transport>) -> Self {
+        Self::new(transport, None)
+    }
+
+    fn new(transport: Box<dyn Transport>, hello: Option<Message>) -> Self {
+        let view = BoardView {
+            x: 0,
+            y: 0,
+            cell_size: 8,
+        };
+        NetVersus {
+            connection: Connection::new(transport),
+            hello,
+            status: NetStatus::Handshake,
+            game: None,
+            handling: Handling::default(),
+            show_ghost: true,
+            allow_180: false,
+            effects: EffectSettings::default(),
+            rng: fastrand::Rng::new(),
+            state_timer: 0.0,
+            opponent: Board::new(BOARD_WIDTH, BOARD_HEIGHT, 0),
+            opponent_lines: 0,
+            opponent_pending: 0,
+            view,
+            opponent_view: view,
+        }
+    }
+
+    #[inline]
+    pub fn status(&self) -> &NetStatus {
+        &self.status
+    }
+
+    /// The local game (once the handshake is done).
+    #[inline]
+    pub fn game(&self) -> Option<&Game> {
+        self.game.as_ref().map(|r| r.game())
+    }
+
+    /// The local game, for the settings which don't change how it's played (e.g. the view).
+    #[inline]
+    pub fn game_mut(&mut self) -> Option<&mut Game> {
+        self.game.as_mut().map(|r| r.game_mut())
+    }
+
+    /// The replay of the local game, so far (once the handshake is done).
+    #[inline]
+    pub fn replay(&self) -> Option<&GameReplay> {
+        self.game.as_ref().map(|r| r.replay())
+    }
+
+    /// The last known board of the opponent (only the visible rows).
+    #[inline]
+    pub fn opponent_board(&self) -> &Board {
+        &self.opponent
+    }
+
+    /// The player's options, for the local game.
+    pub fn set_game_options(&mut self, handling: &Handling, show_ghost: bool, allow_180: bool) {
+        self.handling = handling.clone();
+        self.show_ghost = show_ghost;
+        self.allow_180 = allow_180;
+        if let Some(recorder) = self.game.as_mut() {
+            recorder.set_handling(handling, allow_180);
+            recorder.game_mut().set_show_ghost(show_ghost);
+        }
+    }
+
+    /// The visual effects of the local game.
+    pub fn set_effects(&mut self, effects: EffectSettings) {
+        self.effects = effects;
+        if let Some(game) = self.game_mut() {
+            game.set_effects(effects);
+        }
+    }
+
+    /// Where to paint the local board, and the opponent's.
+    pub fn set_views(&mut self, view: BoardView, opponent_view: BoardView) {
+        self.view = view;
+        self.opponent_view = opponent_view;
+        if let Some(game) = self.game_mut() {
+            game.set_view(view);
+        }
+    }
+
+    /// Receive the messages, advance the local game, and send the attacks and the board snapshots.
+    pub fn update(&mut self, elapsed_time: f64) {
+        if !matches!(self.status, NetStatus::Handshake | NetStatus::Playing) {
+            return;
+        }
+        match self.connection.receive(elapsed_time) {
+            Ok(messages) => messages.into_iter().for_each(|m| self.handle_message(m)),
+            Err(msg) => self.status = NetStatus::Disconnected(msg),
+        }
+        if self.status != NetStatus::Playing {
+            return;
+        }
+        let Some(recorder) = self.game.as_mut() else {
+            return;
+        };
+        recorder.update(elapsed_time);
+        let game = recorder.game_mut();
+        let attack = game.take_attack();
+        if attack > 0 {
+            let hole_x = self.rng.i32(0..game.board().width());
+            self.send(&Message::Garbage {
+                lines: attack,
+                hole_x,
+            });
+        }
+        self.state_timer += elapsed_time;
+        if self.state_timer >= STATE_INTERVAL || self.game_over() {
+            self.state_timer = 0.0;
+            self.send_state();
+        }
+        if self.game_over() {
+            self.send(&Message::GameOver);
+            if self.status == NetStatus::Playing {
+                self.status = NetStatus::Lost;
+            }
+        }
+    }
+
+    //-------------------------------------------------------
+    //  Internal stuff
+
+    fn game_over(&self) -> bool {
+        self.game().is_some_and(|g| g.is_game_over())
+    }
+
+    /// Send a message; a failure ends the game.
+    fn send(&mut self, message: &Message) {
+        if let Err(msg) = self.connection.send(message) {
+            self.status = NetStatus::Disconnected(msg);
+        }
+    }
+
+    fn send_state(&mut self) {
+        let Some(game) = self.game() else {
+            return;
+        };
+        let board = game.board();
+        let mut full = board.clone();
+        if let Some(piece) = game.piece() {
+            if full.fits(piece) {
+                full.lock(piece);
+            }
+        }
+        let mut visible = Board::new(board.width(), board.visible_height(), 0);
+        for y in 0..visible.height() {
+            for x in 0..visible.width() {
+                visible.set_cell(x, y, full.cell(x, y + board.hidden_rows()));
+            }
+        }
+        let state = Message::State {
+            lines: game.lines(),
+            pending_garbage: game.pending_garbage(),
+            board: visible,
+        };
+        self.send(&state);
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::Hello {
+                seed,
+                start_level,
+                randomizer,
+            } if self.status == NetStatus::Handshake => {
+                if self.hello.is_none() {
+                    // the client repeats the settings of the host
+                    let hello = Message::Hello {
+                        seed,
+                        start_level,
+                        randomizer,
+                    };
+                    self.send(&hello);
+                    self.hello = Some(hello);
+                }
+                self.start();
+            }
+            Message::Garbage { lines, hole_x } => {
+                if let Some(recorder) = self.game.as_mut() {
+                    recorder.receive_garbage(lines.min(MAX_GARBAGE_LINES), hole_x);
+                }
+            }
+            Message::State {
+                lines,
+                pending_garbage,
+                board,
+            } => {
+                self.opponent = board;
+                self.opponent_lines = lines;
+                self.opponent_pending = pending_garbage;
+            }
+            Message::GameOver if self.status == NetStatus::Playing => self.status = NetStatus::Won,
+            Message::Bye => self.status = NetStatus::Disconnected("the opponent left".to_string()),
+            _ => {}
+        }
+    }
+
+    /// The handshake is done: start the local game, with the settings of the host.
+    fn start(&mut self) {
+        let Some(Message::Hello {
+            seed,
+            start_level,
+            randomizer,
+        }) = self.hello
+        else {
+            return;
+        };
+        let mut replay = GameReplay::new(GameMode::Versus, start_level, randomizer, seed);
+        replay.handling = self.handling.clone();
+        replay.allow_180 = self.allow_180;
+        let mut recorder = GameRecorder::new(replay, PieceSet::standard());
+        let game = recorder.game_mut();
+        game.set_show_ghost(self.show_ghost);
+        game.set_view(self.view);
+        game.set_effects(self.effects);
+        self.game = Some(recorder);
+        self.status = NetStatus::Playing;
+        self.send_state();
+    }
+}
+
+impl Drop for NetVersus {
+    fn drop(&mut self) {
+        if matches!(self.status, NetStatus::Handshake | NetStatus::Playing) {
+            self.connection.send(&Message::Bye).ok();
+        }
+    }
+}
+
+impl GraphicsLoop for NetVersus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        match self.game.as_mut() {
+            Some(recorder) if self.status == NetStatus::Playing => recorder.handle_event(event),
+            _ => true,
+        }
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        if let Some(game) = self.game() {
+            game.paint(painter);
+        }
+        let view = &self.opponent_view;
+        view.paint_board(painter, &self.opponent);
+        let (w, h) = view.size(&self.opponent);
+        let garbage = (self.opponent_pending as i32 * view.cell_size).min(h);
+        if garbage > 0 {
+            painter.fill_rect(view.x - 6, view.y + h - garbage, 3, garbage, LIGHT_RED);
+        }
+        let lines = format!("LINES\n{}", self.opponent_lines);
+        painter.draw_text(view.x + w + 8, view.y, &lines, 2, LIGHT_GREY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello() -> Message {
+        Message::Hello {
+            seed: 0x0123_4567_89ab_cdef,
+            start_level: 3,
+            randomizer: Randomizer::Nes,
+        }
+    }
+
+    #[test]
+    fn messages_roundtrip_even_in_pieces() {
+        let mut board = Board::new(3, 2, 0);
+        board.set_cell(0, 1, Some(GARBAGE_CELL));
+        board.set_cell(2, 0, Some(4));
+        let messages = [
+            hello(),
+            Message::Garbage {
+                lines: 4,
+                hole_x: 7,
+            },
+            Message::State {
+                lines: 300,
+                pending_garbage: 2,
+                board,
+            },
+            Message::GameOver,
+            Message::Bye,
+        ];
+        let data: Vec<u8> = messages.iter().flat_map(|m| m.encode()).collect();
+        // the data arrives one byte at a time
+        let (mut sender, receiver) = loopback_pair();
+        let mut connection = Connection::new(Box::new(receiver));
+        let mut received = vec![];
+        for byte in data {
+            sender.send(&[byte]).unwrap();
+            received.extend(connection.receive(0.01).unwrap());
+        }
+        assert_eq!(messages.to_vec(), received);
+    }
+
+    #[test]
+    fn invalid_messages_are_rejected() {
+        let mut data = hello().encode();
+        data[8] = 2; // the version
+        assert!(Message::decode(&data).unwrap_err().contains("version 2 "));
+        data[3] = b'X'; // the magic
+        assert!(Message::decode(&data).is_err());
+        assert!(Message::decode(&[0, 1, 42]).is_err());
+        assert!(Message::decode(&[0, 0]).is_err());
+        assert_eq!(None, Message::decode(&[0, 3, MSG_GARBAGE]).unwrap());
+        // a garbage hole outside of the board
+        let mut data = Message::Garbage {
+            lines: 2,
+            hole_x: 3,
+        }
+        .encode();
+        assert!(Message::decode(&data).unwrap().is_some());
+        let len = data.len();
+        data[len - 1] = BOARD_WIDTH as u8;
+        assert!(Message::decode(&data).is_err());
+    }
+
+    #[test]
+    fn two_players_over_loopback() {
+        let (a, b) = loopback_pair();
+        let mut host = NetVersus::host(Box::new(a), 42, 1, Randomizer::Bag);
+        let mut client = NetVersus::join(Box::new(b));
+        host.update(0.01);
+        client.update(0.01);
+        host.update(0.01);
+        assert_eq!(&NetStatus::Playing, host.status());
+        assert_eq!(&NetStatus::Playing, client.status());
+        // both get the same pieces
+        let pieces = |v: &NetVersus| v.game().unwrap().next_pieces().collect::<Vec<_>>();
+        assert_eq!(pieces(&host), pieces(&client));
+        // the host's board is seen by the client
+        host.game_mut().unwrap().hard_drop();
+        host.update(STATE_INTERVAL);
+        client.update(0.01);
+        let opponent = client.opponent_board();
+        let cells = (0..opponent.height())
+            .flat_map(|y| (0..opponent.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| opponent.cell(x, y).is_some())
+            .count();
+        assert!(cells >= 4);
+
+        // the host tops out
+        while host.status() == &NetStatus::Playing {
+            host.game_mut().unwrap().hard_drop();
+            host.update(0.01);
+        }
+        assert_eq!(&NetStatus::Lost, host.status());
+        client.update(0.01);
+        assert_eq!(&NetStatus::Won, client.status());
+    }
+
+    #[test]
+    fn garbage_timeouts_and_disconnections() {
+        let (a, b) = loopback_pair();
+        let mut host = NetVersus::host(Box::new(a), 1, 1, Randomizer::Bag);
+        let mut client = Connection::new(Box::new(b));
+        client.send(&hello()).unwrap();
+        host.update(0.01);
+        assert_eq!(&NetStatus::Playing, host.status());
+        // late garbage is still received
+        host.update(1.0);
+        client
+            .send(&Message::Garbage {
+                lines: 3,
+                hole_x: 2,
+            })
+            .unwrap();
+        host.update(0.01);
+        assert_eq!(3, host.game().unwrap().pending_garbage());
+        // silence
+        for _ in 0..10 {
+            host.update(1.0);
+        }
+        assert!(matches!(host.status(), NetStatus::Disconnected(_)));
+
+        // the client leaves
+        let (a, b) = loopback_pair();
+        let mut host = NetVersus::host(Box::new(a), 1, 1, Randomizer::Bag);
+        drop(b);
+        host.update(0.01);
+        assert_eq!(
+            &NetStatus::Disconnected("connection closed".to_string()),
+            host.status()
+        );
+    }
+
+    #[test]
+    fn tcp_on_localhost() {
+        let host = TcpHost::bind("127.0.0.1:0").unwrap();
+        let address = format!("127.0.0.1:{}", host.port());
+        let mut client = Connection::new(Box::new(TcpTransport::connect(&address).unwrap()));
+        let mut server = None;
+        for _ in 0..200 {
+            server = host.accept().unwrap();
+            if server.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let mut server = Connection::new(Box::new(server.expect("no connection")));
+        client.send(&hello()).unwrap();
+        let mut received = vec![];
+        for _ in 0..200 {
+            received.extend(server.receive(0.0).unwrap());
+            if !received.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(vec![hello()], received);
+    }
+}
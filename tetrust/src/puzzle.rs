@@ -0,0 +1,590 @@
+//! Puzzles: a board with some garbage, a fixed sequence of pieces and a goal to reach with them.
+//! They are loaded from text files, and every puzzle can be checked for a solution.
+
+use crate::ai::*;
+use crate::board::*;
+use crate::game::*;
+use crate::generator::*;
+use crate::handling::*;
+use crate::mode::*;
+use crate::replay::*;
+use crate::scoring::*;
+use crate::tetrimino::*;
+use game_framework::*;
+use std::collections::HashSet;
+
+/// The version of the puzzle files.
+const PUZZLES_VERSION: u32 = 1;
+/// The most pieces a puzzle can have (the solver tries all their placements).
+pub const MAX_PUZZLE_PIECES: usize = 8;
+/// Rows which must stay free at the top of the board, for the pieces to spawn.
+const PUZZLE_FREE_ROWS: i32 = 4;
+
+/// The puzzles which are part of the game.
+pub const BUILTIN_PUZZLES: &str = include_str!("../puzzles/standard.txt");
+
+/// What a puzzle asks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PuzzleGoal {
+    /// Clear at least this many lines, in total.
+    Lines(u32),
+    TSpinDouble,
+    PerfectClear,
+}
+
+impl PuzzleGoal {
+    pub fn description(&self) -> String {
+        match self {
+            PuzzleGoal::Lines(1) => "CLEAR 1 LINE".to_string(),
+            PuzzleGoal::Lines(lines) => format!("CLEAR {lines} LINES"),
+            PuzzleGoal::TSpinDouble => "T-SPIN DOUBLE".to_string(),
+            PuzzleGoal::PerfectClear => "PERFECT CLEAR".to_string(),
+        }
+    }
+
+    /// Check if the goal was reached, after the piece which made the given clear
+    /// (`lines` is the total so far).
+    pub fn is_reached(&self, lines: u32, clear: &LineClear) -> bool {
+        match self {
+            PuzzleGoal::Lines(goal) => lines >= *goal,
+            PuzzleGoal::TSpinDouble => clear.tspin == TSpin::Full && clear.lines == 2,
+            PuzzleGoal::PerfectClear => clear.perfect_clear,
+        }
+    }
+}
+
+/// A puzzle. The pieces are from the standard set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: PuzzleGoal,
+    /// The pieces, in the order they come (as indices for [`PieceSet::piece`]).
+    pub pieces: Vec<usize>,
+    /// The bottom rows of the board, from top to bottom (true = garbage).
+    pub rows: Vec<Vec<bool>>,
+}
+
+impl Puzzle {
+    /// Check that the puzzle can be played: it doesn't tell if it can be solved, see [`Puzzle::solve`].
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("a puzzle needs a name".to_string());
+        }
+        if self.pieces.is_empty() || self.pieces.len() > MAX_PUZZLE_PIECES {
+            return Err(format!("a puzzle needs 1 to {MAX_PUZZLE_PIECES} pieces"));
+        }
+        if self.rows.len() as i32 > BOARD_HEIGHT - PUZZLE_FREE_ROWS {
+            return Err("too many rows".to_string());
+        }
+        if self.rows.iter().any(|row| row.len() as i32 != BOARD_WIDTH) {
+            return Err(format!("the rows must be {BOARD_WIDTH} cells wide"));
+        }
+        if self.rows.iter().any(|row| row.iter().all(|&cell| cell)) {
+            return Err("the rows can't be full".to_string());
+        }
+        if self.goal == PuzzleGoal::Lines(0) {
+            return Err("the goal needs at least one line".to_string());
+        }
+        Ok(())
+    }
+
+    /// The board the puzzle starts with.
+    pub fn board(&self) -> Board {
+        let mut board = Board::default();
+        let top = board.height() - self.rows.len() as i32;
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                board.set_cell(x as i32, top + y as i32, cell.then_some(GARBAGE_CELL));
+            }
+        }
+        board
+    }
+
+    /// The puzzle, as in the puzzle files.
+    pub fn to_text(&self) -> String {
+        let standard = PieceSet::standard();
+        let goal = match self.goal {
+            PuzzleGoal::Lines(lines) => format!("lines {lines}"),
+            PuzzleGoal::TSpinDouble => "tspin_double".to_string(),
+            PuzzleGoal::PerfectClear => "perfect_clear".to_string(),
+        };
+        let pieces: Vec<String> = self
+            .pieces
+            .iter()
+            .map(|&idx| standard.piece(idx).name().to_string())
+            .collect();
+        let mut text = format!(
+            "puzzle {}\ngoal {goal}\npieces {}\n",
+            self.name,
+            pieces.join(" ")
+        );
+        for row in self.rows.iter() {
+            let row: String = row
+                .iter()
+                .map(|&cell| if cell { '#' } else { '.' })
+                .collect();
+            text.push_str(&row);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parse a single puzzle, as written by [`Puzzle::to_text`].
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut set = PuzzleSet::from_text(&format!("puzzles {PUZZLES_VERSION}\n{text}"))?;
+        match set.puzzles.len() {
+            1 => Ok(set.puzzles.remove(0)),
+            _ => Err("not a single puzzle".to_string()),
+        }
+    }
+
+    /// A new game of the puzzle.
+    pub fn game(&self) -> Game {
+        let generator = SequenceGenerator::new(&self.pieces);
+        let mut game = Game::new(GameMode::Puzzle, 1, Box::new(generator));
+        game.set_piece_limit(Some(self.pieces.len() as u32));
+        game.set_board(self.board());
+        game
+    }
+
+    /// Find a way to reach the goal: the inputs for each piece, which can be sent to the game
+    /// with [`Input::apply`] (a piece taken from the hold slot starts with [`Input::Hold`]).
+    /// Returns `None` if the puzzle can't be solved.
+    pub fn solve(&self) -> Option<Vec<Vec<Input>>> {
+        let search = SolverState {
+            board: self.board(),
+            current: self.pieces[0],
+            next: 1,
+            hold: None,
+            lines: 0,
+        };
+        let mut visited = HashSet::new();
+        let mut solution = self.search(search, &mut visited)?;
+        solution.reverse();
+        Some(solution)
+    }
+
+    /// False if the pieces left can't fill the rows which must be cleared for the goal.
+    fn may_be_reached(&self, state: &SolverState) -> bool {
+        let pieces = PieceSet::standard();
+        let cells_left: usize = std::iter::once(state.current)
+            .chain(state.hold)
+            .chain(self.pieces[state.next..].iter().copied())
+            .map(|piece| pieces.shape(piece).len())
+            .sum();
+        let board = &state.board;
+        let mut empty_cells: Vec<usize> = (0..board.height())
+            .filter(|&y| !board.is_row_empty(y))
+            .map(|y| (0..board.width()).filter(|&x| board.is_free(x, y)).count())
+            .collect();
+        // the rows which are the closest to be full
+        empty_cells.sort();
+        let needed: usize = match self.goal {
+            PuzzleGoal::Lines(lines) => {
+                let rows = lines.saturating_sub(state.lines) as usize;
+                empty_cells.iter().take(rows).sum()
+            }
+            PuzzleGoal::TSpinDouble => empty_cells.iter().take(2).sum(),
+            PuzzleGoal::PerfectClear => empty_cells.iter().sum(),
+        };
+        needed <= cells_left
+    }
+
+    /// Depth-first search of the placements; the solution comes back with the last piece first.
+    fn search(
+        &self,
+        state: SolverState,
+        visited: &mut HashSet<SolverKey>,
+    ) -> Option<Vec<Vec<Input>>> {
+        let cells = (0..state.board.height())
+            .flat_map(|y| (0..state.board.width()).map(move |x| (x, y)))
+            .map(|(x, y)| state.board.cell(x, y).is_some())
+            .collect();
+        if !visited.insert((cells, state.current, state.next, state.hold, state.lines))
+            || !self.may_be_reached(&state)
+        {
+            return None;
+        }
+
+        // play the current piece, or swap it with the hold slot (or the next piece)
+        let mut choices = vec![(false, state.current, state.hold, state.next)];
+        match state.hold {
+            Some(held) => choices.push((true, held, Some(state.current), state.next)),
+            None if state.next < self.pieces.len() => choices.push((
+                true,
+                self.pieces[state.next],
+                Some(state.current),
+                state.next + 1,
+            )),
+            None => {}
+        }
+        for (use_hold, piece, hold, next) in choices {
+            let start = spawn_position(&state.board, PieceSet::standard().piece(piece));
+            for (tetr, inputs) in reachable_placements(&state.board, &start, false) {
+                let tspin = {
+                    let (last_move_rotation, tst_kick) =
+                        last_rotation(&state.board, &start, &inputs);
+                    detect_tspin(&state.board, &tetr, last_move_rotation, tst_kick)
+                };
+                let mut board = state.board.clone();
+                board.lock(&tetr);
+                if tetr.cells().all(|(_, y)| y < board.hidden_rows()) {
+                    continue;
+                }
+                let cleared = board.clear_full_rows().len() as u32;
+                let clear = LineClear {
+                    lines: cleared,
+                    tspin,
+                    perfect_clear: cleared > 0 && board.is_empty(),
+                };
+                let mut inputs = inputs;
+                if use_hold {
+                    inputs.insert(0, Input::Hold);
+                }
+                if self.goal.is_reached(state.lines + cleared, &clear) {
+                    return Some(vec![inputs]);
+                }
+                // the next piece (like in the game, the held one comes last)
+                let (current, next, hold) = match (self.pieces.get(next), hold) {
+                    (Some(&piece), _) => (piece, next + 1, hold),
+                    (None, Some(held)) => (held, next, None),
+                    (None, None) => continue,
+                };
+                let state = SolverState {
+                    board,
+                    current,
+                    next,
+                    hold,
+                    lines: state.lines + cleared,
+                };
+                if let Some(mut solution) = self.search(state, visited) {
+                    solution.push(inputs);
+                    return Some(solution);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The states already searched: the filled cells, then the fields of [`SolverState`].
+type SolverKey = (Vec<bool>, usize, usize, Option<usize>, u32);
+
+/// Where the solver is: the board, the piece to play, the index of the next piece, the hold slot
+/// and the lines cleared so far.
+struct SolverState {
+    board: Board,
+    current: usize,
+    next: usize,
+    hold: Option<usize>,
+    lines: u32,
+}
+
+/// Play the inputs (without the final hard drop) from the start position, and tell if the last one
+/// was a rotation, and if it used the TST kick: that's what makes a T-spin.
+fn last_rotation(board: &Board, start: &Tetrimino, inputs: &[Input]) -> (bool, bool) {
+    let mut tetr = start.clone();
+    let mut last = (false, false);
+    for input in inputs {
+        let rotation = match input {
+            Input::Left | Input::Right | Input::Down => {
+                match input {
+                    Input::Left => tetr.slide(-1, 0),
+                    Input::Right => tetr.slide(1, 0),
+                    _ => tetr.slide(0, 1),
+                }
+                last = (false, false);
+                continue;
+            }
+            Input::RotateCw => Rotation::Clockwise,
+            Input::RotateCcw => Rotation::CounterClockwise,
+            Input::Rotate180 => Rotation::Half,
+            Input::Hold | Input::HardDrop => continue,
+        };
+        if let Some((rotated, kick)) = tetr.rotated_srs(rotation, |t| board.fits(t)) {
+            tetr = rotated;
+            last = (true, is_tst_kick(rotation, kick));
+        }
+    }
+    // the hard drop moves the piece down, unless it's already on the floor
+    if board.drop_distance(&tetr) > 0 {
+        last = (false, false);
+    }
+    last
+}
+
+/// A set of puzzles, from a text file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PuzzleSet {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleSet {
+    /// The puzzles which are part of the game.
+    pub fn builtin() -> PuzzleSet {
+        PuzzleSet::from_text(BUILTIN_PUZZLES).unwrap()
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// Parse a puzzle file. Each puzzle is validated, but not solved.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let is_row = |line: &str| line.chars().all(|c| c == '#' || c == '.');
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && (!line.starts_with('#') || is_row(line)));
+        match lines.next() {
+            Some((_, line)) if line == format!("puzzles {PUZZLES_VERSION}") => {}
+            _ => return Err("not a puzzle file (or unsupported version)".to_string()),
+        }
+
+        let standard = PieceSet::standard();
+        let mut set = PuzzleSet {
+            name: String::new(),
+            puzzles: vec![],
+        };
+        // the puzzle being parsed, and the line where it starts
+        let mut puzzle: Option<(Puzzle, usize)> = None;
+        let finish = |puzzle: Option<(Puzzle, usize)>, set: &mut PuzzleSet| {
+            if let Some((puzzle, idx)) = puzzle {
+                puzzle
+                    .validate()
+                    .map_err(|e| format!("puzzle line {}: {e}", idx + 1))?;
+                set.puzzles.push(puzzle);
+            }
+            Ok::<(), String>(())
+        };
+        for (idx, line) in lines {
+            let err = |msg: &str| format!("puzzles line {}: {msg}", idx + 1);
+            let (keyword, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            if keyword == "name" {
+                set.name = value.to_string();
+                continue;
+            }
+            if keyword == "puzzle" {
+                finish(puzzle.take(), &mut set)?;
+                puzzle = Some((
+                    Puzzle {
+                        name: value.to_string(),
+                        goal: PuzzleGoal::Lines(1),
+                        pieces: vec![],
+                        rows: vec![],
+                    },
+                    idx,
+                ));
+                continue;
+            }
+            let Some((puzzle, _)) = puzzle.as_mut() else {
+                return Err(err("outside of a puzzle"));
+            };
+            if is_row(line) {
+                puzzle.rows.push(line.chars().map(|c| c == '#').collect());
+                continue;
+            }
+            match keyword {
+                "goal" => {
+                    puzzle.goal = match value.split_once(' ').unwrap_or((value, "")) {
+                        ("lines", lines) => match lines.trim().parse() {
+                            Ok(lines) => PuzzleGoal::Lines(lines),
+                            Err(_) => return Err(err("invalid number of lines")),
+                        },
+                        ("tspin_double", "") => PuzzleGoal::TSpinDouble,
+                        ("perfect_clear", "") => PuzzleGoal::PerfectClear,
+                        _ => return Err(err("unknown goal")),
+                    }
+                }
+                "pieces" => {
+                    for name in value.split_whitespace() {
+                        let piece = (0..standard.len()).find(|&idx| {
+                            name.len() == 1 && name.starts_with(standard.piece(idx).name())
+                        });
+                        match piece {
+                            Some(piece) => puzzle.pieces.push(piece),
+                            None => return Err(err(&format!("unknown piece: {name}"))),
+                        }
+                    }
+                }
+                _ => return Err(err("unknown keyword")),
+            }
+        }
+        finish(puzzle, &mut set)?;
+        if set.puzzles.is_empty() {
+            return Err("no puzzles".to_string());
+        }
+        Ok(set)
+    }
+}
+
+/// How a puzzle is going.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PuzzleStatus {
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// A puzzle being played: a game which ends when the goal is reached or when the pieces run out.
+/// The game is recorded.
+pub struct PuzzleGame {
+    puzzle: Puzzle,
+    recorder: GameRecorder,
+    status: PuzzleStatus,
+    pieces_placed: u32,
+}
+
+impl PuzzleGame {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        PuzzleGame {
+            puzzle: puzzle.clone(),
+            recorder: GameRecorder::new(GameReplay::for_puzzle(puzzle), PieceSet::standard()),
+            status: PuzzleStatus::Playing,
+            pieces_placed: 0,
+        }
+    }
+
+    #[inline]
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    #[inline]
+    pub fn game(&self) -> &Game {
+        self.recorder.game()
+    }
+
+    /// The game, e.g. to change its view or to play inputs (call [`PuzzleGame::check`] after):
+    /// whatever is done to it isn't recorded.
+    #[inline]
+    pub fn game_mut(&mut self) -> &mut Game {
+        self.recorder.game_mut()
+    }
+
+    /// The replay of the game, so far.
+    #[inline]
+    pub fn replay(&self) -> &GameReplay {
+        self.recorder.replay()
+    }
+
+    /// Change the settings which affect the game.
+    pub fn set_handling(&mut self, handling: &Handling, allow_180: bool) {
+        self.recorder.set_handling(handling, allow_180);
+    }
+
+    /// Release the moves and the soft drop, e.g. after a pause.
+    pub fn release_keys(&mut self) {
+        self.recorder.release_keys();
+    }
+
+    #[inline]
+    pub fn status(&self) -> PuzzleStatus {
+        self.status
+    }
+
+    pub fn update(&mut self, elapsed_time: f64) {
+        if self.status == PuzzleStatus::Playing {
+            self.recorder.update(elapsed_time);
+            self.check();
+        }
+    }
+
+    /// Check the goal, after a piece was locked.
+    pub fn check(&mut self) {
+        if self.status != PuzzleStatus::Playing {
+            return;
+        }
+        let game = self.recorder.game();
+        if game.pieces_placed() != self.pieces_placed {
+            self.pieces_placed = game.pieces_placed();
+            if let Some(clear) = game.last_clear() {
+                if self.puzzle.goal.is_reached(game.lines(), clear) {
+                    self.status = PuzzleStatus::Solved;
+                    return;
+                }
+            }
+        }
+        if game.is_game_over() {
+            self.status = PuzzleStatus::Failed;
+        }
+    }
+}
+
+impl GraphicsLoop for PuzzleGame {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        if self.status == PuzzleStatus::Playing {
+            self.recorder.handle_event(event);
+        }
+        true
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        let game = self.recorder.game();
+        game.paint(painter);
+        let view = game.view();
+        let text = format!("{}: {}", self.puzzle.name, self.puzzle.goal.description());
+        painter.draw_text(view.x - 2, view.y - 12, &text, 2, LIGHT_YELLOW);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_builtin_puzzles_can_be_solved() {
+        let set = PuzzleSet::builtin();
+        assert_eq!("STANDARD", set.name);
+        for puzzle in set.puzzles.iter() {
+            let solution = puzzle.solve();
+            assert!(solution.is_some(), "no solution for {}", puzzle.name);
+            // playing the solution solves the puzzle
+            let mut game = PuzzleGame::new(puzzle);
+            for inputs in solution.unwrap() {
+                for input in inputs {
+                    assert!(input.apply(game.game_mut()), "{}: {input:?}", puzzle.name);
+                }
+                game.check();
+            }
+            assert_eq!(PuzzleStatus::Solved, game.status(), "{}", puzzle.name);
+        }
+    }
+
+    #[test]
+    fn invalid_puzzles_are_rejected() {
+        let parse = |body: &str| PuzzleSet::from_text(&format!("puzzles 1\npuzzle P\n{body}"));
+        assert!(parse("goal lines 1\npieces I\n.#########").is_ok());
+        assert!(parse("goal lines 1\npieces I\n##########").is_err());
+        assert!(parse("goal lines 1\npieces I\n.####").is_err());
+        assert!(parse("goal lines 1\npieces Q\n.#########").is_err());
+        assert!(parse("goal lines 1\n.#########").is_err());
+        assert!(parse("goal sideways\npieces I").is_err());
+        assert!(PuzzleSet::from_text("pieces 1\npuzzle P\npieces I").is_err());
+    }
+
+    #[test]
+    fn impossible_puzzles_have_no_solution() {
+        let set = PuzzleSet::from_text(
+            "puzzles 1\npuzzle P\ngoal tspin_double\npieces O O\n.#########\n.#########",
+        )
+        .unwrap();
+        assert_eq!(None, set.puzzles[0].solve());
+        // running out of pieces ends the game
+        let mut game = PuzzleGame::new(&set.puzzles[0]);
+        game.game_mut().hard_drop();
+        game.game_mut().hard_drop();
+        game.check();
+        assert_eq!(PuzzleStatus::Failed, game.status());
+        assert_eq!(Some(Outcome::OutOfPieces), game.game().outcome());
+    }
+}
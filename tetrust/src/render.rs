@@ -0,0 +1,187 @@
+//! Painting the playfield and the pieces.
+
+use crate::board::*;
+use crate::tetrimino::*;
+use game_framework::*;
+
+/// Colors for each `color_idx`, in the order of the tetriminoes (O, I, T, S, Z, J, L).
+pub const PIECE_COLORS: &[RGB] = &[
+    LIGHT_YELLOW,
+    LIGHT_CYAN,
+    LIGHT_MAGENTA,
+    LIGHT_GREEN,
+    LIGHT_RED,
+    LIGHT_BLUE,
+    ORANGE,
+];
+
+const BOARD_BACKGROUND: RGB = DARK_BLUE;
+const BOARD_FRAME: RGB = GREY;
+
+/// The color of a piece (the color indices larger than the palette wrap around), or of the garbage.
+#[inline]
+pub fn piece_color(color_idx: usize) -> RGB {
+    if color_idx == GARBAGE_CELL as usize {
+        GREY
+    } else {
+        PIECE_COLORS[color_idx % PIECE_COLORS.len()]
+    }
+}
+
+/// A color scaled towards black (`percent` = 100 keeps it unchanged).
+#[inline]
+pub fn shade(color: RGB, percent: u32) -> RGB {
+    let f = |c: u8| ((c as u32) * percent / 100).min(255) as u8;
+    RGB::from(f(color.r), f(color.g), f(color.b))
+}
+
+/// Paints everything darker, e.g. a ghost board which looks translucent over a black background.
+pub struct FadedPainter<'a> {
+    inner: &'a mut dyn Painter,
+    percent: u32,
+}
+
+impl<'a> FadedPainter<'a> {
+    /// `percent` as in `shade()`.
+    pub fn new(inner: &'a mut dyn Painter, percent: u32) -> Self {
+        FadedPainter { inner, percent }
+    }
+}
+
+impl Painter for FadedPainter<'_> {
+    fn draw_pixel(&mut self, x: i32, y: i32, color: RGB) {
+        self.inner.draw_pixel(x, y, shade(color, self.percent));
+    }
+}
+
+/// Paint a single cell, with a simple bevel.
+pub fn paint_cell(painter: &mut dyn Painter, x: i32, y: i32, size: i32, color: RGB) {
+    painter.fill_rect(x, y, size, size, shade(color, 70));
+    painter.fill_rect(x + 1, y + 1, size - 2, size - 2, color);
+    painter.draw_horiz_line(x + 1, x + size - 2, y + 1, shade(color, 140));
+}
+
+/// Paint a piece in its spawn orientation, with the top left corner of its 4x2 box at (x, y)
+/// (larger pieces go beyond the box). Used for the hold slot and the next queue.
+pub fn paint_preview(
+    painter: &mut dyn Painter,
+    tetr: &Tetrimino,
+    x: i32,
+    y: i32,
+    cell_size: i32,
+    color: RGB,
+) {
+    let min_x = tetr.cells().map(|(x, _)| x).min().unwrap();
+    let max_x = tetr.cells().map(|(x, _)| x).max().unwrap();
+    let min_y = tetr.cells().map(|(_, y)| y).min().unwrap();
+    // center the piece horizontally, in the 4 cells wide box
+    let ofs_x = x + (4 - (max_x - min_x + 1)) * cell_size / 2;
+    for (tx, ty) in tetr.cells() {
+        let cx = ofs_x + (tx - min_x) * cell_size;
+        let cy = y + (ty - min_y) * cell_size;
+        paint_cell(painter, cx, cy, cell_size, color);
+    }
+}
+
+/// Where (and how large) a board is painted on the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardView {
+    pub x: i32,
+    pub y: i32,
+    pub cell_size: i32,
+}
+
+impl BoardView {
+    /// Screen position of a board cell (the hidden rows are above `y`).
+    #[inline]
+    pub fn cell_pos(&self, board: &Board, bx: i32, by: i32) -> (i32, i32) {
+        (
+            self.x + bx * self.cell_size,
+            self.y + (by - board.hidden_rows()) * self.cell_size,
+        )
+    }
+
+    /// The size of the visible area, in pixels.
+    #[inline]
+    pub fn size(&self, board: &Board) -> (i32, i32) {
+        (
+            board.width() * self.cell_size,
+            board.visible_height() * self.cell_size,
+        )
+    }
+
+    /// Paint the frame, the background and the locked cells.
+    pub fn paint_board(&self, painter: &mut dyn Painter, board: &Board) {
+        self.paint_board_shifted(painter, board, |_| 0);
+    }
+
+    /// Paint the board, with each row moved up by `row_offset(row)` pixels (the cells moved
+    /// above the visible area are not painted).
+    pub fn paint_board_shifted(
+        &self,
+        painter: &mut dyn Painter,
+        board: &Board,
+        row_offset: impl Fn(i32) -> i32,
+    ) {
+        let (w, h) = self.size(board);
+        painter.draw_rect(self.x - 2, self.y - 2, w + 4, h + 4, BOARD_FRAME);
+        painter.fill_rect(self.x, self.y, w, h, BOARD_BACKGROUND);
+        for by in 0..board.height() {
+            let offset = row_offset(by);
+            for bx in 0..board.width() {
+                let (x, y) = self.cell_pos(board, bx, by);
+                let y = y - offset;
+                if y < self.y {
+                    continue;
+                }
+                if let Some(color_idx) = board.cell(bx, by) {
+                    paint_cell(
+                        painter,
+                        x,
+                        y,
+                        self.cell_size,
+                        piece_color(color_idx as usize),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Paint the outline of a tetrimino on the board, e.g. for the ghost piece.
+    pub fn paint_ghost(
+        &self,
+        painter: &mut dyn Painter,
+        board: &Board,
+        tetr: &Tetrimino,
+        color: RGB,
+    ) {
+        for (bx, by) in tetr.cells() {
+            if by >= board.hidden_rows() {
+                let (x, y) = self.cell_pos(board, bx, by);
+                painter.draw_rect(
+                    x + 1,
+                    y + 1,
+                    self.cell_size - 2,
+                    self.cell_size - 2,
+                    shade(color, 60),
+                );
+            }
+        }
+    }
+
+    /// Paint a tetrimino on the board (the parts in the hidden rows are not painted).
+    pub fn paint_piece(
+        &self,
+        painter: &mut dyn Painter,
+        board: &Board,
+        tetr: &Tetrimino,
+        color: RGB,
+    ) {
+        for (bx, by) in tetr.cells() {
+            if by >= board.hidden_rows() {
+                let (x, y) = self.cell_pos(board, bx, by);
+                paint_cell(painter, x, y, self.cell_size, color);
+            }
+        }
+    }
+}
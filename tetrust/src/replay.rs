@@ -0,0 +1,677 @@
+//! Replays of single games: the settings and the seed the game started with, and the player's
+//! actions, each with the tick it happened at. The game runs at a fixed tick rate while it's
+//! recorded or played back, so the same actions always give the same game.
+//! In versus games, each player has their own replay, with the garbage they received.
+//! All the games of the graphical frontend are recorded (not the ones of the terminal frontend).
+
+use crate::ai::*;
+use crate::game::*;
+use crate::generator::*;
+use crate::handling::*;
+use crate::keymap::*;
+use crate::mode::*;
+use crate::puzzle::*;
+use crate::tetrimino::*;
+use game_framework::*;
+use std::sync::Arc;
+
+/// The version of the replay files.
+const GAME_REPLAY_VERSION: u32 = 1;
+/// The duration of a tick, in seconds.
+pub const REPLAY_TICK: f64 = 1.0 / 60.0;
+
+/// Something which changed the game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplayEvent {
+    Press(Action),
+    Release(Action),
+    /// The handling was changed (e.g. from the pause menu).
+    Handling(Handling),
+    Allow180(bool),
+    /// Garbage sent by the opponent (versus games).
+    Garbage {
+        lines: u32,
+        hole_x: i32,
+    },
+    /// An input of the AI, when it played instead of the player.
+    Ai(Input),
+}
+
+impl ReplayEvent {
+    fn apply(&self, game: &mut Game) {
+        match self {
+            ReplayEvent::Press(action) => game.press(*action),
+            ReplayEvent::Release(action) => game.release(*action),
+            ReplayEvent::Handling(handling) => game.set_handling(handling),
+            ReplayEvent::Allow180(allow_180) => game.set_allow_180(*allow_180),
+            ReplayEvent::Garbage { lines, hole_x } => game.receive_garbage(*lines, *hole_x),
+            ReplayEvent::Ai(input) => {
+                input.apply(game);
+            }
+        }
+    }
+}
+
+/// An event, and the tick it happened at (before the game was updated for this tick).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayInput {
+    pub tick: u64,
+    pub event: ReplayEvent,
+}
+
+/// A recorded game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameReplay {
+    pub mode: GameMode,
+    pub start_level: u32,
+    pub randomizer: Randomizer,
+    /// The name of the piece set.
+    pub piece_set: String,
+    pub seed: u64,
+    pub handling: Handling,
+    pub allow_180: bool,
+    /// The puzzle played (puzzle games only: the seed and the randomizer are not used).
+    pub puzzle: Option<Puzzle>,
+    pub inputs: Vec<ReplayInput>,
+    /// The length of the game, in ticks (the events at this tick are played, but not the tick:
+    /// they ended the game).
+    pub ticks: u64,
+}
+
+impl GameReplay {
+    /// The beginning of a replay, with the settings of the game.
+    pub fn new(mode: GameMode, start_level: u32, randomizer: Randomizer, seed: u64) -> Self {
+        GameReplay {
+            mode,
+            start_level,
+            randomizer,
+            piece_set: BUILTIN_PIECE_SETS[0].0.to_string(),
+            seed,
+            handling: Handling::default(),
+            allow_180: false,
+            puzzle: None,
+            inputs: vec![],
+            ticks: 0,
+        }
+    }
+
+    /// The beginning of the replay of a puzzle.
+    pub fn for_puzzle(puzzle: &Puzzle) -> Self {
+        GameReplay {
+            puzzle: Some(puzzle.clone()),
+            ..GameReplay::new(GameMode::Puzzle, 1, Randomizer::Bag, 0)
+        }
+    }
+
+    /// The game at its start (`pieces` must be the piece set named in the replay).
+    pub fn new_game(&self, pieces: Arc<PieceSet>) -> Game {
+        let mut game = match &self.puzzle {
+            Some(puzzle) => puzzle.game(),
+            None => {
+                let generator = self.randomizer.create(pieces.len(), self.seed);
+                Game::with_piece_set(self.mode, self.start_level, generator, pieces)
+            }
+        };
+        game.set_handling(&self.handling);
+        game.set_allow_180(self.allow_180);
+        game
+    }
+
+    /// The length of the game, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.ticks as f64 * REPLAY_TICK
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn to_text(&self) -> String {
+        let handling = |h: &Handling| {
+            // (the default float formatting is lossless)
+            format!(
+                "{} {} {} {} {}",
+                h.das, h.arr, h.soft_drop_factor, h.lock_delay, h.move_reset_limit
+            )
+        };
+        let mut text = format!(
+            "tetrust-replay {GAME_REPLAY_VERSION}\nmode {}\nlevel {}\nrandomizer {}\npieces {}\nseed {}\nhandling {}\nallow_180 {}\nticks {}\n",
+            self.mode.name(),
+            self.start_level,
+            self.randomizer.name(),
+            self.piece_set,
+            self.seed,
+            handling(&self.handling),
+            self.allow_180 as u8,
+            self.ticks,
+        );
+        if let Some(puzzle) = &self.puzzle {
+            for line in puzzle.to_text().lines() {
+                text.push_str(&format!("puzzle {line}\n"));
+            }
+        }
+        for input in self.inputs.iter() {
+            let event = match &input.event {
+                ReplayEvent::Press(action) => format!("+{}", action.name()),
+                ReplayEvent::Release(action) => format!("-{}", action.name()),
+                ReplayEvent::Handling(h) => format!("handling {}", handling(h)),
+                ReplayEvent::Allow180(allow_180) => format!("allow_180 {}", *allow_180 as u8),
+                ReplayEvent::Garbage { lines, hole_x } => format!("garbage {lines} {hole_x}"),
+                ReplayEvent::Ai(input) => format!("ai {}", input.name()),
+            };
+            text.push_str(&format!("{} {event}\n", input.tick));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        match lines.next() {
+            Some((_, line)) if line == format!("tetrust-replay {GAME_REPLAY_VERSION}") => {}
+            _ => return Err("not a replay file (or unsupported version)".to_string()),
+        }
+
+        let handling = |value: &str| {
+            let v: Vec<&str> = value.split_whitespace().collect();
+            let [das, arr, sdf, lock_delay, resets] = v.as_slice() else {
+                return None;
+            };
+            Some(Handling {
+                das: das.parse().ok()?,
+                arr: arr.parse().ok()?,
+                soft_drop_factor: sdf.parse().ok()?,
+                lock_delay: lock_delay.parse().ok()?,
+                move_reset_limit: resets.parse().ok()?,
+            })
+        };
+        let flag = |value: &str| match value {
+            "0" => Some(false),
+            "1" => Some(true),
+            _ => None,
+        };
+        let garbage = |value: &str| {
+            let (lines, hole_x) = value.split_once(' ')?;
+            Some(ReplayEvent::Garbage {
+                lines: lines.parse().ok()?,
+                hole_x: hole_x.trim().parse().ok()?,
+            })
+        };
+        let mut replay = GameReplay::new(GameMode::Marathon, 1, Randomizer::Bag, 0);
+        // the lines of the puzzle (puzzle games)
+        let mut puzzle = String::new();
+        for (idx, line) in lines {
+            let err = |msg: &str| format!("replay line {}: {msg}", idx + 1);
+            let (keyword, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            if let Ok(tick) = keyword.parse::<u64>() {
+                let (kind, args) = value.split_once(' ').unwrap_or((value, ""));
+                let event = match kind.split_at(kind.len().min(1)) {
+                    ("+", name) => Action::from_name(name).map(ReplayEvent::Press),
+                    ("-", name) => Action::from_name(name).map(ReplayEvent::Release),
+                    _ if kind == "handling" => handling(args).map(ReplayEvent::Handling),
+                    _ if kind == "allow_180" => flag(args).map(ReplayEvent::Allow180),
+                    _ if kind == "garbage" => garbage(args),
+                    _ if kind == "ai" => Input::from_name(args).map(ReplayEvent::Ai),
+                    _ => None,
+                };
+                let event = event.ok_or_else(|| err("invalid event"))?;
+                if replay.inputs.last().is_some_and(|last| last.tick > tick) {
+                    return Err(err("the events are not in order"));
+                }
+                replay.inputs.push(ReplayInput { tick, event });
+                continue;
+            }
+            match keyword {
+                "mode" => {
+                    // (the versus and puzzle games are recorded too)
+                    replay.mode = [GameMode::Versus, GameMode::Puzzle]
+                        .into_iter()
+                        .find(|mode| mode.name() == value)
+                        .or_else(|| GameMode::from_name(value))
+                        .ok_or_else(|| err("unknown mode"))?
+                }
+                "level" => replay.start_level = value.parse().map_err(|_| err("invalid level"))?,
+                "randomizer" => {
+                    replay.randomizer =
+                        Randomizer::from_name(value).ok_or_else(|| err("unknown randomizer"))?
+                }
+                "pieces" => replay.piece_set = value.to_string(),
+                "seed" => replay.seed = value.parse().map_err(|_| err("invalid seed"))?,
+                "handling" => {
+                    replay.handling = handling(value).ok_or_else(|| err("invalid handling"))?
+                }
+                "allow_180" => replay.allow_180 = flag(value).ok_or_else(|| err("invalid flag"))?,
+                "ticks" => replay.ticks = value.parse().map_err(|_| err("invalid ticks"))?,
+                "puzzle" => {
+                    puzzle.push_str(value);
+                    puzzle.push('\n');
+                }
+                _ => return Err(err("unknown keyword")),
+            }
+        }
+        if replay
+            .inputs
+            .last()
+            .is_some_and(|last| last.tick > replay.ticks)
+        {
+            return Err("events after the end of the game".to_string());
+        }
+        if !puzzle.is_empty() {
+            replay.puzzle = Some(Puzzle::from_text(&puzzle)?);
+        }
+        if (replay.mode == GameMode::Puzzle) != replay.puzzle.is_some() {
+            return Err("only the puzzle games have a puzzle".to_string());
+        }
+        Ok(replay)
+    }
+}
+
+/// Runs a game at the fixed tick rate, and records the player's actions.
+pub struct GameRecorder {
+    game: Game,
+    replay: GameReplay,
+    /// The events for the next tick.
+    pending: Vec<ReplayEvent>,
+    tick_timer: f64,
+}
+
+impl GameRecorder {
+    /// Start a new game, with the settings of the given replay (its inputs are dropped).
+    pub fn new(mut replay: GameReplay, pieces: Arc<PieceSet>) -> Self {
+        replay.inputs.clear();
+        replay.ticks = 0;
+        GameRecorder {
+            game: replay.new_game(pieces),
+            replay,
+            pending: vec![],
+            tick_timer: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The game, for the settings which don't change how it's played (e.g. the view):
+    /// whatever is done to it isn't recorded.
+    #[inline]
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// The replay so far.
+    #[inline]
+    pub fn replay(&self) -> &GameReplay {
+        &self.replay
+    }
+
+    /// The number of ticks played.
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.replay.ticks
+    }
+
+    /// Start or end an action, on the next tick.
+    pub fn action(&mut self, action: Action, pressed: bool) {
+        self.pending.push(match pressed {
+            true => ReplayEvent::Press(action),
+            false => ReplayEvent::Release(action),
+        });
+    }
+
+    /// Release the moves and the soft drop, e.g. after a pause.
+    pub fn release_keys(&mut self) {
+        for action in [Action::Left, Action::Right, Action::SoftDrop] {
+            self.action(action, false);
+        }
+    }
+
+    /// Change the settings which affect the game, on the next tick (only if they changed).
+    pub fn set_handling(&mut self, handling: &Handling, allow_180: bool) {
+        if self.game.handling() != handling {
+            self.pending.push(ReplayEvent::Handling(handling.clone()));
+        }
+        if self.game.allow_180() != allow_180 {
+            self.pending.push(ReplayEvent::Allow180(allow_180));
+        }
+    }
+
+    /// Receive garbage from the opponent, on the next tick.
+    pub fn receive_garbage(&mut self, lines: u32, hole_x: i32) {
+        if lines > 0 {
+            self.pending.push(ReplayEvent::Garbage { lines, hole_x });
+        }
+    }
+
+    /// Let the AI play its inputs due in the elapsed time (call it before [`GameRecorder::update`]).
+    /// They are played right away, and recorded for the next tick, before the player's events:
+    /// this is where a playback replays them too.
+    pub fn play_ai(&mut self, ai: &mut AiPlayer, elapsed_time: f64) {
+        let mut inputs = vec![];
+        ai.update_and_log(&mut self.game, elapsed_time, &mut inputs);
+        let tick = self.replay.ticks;
+        self.replay
+            .inputs
+            .extend(inputs.into_iter().map(|input| ReplayInput {
+                tick,
+                event: ReplayEvent::Ai(input),
+            }));
+    }
+
+    /// Play the ticks which fit in the elapsed time (the rest is kept for the next update).
+    pub fn update(&mut self, elapsed_time: f64) {
+        self.tick_timer += elapsed_time;
+        while self.tick_timer >= REPLAY_TICK && !self.game.is_game_over() {
+            self.tick_timer -= REPLAY_TICK;
+            for event in std::mem::take(&mut self.pending) {
+                event.apply(&mut self.game);
+                self.replay.inputs.push(ReplayInput {
+                    tick: self.replay.ticks,
+                    event,
+                });
+            }
+            self.game.update(REPLAY_TICK);
+            self.replay.ticks += 1;
+        }
+    }
+}
+
+impl GraphicsLoop for GameRecorder {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::KeyDown {
+                keycode: Some(key),
+                repeat: false,
+                ..
+            } => {
+                if let Some(action) = self.game.key_map().action(*key) {
+                    self.action(action, true);
+                }
+            }
+            Event::KeyUp {
+                keycode: Some(key), ..
+            } => {
+                if let Some(action) = self.game.key_map().action(*key) {
+                    self.action(action, false);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.game.paint(painter);
+    }
+}
+
+/// Plays a replay back, at any speed, and can jump to any point of it.
+pub struct ReplayPlayback {
+    replay: GameReplay,
+    pieces: Arc<PieceSet>,
+    game: Game,
+    tick: u64,
+    /// The index of the next input to play.
+    next_input: usize,
+    tick_timer: f64,
+    speed: f64,
+    paused: bool,
+}
+
+impl ReplayPlayback {
+    /// `pieces` must be the piece set named in the replay.
+    pub fn new(replay: GameReplay, pieces: Arc<PieceSet>) -> Self {
+        let game = replay.new_game(pieces.clone());
+        ReplayPlayback {
+            replay,
+            pieces,
+            game,
+            tick: 0,
+            next_input: 0,
+            tick_timer: 0.0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    #[inline]
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    #[inline]
+    pub fn replay(&self) -> &GameReplay {
+        &self.replay
+    }
+
+    #[inline]
+    pub fn pieces(&self) -> &Arc<PieceSet> {
+        &self.pieces
+    }
+
+    /// The game, for the settings which don't change how it's played (the view, the ghost piece
+    /// and the effects): they are kept when going back in the replay.
+    #[inline]
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// The number of ticks played.
+    #[inline]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The position in the replay, in seconds.
+    pub fn time(&self) -> f64 {
+        self.tick as f64 * REPLAY_TICK
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.ticks
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// The playback speed (1 = as it was played).
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.0);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Go to a tick of the replay. Going back plays the replay again from its start.
+    pub fn seek(&mut self, tick: u64) {
+        let tick = tick.min(self.replay.ticks);
+        if tick < self.tick {
+            let mut game = self.replay.new_game(self.pieces.clone());
+            game.set_view(self.game.view());
+            game.set_show_ghost(self.game.show_ghost());
+            game.set_effects(self.game.effects().settings());
+            self.game = game;
+            self.tick = 0;
+            self.next_input = 0;
+        }
+        while self.tick < tick {
+            self.step();
+        }
+        self.finish();
+        self.tick_timer = 0.0;
+    }
+
+    /// Play the ticks which fit in the elapsed time, at the playback speed.
+    pub fn update(&mut self, elapsed_time: f64) {
+        if self.paused {
+            return;
+        }
+        self.tick_timer += elapsed_time * self.speed;
+        while self.tick_timer >= REPLAY_TICK && !self.is_finished() {
+            self.tick_timer -= REPLAY_TICK;
+            self.step();
+        }
+        self.finish();
+    }
+
+    fn step(&mut self) {
+        self.apply_inputs();
+        self.game.update(REPLAY_TICK);
+        self.tick += 1;
+    }
+
+    /// Play the events of the last tick, which ended the game before it was played
+    /// (e.g. a hard drop of the AI).
+    fn finish(&mut self) {
+        if self.is_finished() {
+            self.apply_inputs();
+        }
+    }
+
+    /// Play the events of the current tick.
+    fn apply_inputs(&mut self) {
+        while let Some(input) = self.replay.inputs.get(self.next_input) {
+            if input.tick > self.tick {
+                break;
+            }
+            input.event.apply(&mut self.game);
+            self.next_input += 1;
+        }
+    }
+}
+
+impl GraphicsLoop for ReplayPlayback {
+    fn handle_event(&mut self, _event: &Event) -> bool {
+        true
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        self.game.paint(painter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::Weights;
+
+    /// A short sprint game, with some moves and drops.
+    fn record_game() -> GameRecorder {
+        let replay = GameReplay::new(GameMode::Sprint, 1, Randomizer::Bag, 42);
+        let mut recorder = GameRecorder::new(replay, PieceSet::standard());
+        let actions = [
+            Action::Left,
+            Action::RotateCw,
+            Action::HardDrop,
+            Action::Right,
+            Action::Hold,
+            Action::SoftDrop,
+        ];
+        for (i, action) in actions.iter().cycle().take(60).enumerate() {
+            recorder.action(*action, true);
+            // uneven frames: the ticks don't depend on them
+            recorder.update(0.01 + (i % 3) as f64 * 0.007);
+            recorder.action(*action, false);
+            recorder.update(0.02);
+            if i == 30 {
+                let handling = Handling {
+                    das: 0.1,
+                    ..Handling::default()
+                };
+                recorder.set_handling(&handling, true);
+            }
+        }
+        recorder
+    }
+
+    #[test]
+    fn text_roundtrip_is_lossless() {
+        let replay = record_game().replay().clone();
+        assert!(replay.inputs.len() > 100);
+        assert_eq!(Ok(replay.clone()), GameReplay::from_text(&replay.to_text()));
+        assert!(GameReplay::from_text("tetrust-replay 1\nticks 5\n3 +jump").is_err());
+        assert!(GameReplay::from_text("tetrust-replay 1\nticks 5\n7 +left").is_err());
+        assert!(GameReplay::from_text("replay 1").is_err());
+        assert!(GameReplay::from_text("tetrust-replay 1\nmode PUZZLE\nticks 5").is_err());
+    }
+
+    #[test]
+    fn playback_gives_the_same_game_and_can_go_back() {
+        let recorder = record_game();
+        let replay = recorder.replay().clone();
+        let mut playback = ReplayPlayback::new(replay.clone(), PieceSet::standard());
+        playback.set_speed(4.0);
+        while !playback.is_finished() {
+            playback.update(0.1);
+        }
+        let (recorded, played) = (recorder.game(), playback.game());
+        assert!(recorded.pieces_placed() > 5);
+        assert_eq!(recorded.board(), played.board());
+        assert_eq!(recorded.score(), played.score());
+        assert_eq!(recorded.time(), played.time());
+
+        // scrubbing back, then forward again
+        let middle = replay.ticks / 2;
+        playback.seek(middle);
+        let board = playback.game().board().clone();
+        playback.seek(replay.ticks);
+        playback.seek(middle);
+        assert_eq!(&board, playback.game().board());
+        assert_eq!(middle, playback.tick());
+    }
+
+    #[test]
+    fn garbage_ai_and_puzzles_are_replayed() {
+        let puzzle = PuzzleSet::builtin().puzzles[0].clone();
+        let mut recorder = GameRecorder::new(GameReplay::for_puzzle(&puzzle), PieceSet::standard());
+        let mut ai = AiPlayer::new(Weights::default());
+        for i in 0..120 {
+            if i == 10 {
+                recorder.receive_garbage(2, 3);
+            }
+            recorder.play_ai(&mut ai, 0.05);
+            recorder.update(REPLAY_TICK);
+        }
+        let replay = recorder.replay().clone();
+        let has = |f: fn(&ReplayEvent) -> bool| replay.inputs.iter().any(|i| f(&i.event));
+        assert!(has(|e| matches!(e, ReplayEvent::Garbage { .. })));
+        assert!(has(|e| matches!(e, ReplayEvent::Ai(_))));
+
+        let replay = GameReplay::from_text(&replay.to_text()).unwrap();
+        assert_eq!(Some(&puzzle), replay.puzzle.as_ref());
+        let mut playback = ReplayPlayback::new(replay, PieceSet::standard());
+        playback.seek(recorder.tick());
+        assert_eq!(recorder.game().board(), playback.game().board());
+        assert_eq!(recorder.game().outcome(), playback.game().outcome());
+        assert!(recorder.game().pieces_placed() > 0);
+    }
+}
@@ -0,0 +1,313 @@
+//! Scoring, as in the Tetris guideline: line clears, T-spins, combos, back-to-back and perfect clears,
+//! plus the level progression.
+//! - see: https://tetris.wiki/Scoring
+
+use crate::board::*;
+use crate::tetrimino::*;
+
+/// Lines to clear for advancing to the next level.
+pub const LINES_PER_LEVEL: u32 = 10;
+
+/// The kind of T-spin done by a piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What happened when a piece was locked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineClear {
+    pub lines: u32,
+    pub tspin: TSpin,
+    pub perfect_clear: bool,
+}
+
+impl LineClear {
+    /// Tetrises and T-spins which clear lines are "difficult", and can be chained back-to-back.
+    #[inline]
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.lines > 0 && self.tspin != TSpin::None)
+    }
+
+    /// Text describing the clear, e.g. "T-SPIN DOUBLE" (empty if there's nothing to show).
+    pub fn name(&self) -> String {
+        let tspin = match self.tspin {
+            TSpin::None => "",
+            TSpin::Mini => "MINI T-SPIN",
+            TSpin::Full => "T-SPIN",
+        };
+        let lines = match self.lines {
+            0 => "",
+            1 => "SINGLE",
+            2 => "DOUBLE",
+            3 => "TRIPLE",
+            4 => "TETRIS",
+            _ => "CLEAR",
+        };
+        let mut name = [tspin, lines].join(" ").trim().to_string();
+        if self.perfect_clear {
+            name.push_str("\nPERFECT CLEAR");
+        }
+        name
+    }
+
+    /// Base points (to be multiplied by the level).
+    fn base_points(&self) -> u64 {
+        let idx = self.lines.min(4) as usize;
+        match self.tspin {
+            TSpin::None => [0, 100, 300, 500, 800][idx],
+            TSpin::Mini => [100, 200, 400, 400, 400][idx],
+            TSpin::Full => [400, 800, 1200, 1600, 1600][idx],
+        }
+    }
+
+    /// Perfect clear bonus (to be multiplied by the level).
+    fn perfect_clear_points(&self, back_to_back: bool) -> u64 {
+        match (self.perfect_clear, self.lines) {
+            (false, _) | (true, 0) => 0,
+            (true, 1) => 800,
+            (true, 2) => 1200,
+            (true, 3) => 1800,
+            (true, _) if back_to_back => 3200,
+            (true, _) => 2000,
+        }
+    }
+}
+
+/// Is a rotation's wall kick (as returned by [`Tetrimino::rotated_srs`]) the "TST kick"? That's the last
+/// kick of the quarter turns; the 180° rotations don't have one, their last kick is a plain shift.
+pub fn is_tst_kick(rotation: Rotation, kick: usize) -> bool {
+    rotation != Rotation::Half && kick == 4
+}
+
+/// Detect T-spins with the 3-corner rule: the piece is a T, its last move was a rotation, and at least
+/// 3 of the 4 cells diagonal to its center are occupied (the walls and the floor count as occupied).
+/// It's a mini T-spin if only one of the 2 corners the T is pointing to is occupied - unless the rotation
+/// used the TST kick (see [`is_tst_kick`]), which always makes a full T-spin.
+pub fn detect_tspin(
+    board: &Board,
+    tetr: &Tetrimino,
+    last_move_rotation: bool,
+    tst_kick: bool,
+) -> TSpin {
+    if tetr.name() != 'T' || tetr.len() != 4 || !last_move_rotation {
+        return TSpin::None;
+    }
+    // the center is the cell which touches all the others
+    let cells: Vec<(i32, i32)> = tetr.cells().collect();
+    let Some(&(cx, cy)) = cells.iter().find(|&&(x, y)| {
+        cells
+            .iter()
+            .filter(|&&(ox, oy)| (ox - x).abs() + (oy - y).abs() == 1)
+            .count()
+            == 3
+    }) else {
+        return TSpin::None;
+    };
+
+    let occupied = |dx: i32, dy: i32| !board.is_free(cx + dx, cy + dy);
+    // corners, in clockwise order starting from the top left; the T points up in rotation state 0
+    let corners = [
+        occupied(-1, -1),
+        occupied(1, -1),
+        occupied(1, 1),
+        occupied(-1, 1),
+    ];
+    if corners.iter().filter(|&&c| c).count() < 3 {
+        return TSpin::None;
+    }
+    let front = tetr.rotation() as usize;
+    let front_count = (corners[front] as u32) + (corners[(front + 1) % 4] as u32);
+    if front_count == 2 || tst_kick {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
+
+/// Score, level and lines of a game.
+#[derive(Clone, Debug)]
+pub struct Scoring {
+    score: u64,
+    start_level: u32,
+    level: u32,
+    lines: u32,
+    combo: i32,
+    back_to_back: bool,
+}
+
+impl Scoring {
+    pub fn new(start_level: u32) -> Self {
+        let start_level = start_level.max(1);
+        Scoring {
+            score: 0,
+            start_level,
+            level: start_level,
+            lines: 0,
+            combo: -1,
+            back_to_back: false,
+        }
+    }
+
+    #[inline]
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    #[inline]
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    #[inline]
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// The number of consecutive clears, minus one (-1 = no combo in progress).
+    #[inline]
+    pub fn combo(&self) -> i32 {
+        self.combo
+    }
+
+    /// True if the last clear was difficult, so the next difficult one gets the back-to-back bonus.
+    #[inline]
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// Points for soft dropping (1 per row).
+    #[inline]
+    pub fn soft_drop(&mut self, rows: i32) {
+        self.score += rows.max(0) as u64;
+    }
+
+    /// Points for hard dropping (2 per row).
+    #[inline]
+    pub fn hard_drop(&mut self, rows: i32) {
+        self.score += 2 * rows.max(0) as u64;
+    }
+
+    /// Score a locked piece, and advance the level. Returns the points awarded.
+    pub fn piece_locked(&mut self, clear: &LineClear) -> u64 {
+        let level = self.level as u64;
+        let mut points = clear.base_points() * level;
+
+        if clear.lines > 0 {
+            let b2b = clear.is_difficult() && self.back_to_back;
+            if b2b {
+                points = points * 3 / 2;
+            }
+            points += clear.perfect_clear_points(b2b) * level;
+            self.combo += 1;
+            points += 50 * (self.combo as u64) * level;
+            // easy clears break the back-to-back chain (but T-spins without lines don't)
+            self.back_to_back = clear.is_difficult();
+        } else {
+            self.combo = -1;
+        }
+
+        self.lines += clear.lines;
+        self.level = self.start_level.max(1 + self.lines / LINES_PER_LEVEL);
+        self.score += points;
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u32, tspin: TSpin) -> LineClear {
+        LineClear {
+            lines,
+            tspin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn line_clears_are_multiplied_by_the_level() {
+        let mut scoring = Scoring::new(3);
+        assert_eq!(300, scoring.piece_locked(&clear(1, TSpin::None)));
+        scoring.piece_locked(&clear(0, TSpin::None));
+        assert_eq!(2400, scoring.piece_locked(&clear(4, TSpin::None)));
+    }
+
+    #[test]
+    fn combos_and_back_to_back() {
+        let mut scoring = Scoring::new(1);
+        assert_eq!(800, scoring.piece_locked(&clear(4, TSpin::None)));
+        // back-to-back tetris (x1.5) + combo 1
+        assert_eq!(1200 + 50, scoring.piece_locked(&clear(4, TSpin::None)));
+        // a single breaks the back-to-back chain, but continues the combo
+        assert_eq!(100 + 100, scoring.piece_locked(&clear(1, TSpin::None)));
+        assert!(!scoring.back_to_back());
+        assert_eq!(1200 + 150, scoring.piece_locked(&clear(2, TSpin::Full)));
+        assert_eq!(3, scoring.combo());
+        scoring.piece_locked(&clear(0, TSpin::None));
+        assert_eq!(-1, scoring.combo());
+    }
+
+    #[test]
+    fn level_advances_every_10_lines() {
+        let mut scoring = Scoring::new(1);
+        for _ in 0..5 {
+            scoring.piece_locked(&clear(2, TSpin::None));
+        }
+        assert_eq!(2, scoring.level());
+        // starting at a higher level, the level only goes up when it catches up
+        let mut scoring = Scoring::new(5);
+        for _ in 0..10 {
+            scoring.piece_locked(&clear(4, TSpin::None));
+        }
+        assert_eq!(40, scoring.lines());
+        assert_eq!(5, scoring.level());
+    }
+
+    #[test]
+    fn three_corner_tspin_detection() {
+        // a T-slot at the bottom of the board:   #.##   (the T rotates into it, pointing down)
+        //                                        ...#
+        //                                        #.##
+        let mut board = Board::new(4, 4, 0);
+        for (x, y) in [(0, 1), (2, 1), (3, 1), (3, 2), (0, 3), (2, 3), (3, 3)] {
+            board.set_cell(x, y, Some(0));
+        }
+        let mut tetr = Tetrimino::from_index(2);
+        tetr.rotate_180();
+        tetr.slide(0, 1);
+        assert!(board.fits(&tetr));
+        assert_eq!(TSpin::Full, detect_tspin(&board, &tetr, true, false));
+        assert_eq!(TSpin::None, detect_tspin(&board, &tetr, false, false));
+        // only one corner in front of the T (pointing down): mini, unless the TST kick was used
+        board.set_cell(2, 3, None);
+        assert_eq!(TSpin::Mini, detect_tspin(&board, &tetr, true, false));
+        assert_eq!(TSpin::Full, detect_tspin(&board, &tetr, true, true));
+    }
+
+    #[test]
+    fn half_rotations_have_no_tst_kick() {
+        // the T (pointing left) turns to point right, with the last 180° kick (2 cells up):   .#T#.
+        // only one corner in front of it, so it's a mini T-spin                               ..TT.
+        //                                                                                     .#T..
+        //                                                                                     .tt#.
+        //                                                                                     ..t..
+        let mut board = Board::new(5, 5, 0);
+        for (x, y) in [(1, 0), (3, 0), (1, 2), (3, 3)] {
+            board.set_cell(x, y, Some(0));
+        }
+        let mut tetr = Tetrimino::from_index(2);
+        tetr.rotate_ccw();
+        tetr.slide(1, 2);
+        assert!(board.fits(&tetr));
+        let (rotated, kick) = tetr.rotated_srs(Rotation::Half, |t| board.fits(t)).unwrap();
+        assert_eq!(4, kick);
+        assert!(!is_tst_kick(Rotation::Half, kick));
+        assert!(is_tst_kick(Rotation::Clockwise, kick));
+        let tst_kick = is_tst_kick(Rotation::Half, kick);
+        assert_eq!(TSpin::Mini, detect_tspin(&board, &rotated, true, tst_kick));
+    }
+}
@@ -0,0 +1,49 @@
+//! User-adjustable game settings.
+
+use crate::effects::EffectSettings;
+use crate::generator::Randomizer;
+use crate::handling::Handling;
+use crate::mode::GameMode;
+use crate::tetrimino::BUILTIN_PIECE_SETS;
+
+/// The game settings, edited from the settings screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Master volume, 0..1.
+    pub master_volume: f64,
+    /// The name used for the high scores.
+    pub player_name: String,
+    /// The last mode played.
+    pub mode: GameMode,
+    /// The level the game starts at.
+    pub start_level: u32,
+    /// Show where the falling piece will land.
+    pub ghost_piece: bool,
+    /// Allow 180 degree rotations.
+    pub allow_180: bool,
+    /// How the pieces are chosen.
+    pub randomizer: Randomizer,
+    /// The name of the piece set: a built-in one, or a file in the `pieces` directory of the configuration.
+    pub piece_set: String,
+    /// DAS, ARR etc.
+    pub handling: Handling,
+    /// The visual effects shown.
+    pub effects: EffectSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 0.8,
+            player_name: String::new(),
+            mode: GameMode::Marathon,
+            start_level: 1,
+            ghost_piece: true,
+            allow_180: false,
+            randomizer: Randomizer::Bag,
+            piece_set: BUILTIN_PIECE_SETS[0].0.to_string(),
+            handling: Handling::default(),
+            effects: EffectSettings::default(),
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//! Playing in a text terminal (e.g. over SSH): a character screen painted with ANSI escape
+//! sequences, and the decoding of the keys read from a terminal in raw mode.
+//! - see: https://en.wikipedia.org/wiki/ANSI_escape_code
+
+use crate::game::*;
+use crate::keymap::*;
+use crate::mode::*;
+use crate::render::*;
+use game_framework::*;
+
+/// Each board cell is 2 characters wide, so that the cells look square.
+pub const TERM_CELL_WIDTH: i32 = 2;
+
+/// The terminal size needed by [`paint_game`], for a standard board.
+pub const TERM_GAME_SIZE: (i32, i32) = (50, 24);
+
+/// A character on the terminal screen, with its 256 colors palette colors (`None` = default).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TermCell {
+    pub ch: char,
+    pub fg: Option<u8>,
+    pub bg: Option<u8>,
+}
+
+impl Default for TermCell {
+    fn default() -> Self {
+        TermCell {
+            ch: ' ',
+            fg: None,
+            bg: None,
+        }
+    }
+}
+
+/// The closest color of the xterm 256 colors palette (from its 6x6x6 color cube).
+pub fn ansi_color(color: RGB) -> u8 {
+    let level = |c: u8| ((c as u32 * 5 + 127) / 255) as u8;
+    16 + 36 * level(color.r) + 6 * level(color.g) + level(color.b)
+}
+
+/// A character screen. Only the characters which changed since the previous frame are sent
+/// to the terminal, which keeps the output small.
+pub struct TermScreen {
+    width: i32,
+    height: i32,
+    cells: Vec<TermCell>,
+    shown: Option<Vec<TermCell>>,
+}
+
+impl TermScreen {
+    pub fn new(width: i32, height: i32) -> Self {
+        TermScreen {
+            width,
+            height,
+            cells: vec![TermCell::default(); (width * height) as usize],
+            shown: None,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// The character at (x, y).
+    pub fn cell(&self, x: i32, y: i32) -> TermCell {
+        self.cells[(y * self.width + x) as usize]
+    }
+
+    /// Clear the screen (to be painted again).
+    pub fn clear(&mut self) {
+        self.cells.fill(TermCell::default());
+    }
+
+    /// Paint everything on the next [`render`](Self::render), e.g. after the terminal was resized.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// Set a character (outside of the screen it's ignored).
+    pub fn put(&mut self, x: i32, y: i32, ch: char, fg: Option<u8>, bg: Option<u8>) {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            self.cells[(y * self.width + x) as usize] = TermCell { ch, fg, bg };
+        }
+    }
+
+    /// Write some text (one line per `'\n'`), on the default background.
+    pub fn text(&mut self, x: i32, y: i32, text: &str, fg: RGB) {
+        for (row, line) in text.lines().enumerate() {
+            for (col, ch) in line.chars().enumerate() {
+                self.put(
+                    x + col as i32,
+                    y + row as i32,
+                    ch,
+                    Some(ansi_color(fg)),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// Paint a board cell (2 characters) at a character position.
+    pub fn block(&mut self, x: i32, y: i32, text: [char; 2], fg: Option<RGB>, bg: Option<RGB>) {
+        for (i, ch) in text.into_iter().enumerate() {
+            self.put(x + i as i32, y, ch, fg.map(ansi_color), bg.map(ansi_color));
+        }
+    }
+
+    /// The escape sequences updating the terminal to the current content of the screen.
+    pub fn render(&mut self) -> String {
+        let mut out = String::new();
+        let full = self.shown.is_none();
+        if full {
+            out.push_str("\x1b[0m\x1b[2J");
+        }
+        // the position and colors of the terminal cursor, as far as we know
+        let mut cursor = None;
+        let mut colors = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = (y * self.width + x) as usize;
+                let cell = self.cells[idx];
+                if self.shown.as_ref().is_some_and(|shown| shown[idx] == cell) {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    out.push_str(&format!("\x1b[{};{}H", y + 1, x + 1));
+                }
+                if colors != Some((cell.fg, cell.bg)) {
+                    out.push_str("\x1b[0");
+                    if let Some(fg) = cell.fg {
+                        out.push_str(&format!(";38;5;{fg}"));
+                    }
+                    if let Some(bg) = cell.bg {
+                        out.push_str(&format!(";48;5;{bg}"));
+                    }
+                    out.push('m');
+                    colors = Some((cell.fg, cell.bg));
+                }
+                out.push(cell.ch);
+                cursor = Some((x + 1, y));
+            }
+        }
+        if !out.is_empty() {
+            out.push_str("\x1b[0m");
+        }
+        self.shown = Some(self.cells.clone());
+        out
+    }
+}
+
+/// Paint a piece in its spawn orientation, centered in a 4 cells wide box.
+fn paint_preview(
+    screen: &mut TermScreen,
+    game: &Game,
+    piece_idx: usize,
+    x: i32,
+    y: i32,
+    color: RGB,
+) {
+    let tetr = game.piece_set().piece(piece_idx);
+    let min_x = tetr.cells().map(|(x, _)| x).min().unwrap();
+    let max_x = tetr.cells().map(|(x, _)| x).max().unwrap();
+    let min_y = tetr.cells().map(|(_, y)| y).min().unwrap();
+    let ofs_x = x + (4 - (max_x - min_x + 1)) * TERM_CELL_WIDTH / 2;
+    for (tx, ty) in tetr.cells() {
+        let cx = ofs_x + (tx - min_x) * TERM_CELL_WIDTH;
+        screen.block(cx, y + ty - min_y, [' ', ' '], None, Some(color));
+    }
+}
+
+/// Paint a game like [`Game::paint`] does, but with characters: the hold slot and the stats on
+/// the left, the board in the middle and the next queue on the right. (x, y) is the top left
+/// corner; the game takes [`TERM_GAME_SIZE`] characters.
+pub fn paint_game(screen: &mut TermScreen, game: &Game, x: i32, y: i32) {
+    let board = game.board();
+    let board_x = x + 12;
+    let (w, h) = (board.width() * TERM_CELL_WIDTH, board.visible_height());
+    let cell_pos = |bx: i32, by: i32| {
+        (
+            board_x + 1 + bx * TERM_CELL_WIDTH,
+            y + 1 + by - board.hidden_rows(),
+        )
+    };
+
+    // the frame, the background and the locked cells
+    for row in 0..h {
+        screen.put(board_x, y + 1 + row, '│', Some(ansi_color(GREY)), None);
+        screen.put(
+            board_x + w + 1,
+            y + 1 + row,
+            '│',
+            Some(ansi_color(GREY)),
+            None,
+        );
+    }
+    for col in 0..w + 2 {
+        let ch = match col {
+            0 => '└',
+            c if c == w + 1 => '┘',
+            _ => '─',
+        };
+        screen.put(board_x + col, y + 1 + h, ch, Some(ansi_color(GREY)), None);
+    }
+    for by in board.hidden_rows()..board.height() {
+        for bx in 0..board.width() {
+            let (cx, cy) = cell_pos(bx, by);
+            match board.cell(bx, by) {
+                Some(color_idx) => screen.block(
+                    cx,
+                    cy,
+                    [' ', ' '],
+                    None,
+                    Some(piece_color(color_idx as usize)),
+                ),
+                None => screen.block(cx, cy, [' ', '.'], Some(GREY), Some(BLACK)),
+            }
+        }
+    }
+
+    // the ghost and the falling piece
+    if let Some(tetr) = game.piece() {
+        let color = piece_color(tetr.color_idx());
+        if game.show_ghost() {
+            if let Some(ghost) = game.ghost_piece() {
+                for (bx, by) in ghost.cells().filter(|&(_, by)| by >= board.hidden_rows()) {
+                    let (cx, cy) = cell_pos(bx, by);
+                    screen.block(cx, cy, ['[', ']'], Some(shade(color, 60)), Some(BLACK));
+                }
+            }
+        }
+        for (bx, by) in tetr.cells().filter(|&(_, by)| by >= board.hidden_rows()) {
+            let (cx, cy) = cell_pos(bx, by);
+            screen.block(cx, cy, [' ', ' '], None, Some(color));
+        }
+    }
+
+    // the incoming garbage, as a bar growing from the bottom, on the left of the board
+    let garbage = (game.pending_garbage() as i32).min(h);
+    for row in 0..garbage {
+        screen.put(
+            board_x - 1,
+            y + h - row,
+            '█',
+            Some(ansi_color(LIGHT_RED)),
+            None,
+        );
+    }
+
+    // the side panels: hold + stats on the left, next queue on the right
+    screen.text(x, y, game.mode().name(), WHITE);
+    screen.text(x, y + 2, "HOLD", LIGHT_GREY);
+    if let Some(held) = game.held_piece() {
+        let color = if game.can_hold() {
+            piece_color(held)
+        } else {
+            GREY
+        };
+        paint_preview(screen, game, held, x + 1, y + 3, color);
+    }
+    let lines = match game.lines_left() {
+        Some(left) => format!("LEFT\n{left}"),
+        None => format!("LINES\n{}", game.lines()),
+    };
+    let time = format_time(game.time_left().unwrap_or(game.time()));
+    let stats = format!(
+        "SCORE\n{}\nLEVEL\n{}\n{lines}\nTIME\n{time}",
+        game.score(),
+        game.level(),
+    );
+    screen.text(x, y + 7, &stats, LIGHT_GREY);
+    if let Some(text) = game.clear_text() {
+        screen.text(x, y + 16, &text, LIGHT_YELLOW);
+    }
+
+    let right_x = board_x + w + 4;
+    screen.text(right_x, y + 2, "NEXT", LIGHT_GREY);
+    for (i, next) in game.next_pieces().enumerate() {
+        paint_preview(
+            screen,
+            game,
+            next,
+            right_x,
+            y + 3 + i as i32 * 3,
+            piece_color(next),
+        );
+    }
+}
+
+/// What was typed on the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TermInput {
+    Key(Keycode),
+    /// Ctrl+C (in raw mode, it doesn't stop the program).
+    Interrupt,
+}
+
+/// Decode the bytes read from a terminal in raw mode. Returns the inputs, and the number of
+/// bytes used: an incomplete escape sequence at the end is left for the next read, unless
+/// `complete` is true (then a lone escape is the Escape key).
+pub fn decode_input(bytes: &[u8], complete: bool) -> (Vec<TermInput>, usize) {
+    let mut inputs = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest[0] == 0x1b {
+            // "ESC [ X" or "ESC O X" for the arrows
+            match rest.get(1) {
+                Some(b'[' | b'O') => {
+                    let Some(end) = rest[2..].iter().position(|b| (0x40..=0x7e).contains(b)) else {
+                        if complete {
+                            pos = bytes.len();
+                        }
+                        break;
+                    };
+                    let key = match rest[2 + end] {
+                        b'A' => Some(Keycode::Up),
+                        b'B' => Some(Keycode::Down),
+                        b'C' => Some(Keycode::Right),
+                        b'D' => Some(Keycode::Left),
+                        b'H' => Some(Keycode::Home),
+                        b'F' => Some(Keycode::End),
+                        _ => None,
+                    };
+                    inputs.extend(key.map(TermInput::Key));
+                    pos += 3 + end;
+                }
+                Some(_) => {
+                    inputs.push(TermInput::Key(Keycode::Escape));
+                    pos += 1;
+                }
+                None if complete => {
+                    inputs.push(TermInput::Key(Keycode::Escape));
+                    pos += 1;
+                }
+                None => break,
+            }
+            continue;
+        }
+        let key = match rest[0] {
+            0x03 => {
+                inputs.push(TermInput::Interrupt);
+                pos += 1;
+                continue;
+            }
+            b'\r' | b'\n' => Some(Keycode::Return),
+            b'\t' => Some(Keycode::Tab),
+            0x7f | 0x08 => Some(Keycode::Backspace),
+            b' ' => Some(Keycode::Space),
+            b'/' => Some(Keycode::Slash),
+            c @ (b'0'..=b'9') => Some(Keycode(c as i32)),
+            c @ (b'a'..=b'z') => Some(Keycode(c as i32)),
+            c @ (b'A'..=b'Z') => Some(Keycode(c.to_ascii_lowercase() as i32)),
+            _ => None,
+        };
+        inputs.extend(key.map(TermInput::Key));
+        pos += 1;
+    }
+    (inputs, pos)
+}
+
+/// Seconds a soft drop lasts after its key: terminals only tell when a key is typed (and
+/// repeated), not when it's released.
+pub const TERM_SOFT_DROP_DURATION: f64 = 0.15;
+
+/// Feeds the keys typed on a terminal to a game. The terminal repeats the held keys by itself,
+/// so each key is a press immediately followed by a release, except for the soft drop which
+/// lasts until the key stops repeating.
+#[derive(Clone, Debug, Default)]
+pub struct TermKeys {
+    soft_drop_timer: f64,
+}
+
+impl TermKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A key was typed.
+    pub fn key(&mut self, game: &mut Game, key: Keycode) {
+        let key_event = |down: bool| {
+            let (keycode, keymod, repeat) = (Some(key), Mod::NOMOD, false);
+            if down {
+                Event::KeyDown {
+                    keycode,
+                    keymod,
+                    repeat,
+                }
+            } else {
+                Event::KeyUp {
+                    keycode,
+                    keymod,
+                    repeat,
+                }
+            }
+        };
+        game.handle_event(&key_event(true));
+        if game.key_map().action(key) == Some(Action::SoftDrop) {
+            self.soft_drop_timer = TERM_SOFT_DROP_DURATION;
+        } else {
+            game.handle_event(&key_event(false));
+        }
+    }
+
+    /// Ends the soft drop once its key isn't repeated anymore.
+    pub fn update(&mut self, game: &mut Game, elapsed_time: f64) {
+        if self.soft_drop_timer > 0.0 {
+            self.soft_drop_timer -= elapsed_time;
+            if self.soft_drop_timer <= 0.0 {
+                game.set_soft_drop(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::*;
+
+    #[test]
+    fn escape_sequences_are_decoded() {
+        let keys = |bytes: &[u8], complete: bool| {
+            let (inputs, used) = decode_input(bytes, complete);
+            (
+                inputs
+                    .into_iter()
+                    .map(|input| match input {
+                        TermInput::Key(key) => key,
+                        TermInput::Interrupt => Keycode::Delete,
+                    })
+                    .collect::<Vec<_>>(),
+                used,
+            )
+        };
+        assert_eq!(
+            (vec![Keycode::Left, Keycode::Z, Keycode::Space], 5),
+            keys(b"\x1b[DZ ", false)
+        );
+        assert_eq!(
+            (vec![Keycode::Up, Keycode::Delete], 4),
+            keys(b"\x1bOA\x03", false)
+        );
+        // the end of the sequence is still on its way
+        assert_eq!((vec![Keycode::Return], 1), keys(b"\r\x1b[", false));
+        assert_eq!((vec![Keycode::Escape], 1), keys(b"\x1b", true));
+        assert_eq!((vec![], 0), keys(b"\x1b", false));
+    }
+
+    #[test]
+    fn colors_and_rendering() {
+        assert_eq!(16, ansi_color(BLACK));
+        assert_eq!(231, ansi_color(WHITE));
+        let mut screen = TermScreen::new(4, 2);
+        screen.text(1, 1, "AB", WHITE);
+        let first = screen.render();
+        assert!(first.starts_with("\x1b[0m\x1b[2J"));
+        assert!(first.contains(" \x1b[0;38;5;231mAB\x1b[0m "));
+        // nothing changed: nothing to send
+        assert_eq!("", screen.render());
+        screen.put(3, 0, '#', None, Some(16));
+        assert_eq!("\x1b[1;4H\x1b[0;48;5;16m#\x1b[0m", screen.render());
+    }
+
+    #[test]
+    fn the_soft_drop_ends_when_the_key_is_not_repeated() {
+        let generator = SequenceGenerator::new(&[1]);
+        let mut game = Game::new(GameMode::Marathon, 1, Box::new(generator));
+        let mut keys = TermKeys::new();
+        let mut screen = TermScreen::new(TERM_GAME_SIZE.0, TERM_GAME_SIZE.1);
+        keys.key(&mut game, Keycode::Down);
+        keys.update(&mut game, 0.1);
+        game.update(0.1);
+        let y = game.piece().unwrap().y(0);
+        keys.update(&mut game, 0.1);
+        game.update(0.5);
+        // gravity only: at most one row in half a second at level 1
+        assert!(game.piece().unwrap().y(0) <= y + 1);
+        paint_game(&mut screen, &game, 0, 0);
+        assert_eq!(
+            "HOLD",
+            (0..4).map(|x| screen.cell(x, 2).ch).collect::<String>()
+        );
+    }
+}
@@ -1,58 +1,446 @@
 //! Tetrimino data and functionalities.
+//!
+//! The shapes of the pieces come from piece sets, which are plain text: see [`PieceSet::from_text`].
+//! The standard set has the 7 tetriminoes, but other sets can have pieces of any size (e.g. pentominoes).
 
-// constant with the data for generating all 7 tetriminoes
-const TETRIMINOES: &'static [Tetrimino] = &[
-    internal_build_tetrimino('O', 0b_0000_0110_0110),
-    internal_build_tetrimino('I', 0b_0000_1111_0000),
-    internal_build_tetrimino('T', 0b_0100_1110_0000),
-    internal_build_tetrimino('S', 0b_0110_1100_0000),
-    internal_build_tetrimino('Z', 0b_1100_0110_0000),
-    internal_build_tetrimino('J', 0b_1000_1110_0000),
-    internal_build_tetrimino('L', 0b_0010_1110_0000),
+use std::sync::{Arc, OnceLock};
+
+/// How many different tetriminoes there are (in the standard set).
+pub const TETRIMINO_COUNT: usize = 7;
+/// The largest pieces, in cells.
+pub const MAX_PIECE_CELLS: usize = 16;
+/// The largest pieces, in rows or columns of their rotation box.
+pub const MAX_PIECE_SIZE: i32 = 8;
+/// The largest piece sets.
+pub const MAX_PIECE_SET_LEN: usize = 64;
+
+const PIECES_VERSION: u32 = 1;
+
+/// The piece sets which are part of the game: (name, data).
+pub const BUILTIN_PIECE_SETS: &[(&str, &str)] = &[
+    ("STANDARD", include_str!("../pieces/standard.txt")),
+    ("TRIOMINOES", include_str!("../pieces/triominoes.txt")),
+    ("PENTOMINOES", include_str!("../pieces/pentominoes.txt")),
+];
+
+// SRS wall kicks, for the J, L, S, T, Z pieces, indexed by the initial rotation state (0, R, 2, L).
+// The offsets are (x, y) with y going *down*, so they are the official tables with y negated.
+// - see: https://tetris.wiki/Super_Rotation_System
+const KICKS_JLSTZ_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // 0 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // R -> 2
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // 2 -> L
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // L -> 0
+];
+const KICKS_JLSTZ_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // 0 -> L
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // R -> 0
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // 2 -> R
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // L -> 2
+];
+
+// SRS wall kicks for the I piece
+const KICKS_I_CW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)], // 0 -> R
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)], // R -> 2
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)], // 2 -> L
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)], // L -> 0
+];
+const KICKS_I_CCW: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)], // 0 -> L
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)], // R -> 0
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)], // 2 -> R
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)], // L -> 2
 ];
 
-/// Data structure for a tetrimino.
+// 180 degree rotations are not part of SRS; these kicks are the ones used by TETR.IO (all pieces)
+const KICKS_180: [[(i32, i32); 6]; 4] = [
+    [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)], // 0 -> 2
+    [(0, 0), (1, 0), (1, -2), (1, -1), (0, -2), (0, -1)],  // R -> L
+    [(0, 0), (0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)],    // 2 -> 0
+    [(0, 0), (-1, 0), (-1, -2), (-1, -1), (0, -2), (0, -1)], // L -> R
+];
+
+/// The ways a tetrimino can be rotated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Clockwise,
+    CounterClockwise,
+    Half,
+}
+
+/// The wall kicks tried by the rotations of a piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kicks {
+    /// The SRS kicks of the J, L, S, T, Z pieces.
+    Srs,
+    /// The SRS kicks of the I piece.
+    SrsI,
+    /// No kicks at all (e.g. for the O piece, which doesn't really rotate).
+    None,
+}
+
+/// The shape of a piece: its cells in the 4 rotation states, relative to the top left corner of its rotation box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceShape {
+    name: char,
+    kicks: Kicks,
+    cells: [Vec<(i8, i8)>; 4],
+    /// The size of the (square) box the piece rotates in.
+    box_size: i32,
+}
+
+impl PieceShape {
+    /// Build a shape from its cells in the spawn orientation, and its rotation center (in cell coordinates,
+    /// doubled, so that it can also be a corner between the cells: e.g. the center of a 4x4 box is (3, 3)).
+    pub fn new(
+        name: char,
+        kicks: Kicks,
+        cells: &[(i32, i32)],
+        center2: (i32, i32),
+    ) -> Result<Self, String> {
+        let (cx2, cy2) = center2;
+        if cells.is_empty() || cells.len() > MAX_PIECE_CELLS {
+            return Err(format!(
+                "piece {name}: 1 to {MAX_PIECE_CELLS} cells are needed"
+            ));
+        }
+        if cx2 >= MAX_PIECE_SIZE || cy2 >= MAX_PIECE_SIZE {
+            return Err(format!(
+                "piece {name}: the piece must rotate in a box of at most {MAX_PIECE_SIZE}x{MAX_PIECE_SIZE} cells"
+            ));
+        }
+        if cx2 < 0 || cy2 < 0 || (cx2 + cy2) % 2 != 0 {
+            return Err(format!(
+                "piece {name}: the rotation center must be on a cell, or on a corner between cells"
+            ));
+        }
+        if !is_connected(cells) {
+            return Err(format!("piece {name}: the cells are not connected"));
+        }
+        // rotate clockwise around the center (y goes down)
+        let rotate = |&(x, y): &(i32, i32)| ((cx2 + cy2) / 2 - y, x + (cy2 - cx2) / 2);
+        let mut rotations: [Vec<(i32, i32)>; 4] = Default::default();
+        rotations[0] = cells.to_vec();
+        for r in 1..4 {
+            rotations[r] = rotations[r - 1].iter().map(rotate).collect();
+        }
+        let box_size = cx2.max(cy2) + 1;
+        let in_box =
+            |&(x, y): &(i32, i32)| (0..box_size).contains(&x) && (0..box_size).contains(&y);
+        if box_size > MAX_PIECE_SIZE || !rotations.iter().flatten().all(in_box) {
+            return Err(format!(
+                "piece {name}: the piece must rotate in a box of at most {MAX_PIECE_SIZE}x{MAX_PIECE_SIZE} cells"
+            ));
+        }
+        Ok(PieceShape {
+            name,
+            kicks,
+            cells: rotations.map(|r| r.iter().map(|&(x, y)| (x as i8, y as i8)).collect()),
+            box_size,
+        })
+    }
+
+    #[inline]
+    pub fn name(&self) -> char {
+        self.name
+    }
+
+    #[inline]
+    pub fn kicks(&self) -> Kicks {
+        self.kicks
+    }
+
+    /// The number of cells.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells[0].is_empty()
+    }
+
+    /// The size of the square box the piece rotates in.
+    #[inline]
+    pub fn box_size(&self) -> i32 {
+        self.box_size
+    }
+}
+
+/// True if all the cells are connected through their sides.
+fn is_connected(cells: &[(i32, i32)]) -> bool {
+    let mut reached = vec![cells[0]];
+    let mut next = 0;
+    while next < reached.len() {
+        let (x, y) = reached[next];
+        for &cell in cells {
+            if (cell.0 - x).abs() + (cell.1 - y).abs() == 1 && !reached.contains(&cell) {
+                reached.push(cell);
+            }
+        }
+        next += 1;
+    }
+    cells.iter().all(|cell| reached.contains(cell))
+}
+
+/// The pieces which can be played in a game. The pieces are identified by their index in the set.
+///
+/// The sets are plain text: a header, the name of the set, then the pieces (the other lines starting with `#` are
+/// comments). Each piece starts with its name
+/// (one character), then the kicks (`srs`, `srs_i` or `none`; `srs` if omitted), the rotation center (in cell
+/// coordinates, from the top left cell) and the rows of cells in the spawn orientation, `#` = filled:
+/// ```text
+/// pieces 1
+/// name STANDARD
+///
+/// piece T
+/// center 1 1
+/// .#.
+/// ###
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceSet {
+    name: String,
+    shapes: Vec<Arc<PieceShape>>,
+}
+
+impl PieceSet {
+    /// The 7 tetriminoes.
+    pub fn standard() -> Arc<PieceSet> {
+        static STANDARD: OnceLock<Arc<PieceSet>> = OnceLock::new();
+        STANDARD
+            .get_or_init(|| Arc::new(PieceSet::from_text(BUILTIN_PIECE_SETS[0].1).unwrap()))
+            .clone()
+    }
+
+    /// A piece set which is part of the game, by name.
+    pub fn builtin(name: &str) -> Option<PieceSet> {
+        let (_, text) = BUILTIN_PIECE_SETS.iter().find(|(n, _)| *n == name)?;
+        PieceSet::from_text(text).ok()
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let is_row = |line: &str| line.chars().all(|c| c == '#' || c == '.');
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && (!line.starts_with('#') || is_row(line)));
+        match lines.next() {
+            Some((_, line)) if line == format!("pieces {PIECES_VERSION}") => {}
+            _ => return Err("not a piece set (or unsupported version)".to_string()),
+        }
+
+        let mut set = PieceSet {
+            name: String::new(),
+            shapes: vec![],
+        };
+        // the piece being parsed
+        let mut piece: Option<PieceDefinition> = None;
+        for (idx, line) in lines {
+            let err = |msg: &str| format!("pieces line {}: {msg}", idx + 1);
+            let (keyword, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            if is_row(line) {
+                let Some(piece) = piece.as_mut() else {
+                    return Err(err("cells outside of a piece"));
+                };
+                piece.rows.push(line);
+                continue;
+            }
+            match keyword {
+                "name" => set.name = value.to_string(),
+                "piece" => {
+                    if let Some(piece) = piece.take() {
+                        set.shapes.push(Arc::new(piece.build()?));
+                    }
+                    let mut chars = value.chars();
+                    let (Some(name), None) = (chars.next(), chars.next()) else {
+                        return Err(err("the name of a piece is one character"));
+                    };
+                    piece = Some(PieceDefinition {
+                        name,
+                        kicks: Kicks::Srs,
+                        center2: None,
+                        rows: vec![],
+                    });
+                }
+                "kicks" | "center" => {
+                    let Some(piece) = piece.as_mut() else {
+                        return Err(err("outside of a piece"));
+                    };
+                    if keyword == "kicks" {
+                        piece.kicks = match value {
+                            "srs" => Kicks::Srs,
+                            "srs_i" => Kicks::SrsI,
+                            "none" => Kicks::None,
+                            _ => return Err(err("unknown kicks")),
+                        };
+                    } else {
+                        // doubled, to support the corners between the cells
+                        // (and checked before the conversion, which would clamp the huge values)
+                        let max_center2 = (2 * MAX_PIECE_SIZE) as f64;
+                        let coords: Option<Vec<i32>> = value
+                            .split_whitespace()
+                            .map(|t| t.parse::<f64>().ok().map(|v| v * 2.0))
+                            .map(|v| {
+                                v.filter(|v| v.fract() == 0.0 && (0.0..=max_center2).contains(v))
+                                    .map(|v| v as i32)
+                            })
+                            .collect();
+                        let Some([cx2, cy2]) = coords.as_deref() else {
+                            return Err(err("invalid center"));
+                        };
+                        piece.center2 = Some((*cx2, *cy2));
+                    }
+                }
+                _ => return Err(err("unknown keyword")),
+            }
+        }
+        if let Some(piece) = piece {
+            set.shapes.push(Arc::new(piece.build()?));
+        }
+        if set.shapes.is_empty() || set.shapes.len() > MAX_PIECE_SET_LEN {
+            return Err(format!("a set needs 1 to {MAX_PIECE_SET_LEN} pieces"));
+        }
+        Ok(set)
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of pieces.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    #[inline]
+    pub fn shape(&self, idx: usize) -> &PieceShape {
+        &self.shapes[idx]
+    }
+
+    /// A piece of the set, in its spawn orientation, with its top row at 0.
+    pub fn piece(&self, idx: usize) -> Tetrimino {
+        assert!(idx < self.shapes.len(), "Invalid piece index: {idx}");
+        let shape = self.shapes[idx].clone();
+        let top = shape.cells[0].iter().map(|&(_, y)| y).min().unwrap_or(0);
+        Tetrimino {
+            shape,
+            x_ofs: 0,
+            y_ofs: -top as i16,
+            rotation: 0,
+            color_idx: idx as u8,
+        }
+    }
+}
+
+/// A piece of a set, as written in the text.
+struct PieceDefinition<'a> {
+    name: char,
+    kicks: Kicks,
+    center2: Option<(i32, i32)>,
+    rows: Vec<&'a str>,
+}
+
+impl PieceDefinition<'_> {
+    fn build(self) -> Result<PieceShape, String> {
+        let name = self.name;
+        let center2 = self
+            .center2
+            .ok_or_else(|| format!("piece {name}: the rotation center is missing"))?;
+        let cells: Vec<(i32, i32)> = self
+            .rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == '#')
+                    .map(move |(x, _)| (x as i32, y as i32))
+            })
+            .collect();
+        PieceShape::new(name, self.kicks, &cells, center2)
+    }
+}
+
+/// Data structure for a tetrimino (or any other piece): a shape, with its position and rotation.
 #[derive(Clone, Debug)]
 pub struct Tetrimino {
-    x: [i8; 16],
-    y: [i8; 16],
+    shape: Arc<PieceShape>,
     x_ofs: i16,
     y_ofs: i16,
     rotation: u8,
     color_idx: u8,
-    name: u8,
 }
 
 impl Tetrimino {
+    /// A tetrimino of the standard set.
     pub fn from_index(idx: usize) -> Self {
-        assert!(idx < TETRIMINOES.len(), "Invalid Tetromino index: {idx}");
-        let mut tetr = TETRIMINOES[idx].clone();
-        tetr.color_idx = idx as u8;
-        tetr
+        assert!(idx < TETRIMINO_COUNT, "Invalid Tetromino index: {idx}");
+        PieceSet::standard().piece(idx)
     }
 
     #[inline]
     pub fn name(&self) -> char {
-        self.name as char
+        self.shape.name
     }
 
+    /// The index of the piece in its set (which is also its color index).
     #[inline]
     pub fn color_idx(&self) -> usize {
         self.color_idx as usize
     }
 
+    #[inline]
+    pub fn shape(&self) -> &PieceShape {
+        &self.shape
+    }
+
+    /// The number of cells.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shape.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.shape.is_empty()
+    }
+
+    /// The rotation state: 0 = spawn, 1 = R (clockwise), 2 = upside down, 3 = L (counter-clockwise).
+    #[inline]
+    pub fn rotation(&self) -> u8 {
+        self.rotation
+    }
+
     #[inline]
     pub fn x(&self, idx: usize) -> i32 {
-        assert!(idx < 4);
-        let cidx = idx + ((self.rotation as usize) << 2);
-        (self.x[cidx] as i32) + (self.x_ofs as i32)
+        (self.shape.cells[self.rotation as usize][idx].0 as i32) + (self.x_ofs as i32)
     }
 
     #[inline]
     pub fn y(&self, idx: usize) -> i32 {
-        assert!(idx < 4);
-        let cidx = idx + ((self.rotation as usize) << 2);
-        (self.y[cidx] as i32) + (self.y_ofs as i32)
+        (self.shape.cells[self.rotation as usize][idx].1 as i32) + (self.y_ofs as i32)
+    }
+
+    /// The positions of all the cells.
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..self.len()).map(|i| (self.x(i), self.y(i)))
     }
 
     #[inline]
@@ -70,50 +458,236 @@ impl Tetrimino {
     pub fn rotate_ccw(&mut self) {
         self.rotation = (self.rotation + 3) & 0x03;
     }
+
+    #[inline]
+    pub fn rotate_180(&mut self) {
+        self.rotation = (self.rotation + 2) & 0x03;
+    }
+
+    /// Rotate using the Super Rotation System: after the basic rotation, the wall kicks are tried in order,
+    /// until one where `fits` returns true. Returns the rotated (and kicked) tetrimino, plus the index of the
+    /// kick which was used (0 = no kick), or `None` if the rotation is not possible.
+    pub fn rotated_srs(
+        &self,
+        rotation: Rotation,
+        fits: impl Fn(&Tetrimino) -> bool,
+    ) -> Option<(Tetrimino, usize)> {
+        let mut rotated = self.clone();
+        match rotation {
+            Rotation::Clockwise => rotated.rotate_cw(),
+            Rotation::CounterClockwise => rotated.rotate_ccw(),
+            Rotation::Half => rotated.rotate_180(),
+        }
+        self.kicks(rotation)
+            .iter()
+            .enumerate()
+            .find_map(|(idx, &(dx, dy))| {
+                let mut kicked = rotated.clone();
+                kicked.slide(dx, dy);
+                fits(&kicked).then_some((kicked, idx))
+            })
+    }
+
+    /// The wall kicks to try, for rotating from the current rotation state.
+    fn kicks(&self, rotation: Rotation) -> &'static [(i32, i32)] {
+        let state = self.rotation as usize;
+        match (self.shape.kicks, rotation) {
+            (Kicks::None, _) => &[(0, 0)],
+            (_, Rotation::Half) => &KICKS_180[state],
+            (Kicks::SrsI, Rotation::Clockwise) => &KICKS_I_CW[state],
+            (Kicks::SrsI, Rotation::CounterClockwise) => &KICKS_I_CCW[state],
+            (_, Rotation::Clockwise) => &KICKS_JLSTZ_CW[state],
+            (_, Rotation::CounterClockwise) => &KICKS_JLSTZ_CCW[state],
+        }
+    }
 }
 
-/// Internal function, for building a tetrimino.
-/// It *must* be `const fn`, so that it can be called at compile time
-/// (which is why we cannot use `for` loops).
-const fn internal_build_tetrimino(name: char, bits: u32) -> Tetrimino {
-    // check for pieces which are 3 squares wide
-    // (this info is needed for computing the rotations)
-    let is_3_width = (name != 'O') && (name != 'I');
-
-    let mut tetr = Tetrimino {
-        x: [0; 16],
-        y: [0; 16],
-        x_ofs: 0,
-        y_ofs: (is_3_width as i16) - 1,
-        rotation: 0,
-        color_idx: 0,
-        name: name as u8,
-    };
-
-    // compute positions for the initial rotation
-    let mut mask: u32 = 0b_1000_0000_0000;
-    let mut idx: usize = 0;
-    let mut xy: i8 = 0;
-    // `for` cannot be used in const fn
-    // - see: https://github.com/rust-lang/rust/issues/87575
-    while mask > 0 {
-        if (bits & mask) != 0 {
-            tetr.x[idx] = xy & 0x03;
-            tetr.y[idx] = (xy >> 2) & 0x03;
-            idx += 1;
-        }
-        mask >>= 1;
-        xy += 1;
-    }
-
-    // compute all rotations
-    let rotation_transformer = 3 - (is_3_width as i8);
-    idx = 4;
-    while idx < 16 {
-        tetr.x[idx] = rotation_transformer - tetr.y[idx - 4];
-        tetr.y[idx] = tetr.x[idx - 4];
-        idx += 1;
-    }
-
-    tetr
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(tetr: &Tetrimino) -> Vec<(i32, i32)> {
+        let mut cells: Vec<(i32, i32)> = tetr.cells().collect();
+        cells.sort();
+        cells
+    }
+
+    /// The 4 rotations of a piece side by side, clockwise from the spawn orientation.
+    fn rotations_picture(mut tetr: Tetrimino) -> String {
+        let mut rotations = vec![];
+        for _ in 0..4 {
+            rotations.push(cells(&tetr));
+            tetr.rotate_cw();
+        }
+        // the same frame for all the rotations
+        let (xs, ys): (Vec<i32>, Vec<i32>) = rotations.concat().into_iter().unzip();
+        let (min_x, max_x) = (*xs.iter().min().unwrap(), *xs.iter().max().unwrap());
+        let (min_y, max_y) = (*ys.iter().min().unwrap(), *ys.iter().max().unwrap());
+        let mut lines = vec![];
+        for y in min_y..=max_y {
+            let row: Vec<String> = rotations
+                .iter()
+                .map(|cells| {
+                    (min_x..=max_x)
+                        .map(|x| if cells.contains(&(x, y)) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+            lines.push(row.join(" "));
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn standard_rotations_match_srs() {
+        let expected = [
+            "\
+## ## ## ##
+## ## ## ##",
+            "\
+.... ..#. .... .#..
+#### ..#. .... .#..
+.... ..#. #### .#..
+.... ..#. .... .#..",
+            "\
+.#. .#. ... .#.
+### .## ### ##.
+... .#. .#. .#.",
+            "\
+.## .#. ... #..
+##. .## .## ##.
+... ..# ##. .#.",
+            "\
+##. ..# ... .#.
+.## .## ##. ##.
+... .#. .## #..",
+            "\
+#.. .## ... .#.
+### .#. ### .#.
+... .#. ..# ##.",
+            "\
+..# .#. ... ##.
+### .#. ### .#.
+... .## #.. .#.",
+        ];
+        for (idx, picture) in expected.iter().enumerate() {
+            let tetr = Tetrimino::from_index(idx);
+            assert_eq!(*picture, rotations_picture(tetr), "piece {idx}");
+        }
+    }
+
+    #[test]
+    fn rotations_return_to_the_original_shape() {
+        for (name, _) in BUILTIN_PIECE_SETS {
+            let set = PieceSet::builtin(name).unwrap();
+            for idx in 0..set.len() {
+                let original = set.piece(idx);
+                let turned = |turn: &dyn Fn(&mut Tetrimino), times: usize| {
+                    let mut tetr = original.clone();
+                    for _ in 0..times {
+                        turn(&mut tetr);
+                    }
+                    tetr
+                };
+                let same = |tetr: &Tetrimino| {
+                    cells(tetr) == cells(&original) && tetr.rotation() == original.rotation()
+                };
+                let context = format!("{name} piece {idx}");
+                assert!(same(&turned(&Tetrimino::rotate_cw, 4)), "{context}");
+                assert!(same(&turned(&Tetrimino::rotate_ccw, 4)), "{context}");
+                assert!(same(&turned(&Tetrimino::rotate_180, 2)), "{context}");
+                let mut tetr = turned(&Tetrimino::rotate_cw, 1);
+                tetr.rotate_ccw();
+                assert!(same(&tetr), "{context}");
+                let twice = turned(&Tetrimino::rotate_cw, 2);
+                assert_eq!(
+                    cells(&twice),
+                    cells(&turned(&Tetrimino::rotate_180, 1)),
+                    "{context}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn srs_rotation_without_obstacles_uses_no_kick() {
+        let tetr = Tetrimino::from_index(2); // T
+        let (rotated, kick) = tetr.rotated_srs(Rotation::Clockwise, |_| true).unwrap();
+        assert_eq!(0, kick);
+        assert_eq!(1, rotated.rotation());
+        // T pointing right: the stem is at (2, 1)
+        assert_eq!(vec![(1, 0), (1, 1), (1, 2), (2, 1)], cells(&rotated));
+    }
+
+    #[test]
+    fn srs_tries_the_kicks_in_order() {
+        // J: reject the basic rotation, so the first kick (0 -> R = one cell left) is used
+        let tetr = Tetrimino::from_index(5);
+        let (rotated, kick) = tetr
+            .rotated_srs(Rotation::Clockwise, |t| cells(t)[0].0 < 1)
+            .unwrap();
+        assert_eq!(1, kick);
+        assert_eq!(0, cells(&rotated)[0].0);
+    }
+
+    #[test]
+    fn srs_fails_when_no_kick_fits() {
+        let tetr = Tetrimino::from_index(1);
+        assert!(tetr.rotated_srs(Rotation::Clockwise, |_| false).is_none());
+        assert!(tetr.rotated_srs(Rotation::Half, |_| false).is_none());
+    }
+
+    #[test]
+    fn standard_set_matches_the_guideline() {
+        let set = PieceSet::standard();
+        assert_eq!(TETRIMINO_COUNT, set.len());
+        let names: String = (0..set.len()).map(|i| set.shape(i).name()).collect();
+        assert_eq!("OITSZJL", names);
+        // I: horizontal on row 0, then vertical in the third column of its box
+        let mut tetr = Tetrimino::from_index(1);
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (3, 0)], cells(&tetr));
+        tetr.rotate_cw();
+        assert_eq!(vec![(2, -1), (2, 0), (2, 1), (2, 2)], cells(&tetr));
+        // O: doesn't move when rotated
+        let mut tetr = Tetrimino::from_index(0);
+        let before = cells(&tetr);
+        tetr.rotate_ccw();
+        assert_eq!(before, cells(&tetr));
+    }
+
+    #[test]
+    fn every_builtin_set_is_valid() {
+        for (name, text) in BUILTIN_PIECE_SETS {
+            let set = PieceSet::from_text(text).unwrap();
+            assert_eq!(*name, set.name());
+        }
+        let pentominoes = PieceSet::builtin("PENTOMINOES").unwrap();
+        assert_eq!(18, pentominoes.len());
+        assert!((0..18).all(|i| pentominoes.piece(i).len() == 5));
+    }
+
+    #[test]
+    fn invalid_piece_sets_are_rejected() {
+        let piece = |body: &str| PieceSet::from_text(&format!("pieces 1\npiece A\n{body}"));
+        assert!(piece("center 1 1\n##.\n..#")
+            .unwrap_err()
+            .contains("not connected"));
+        assert!(piece("center 0.5 1\n##")
+            .unwrap_err()
+            .contains("rotation center"));
+        assert!(piece("##").unwrap_err().contains("center is missing"));
+        assert!(piece("center 1e10 1e10\n#")
+            .unwrap_err()
+            .contains("invalid center"));
+        assert!(piece("center 7.5 7.5\n#").unwrap_err().contains("box"));
+        assert!(piece("center 0 0\nkicks big\n#")
+            .unwrap_err()
+            .contains("line 4"));
+        assert!(PieceSet::from_text("pieces 1\n").is_err());
+        // a domino, rotating around the corner between its cells
+        let set = piece("center 0.5 0.5\n##").unwrap();
+        let mut domino = set.piece(0);
+        domino.rotate_cw();
+        assert_eq!(vec![(1, 0), (1, 1)], cells(&domino));
+    }
 }
@@ -0,0 +1,327 @@
+//! Genetic tuning of the AI [`Weights`]: a population of weight vectors plays simulated games, and the best ones
+//! are crossed over and mutated to make the next generation.
+//!
+//! The games of a generation all use the same piece sequences (from seeds derived from the generation number),
+//! so the fitness of the individuals can be compared fairly.
+//!
+//! Checkpoint files are plain text: a header, the last generation, then the best weight vectors, one per line,
+//! with their fitness first:
+//! ```text
+//! checkpoint 1
+//! generation 12
+//! w 1234 -0.51 0 -0.36 -0.18 0 0 0 0.76
+//! ```
+
+use crate::ai::*;
+use crate::evaluation::*;
+use crate::generator::*;
+use crate::tetrimino::TETRIMINO_COUNT;
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// The parameters of the training.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingConfig {
+    /// Individuals in each generation.
+    pub population: usize,
+    /// Games played by each individual, in each generation.
+    pub games: u32,
+    /// Pieces after which a game is stopped (the good players could go on forever).
+    pub max_pieces: u32,
+    /// Part of the population replaced by offspring in each generation.
+    pub offspring_ratio: f64,
+    /// Probability of mutating a newborn.
+    pub mutation_rate: f64,
+    /// Maximum change of a mutated weight.
+    pub mutation_step: f64,
+    /// Base seed for all the randomness.
+    pub seed: u64,
+    /// How many threads evaluate the population (0 = one per CPU).
+    pub threads: usize,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> Self {
+        TrainingConfig {
+            population: 100,
+            games: 5,
+            max_pieces: 500,
+            offspring_ratio: 0.3,
+            mutation_rate: 0.05,
+            mutation_step: 0.2,
+            seed: 1,
+            threads: 0,
+        }
+    }
+}
+
+/// A weight vector, with its fitness (total lines cleared in the games of the last evaluation).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Individual {
+    pub weights: [f64; FEATURE_COUNT],
+    pub fitness: f64,
+}
+
+/// The state of the training.
+pub struct Trainer {
+    config: TrainingConfig,
+    rng: fastrand::Rng,
+    generation: u32,
+    population: Vec<Individual>,
+}
+
+impl Trainer {
+    /// Start with a random population, plus the given individuals (e.g. from a checkpoint).
+    pub fn new(config: TrainingConfig, initial: &[Individual]) -> Self {
+        let rng = fastrand::Rng::with_seed(config.seed);
+        let mut population: Vec<Individual> =
+            initial.iter().take(config.population).cloned().collect();
+        while population.len() < config.population.max(2) {
+            let mut weights = [0.0; FEATURE_COUNT];
+            weights.iter_mut().for_each(|w| *w = rng.f64() * 2.0 - 1.0);
+            population.push(Individual {
+                weights: normalized(weights),
+                fitness: 0.0,
+            });
+        }
+        Trainer {
+            config,
+            rng,
+            generation: 0,
+            population,
+        }
+    }
+
+    /// The number of generations completed so far.
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Continue the numbering of the generations (e.g. after loading a checkpoint).
+    #[inline]
+    pub fn set_generation(&mut self, generation: u32) {
+        self.generation = generation;
+    }
+
+    /// The population, best first (after an evaluation).
+    #[inline]
+    pub fn population(&self) -> &[Individual] {
+        &self.population
+    }
+
+    /// Play the games of the generation with all the individuals (in parallel), then sort them by fitness.
+    pub fn evaluate(&mut self) {
+        let seeds: Vec<u64> = (0..self.config.games as u64)
+            .map(|game| self.config.seed ^ ((self.generation as u64) << 32) ^ game)
+            .collect();
+        let threads = match self.config.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let chunk_size = self.population.len().div_ceil(threads);
+        let max_pieces = self.config.max_pieces;
+        std::thread::scope(|scope| {
+            for chunk in self.population.chunks_mut(chunk_size) {
+                let seeds = &seeds;
+                scope.spawn(move || {
+                    for individual in chunk {
+                        individual.fitness = fitness(&individual.weights, seeds, max_pieces);
+                    }
+                });
+            }
+        });
+        self.population
+            .sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+    }
+
+    /// Make the next generation, after the evaluation: the worst individuals are replaced by the offspring of the
+    /// best ones. The parents are chosen by tournament, the child is their average weighted by fitness, and
+    /// sometimes a random weight is mutated.
+    pub fn next_generation(&mut self) {
+        let population = self.population.len();
+        let offspring_count =
+            ((population as f64 * self.config.offspring_ratio) as usize).clamp(1, population - 1);
+        let tournament = (population / 10).max(2);
+        let mut offspring = Vec::with_capacity(offspring_count);
+        for _ in 0..offspring_count {
+            // the population is sorted, so the best of the tournament is the one with the smallest index
+            let mut picks: Vec<usize> = (0..tournament)
+                .map(|_| self.rng.usize(0..population))
+                .collect();
+            picks.sort();
+            let (a, b) = (&self.population[picks[0]], &self.population[picks[1]]);
+            let total = (a.fitness + b.fitness).max(1e-9);
+            let mut weights = [0.0; FEATURE_COUNT];
+            for (i, w) in weights.iter_mut().enumerate() {
+                *w = if a.fitness + b.fitness > 0.0 {
+                    (a.weights[i] * a.fitness + b.weights[i] * b.fitness) / total
+                } else {
+                    (a.weights[i] + b.weights[i]) / 2.0
+                };
+            }
+            if self.rng.f64() < self.config.mutation_rate {
+                let i = self.rng.usize(0..FEATURE_COUNT);
+                weights[i] += (self.rng.f64() * 2.0 - 1.0) * self.config.mutation_step;
+            }
+            offspring.push(Individual {
+                weights: normalized(weights),
+                fitness: 0.0,
+            });
+        }
+        self.population.truncate(population - offspring_count);
+        self.population.extend(offspring);
+        self.generation += 1;
+    }
+}
+
+/// Total lines cleared by the weights in the games with the given seeds.
+pub fn fitness(weights: &[f64; FEATURE_COUNT], seeds: &[u64], max_pieces: u32) -> f64 {
+    let weights = Weights::from_array(*weights);
+    seeds
+        .iter()
+        .map(|&seed| {
+            let generator = BagGenerator::new(TETRIMINO_COUNT, seed);
+            simulate_game(&weights, Box::new(generator), max_pieces).lines as f64
+        })
+        .sum()
+}
+
+/// Scale the vector to length 1 (only the direction of the weights matters for choosing the moves).
+fn normalized(mut weights: [f64; FEATURE_COUNT]) -> [f64; FEATURE_COUNT] {
+    let length = weights.iter().map(|w| w * w).sum::<f64>().sqrt();
+    if length > 0.0 {
+        weights.iter_mut().for_each(|w| *w /= length);
+    }
+    weights
+}
+
+/// The best individuals found so far, saved to a file after each generation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Checkpoint {
+    pub generation: u32,
+    /// Best first.
+    pub best: Vec<Individual>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        game_framework::storage::save_file(std::path::Path::new(path), &self.to_text())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "checkpoint {CHECKPOINT_VERSION}\ngeneration {}\n",
+            self.generation
+        );
+        for individual in self.best.iter() {
+            text.push_str(&format!("w {}", individual.fitness));
+            for w in individual.weights {
+                text.push_str(&format!(" {w}"));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        match lines.next() {
+            Some((_, line)) if line.trim() == format!("checkpoint {CHECKPOINT_VERSION}") => {}
+            _ => return Err("not a checkpoint file (or unsupported version)".to_string()),
+        }
+        let mut checkpoint = Checkpoint::default();
+        for (idx, line) in lines {
+            let err = || format!("checkpoint line {}: invalid data", idx + 1);
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("generation") => {
+                    checkpoint.generation =
+                        tokens.next().and_then(|t| t.parse().ok()).ok_or_else(err)?;
+                }
+                Some("w") => {
+                    let values: Vec<f64> = tokens
+                        .map(|t| t.parse::<f64>().ok().filter(|v| v.is_finite()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(err)?;
+                    let Some((&fitness, weights)) = values.split_first() else {
+                        return Err(err());
+                    };
+                    checkpoint.best.push(Individual {
+                        weights: weights.try_into().map_err(|_| err())?,
+                        fitness,
+                    });
+                }
+                _ => return Err(err()),
+            }
+        }
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> TrainingConfig {
+        TrainingConfig {
+            population: 6,
+            games: 1,
+            max_pieces: 30,
+            threads: 2,
+            ..TrainingConfig::default()
+        }
+    }
+
+    #[test]
+    fn training_is_deterministic() {
+        let mut first = Trainer::new(small_config(), &[]);
+        let mut second = Trainer::new(small_config(), &[]);
+        for _ in 0..2 {
+            first.evaluate();
+            second.evaluate();
+            assert_eq!(first.population(), second.population());
+            first.next_generation();
+            second.next_generation();
+        }
+        assert_eq!(2, first.generation());
+        assert_eq!(6, first.population().len());
+    }
+
+    #[test]
+    fn evaluation_sorts_by_fitness() {
+        let good = Individual {
+            weights: Weights::default().to_array(),
+            fitness: 0.0,
+        };
+        let mut trainer = Trainer::new(small_config(), &[good]);
+        trainer.evaluate();
+        let population = trainer.population();
+        assert!(population.windows(2).all(|w| w[0].fitness >= w[1].fitness));
+        assert!(population[0].fitness > 0.0);
+    }
+
+    #[test]
+    fn checkpoint_text_roundtrip() {
+        let checkpoint = Checkpoint {
+            generation: 12,
+            best: vec![Individual {
+                weights: normalized([1.0, -2.0, 0.5, 0.0, 3.0, -0.25, 0.1, 1e-3]),
+                fitness: 1234.0,
+            }],
+        };
+        assert_eq!(
+            checkpoint,
+            Checkpoint::from_text(&checkpoint.to_text()).unwrap()
+        );
+        assert!(Checkpoint::from_text("checkpoint 1\nw 1 2 3\n").is_err());
+    }
+}
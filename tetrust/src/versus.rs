@@ -0,0 +1,208 @@
+//! Versus games: two players, who attack each other with garbage rows.
+//! - see: https://tetris.wiki/Garbage
+
+use crate::game::*;
+use crate::generator::*;
+use crate::handling::*;
+use crate::keymap::*;
+use crate::mode::*;
+use crate::replay::*;
+use crate::scoring::*;
+use crate::tetrimino::PieceSet;
+use game_framework::*;
+
+/// Attack bonus for combos, indexed by the combo count (0 = the first clear of the chain).
+const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+/// Attack bonus for perfect clears.
+const PERFECT_CLEAR_ATTACK: u32 = 10;
+
+/// The number of garbage rows sent by a clear. `combo` is the combo count after the clear,
+/// `back_to_back` tells if the clear continued a back-to-back chain.
+pub fn attack_lines(clear: &LineClear, combo: i32, back_to_back: bool) -> u32 {
+    if clear.lines == 0 {
+        return 0;
+    }
+    let idx = clear.lines.min(4) as usize;
+    let mut attack = match clear.tspin {
+        TSpin::None => [0, 0, 1, 2, 4][idx],
+        TSpin::Mini => [0, 0, 1, 2, 2][idx],
+        TSpin::Full => [0, 2, 4, 6, 6][idx],
+    };
+    if back_to_back {
+        attack += 1;
+    }
+    if combo >= 0 {
+        attack += COMBO_ATTACK[(combo as usize).min(COMBO_ATTACK.len() - 1)];
+    }
+    if clear.perfect_clear {
+        attack += PERFECT_CLEAR_ATTACK;
+    }
+    attack
+}
+
+/// A versus game between two players, on the same computer. Both get the same pieces.
+/// Each game is recorded, with the garbage it received.
+pub struct Versus {
+    players: [GameRecorder; 2],
+    rng: fastrand::Rng,
+}
+
+impl Versus {
+    pub fn new(start_level: u32, randomizer: Randomizer, seed: u64) -> Self {
+        let new_player = |key_map: KeyMap| {
+            let replay = GameReplay::new(GameMode::Versus, start_level, randomizer, seed);
+            let mut recorder = GameRecorder::new(replay, PieceSet::standard());
+            recorder.game_mut().set_key_map(key_map);
+            recorder
+        };
+        Versus {
+            players: [
+                new_player(KeyMap::player_1()),
+                new_player(KeyMap::player_2()),
+            ],
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    #[inline]
+    pub fn player(&self, idx: usize) -> &Game {
+        self.players[idx].game()
+    }
+
+    /// The game of a player, for the settings which don't change how it's played (e.g. the view).
+    #[inline]
+    pub fn player_mut(&mut self, idx: usize) -> &mut Game {
+        self.players[idx].game_mut()
+    }
+
+    /// The replay of a player's game, so far.
+    #[inline]
+    pub fn replay(&self, idx: usize) -> &GameReplay {
+        self.players[idx].replay()
+    }
+
+    /// Change the settings which affect both games.
+    pub fn set_handling(&mut self, handling: &Handling, allow_180: bool) {
+        for player in self.players.iter_mut() {
+            player.set_handling(handling, allow_180);
+        }
+    }
+
+    /// Release the moves and the soft drops, e.g. after a pause.
+    pub fn release_keys(&mut self) {
+        for player in self.players.iter_mut() {
+            player.release_keys();
+        }
+    }
+
+    /// True when at least one of the players topped out.
+    pub fn is_over(&self) -> bool {
+        self.players.iter().any(|p| p.game().is_game_over())
+    }
+
+    /// The index of the winner, once the game is over (`None` for a draw).
+    pub fn winner(&self) -> Option<usize> {
+        match (
+            self.players[0].game().is_game_over(),
+            self.players[1].game().is_game_over(),
+        ) {
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Advance both games, and deliver the attacks.
+    pub fn update(&mut self, elapsed_time: f64) {
+        if self.is_over() {
+            return;
+        }
+        for player in self.players.iter_mut() {
+            player.update(elapsed_time);
+        }
+        for idx in 0..2 {
+            let attack = self.players[idx].game_mut().take_attack();
+            if attack > 0 {
+                let opponent = &mut self.players[1 - idx];
+                let hole = self.rng.i32(0..opponent.game().board().width());
+                opponent.receive_garbage(attack, hole);
+            }
+        }
+    }
+}
+
+impl GraphicsLoop for Versus {
+    fn handle_event(&mut self, event: &Event) -> bool {
+        // each game only reacts to its own keys
+        self.players.iter_mut().all(|p| p.handle_event(event))
+    }
+
+    fn update_state(&mut self, elapsed_time: f64) -> bool {
+        self.update(elapsed_time);
+        true
+    }
+
+    fn paint(&self, painter: &mut dyn Painter) {
+        for player in self.players.iter() {
+            player.game().paint(painter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrimino::Rotation;
+
+    fn clear(lines: u32, tspin: TSpin) -> LineClear {
+        LineClear {
+            lines,
+            tspin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn attack_table() {
+        assert_eq!(0, attack_lines(&clear(1, TSpin::None), 0, false));
+        assert_eq!(4, attack_lines(&clear(4, TSpin::None), 0, false));
+        assert_eq!(5, attack_lines(&clear(4, TSpin::None), 0, true));
+        assert_eq!(4, attack_lines(&clear(2, TSpin::Full), 0, false));
+        // a double, as the 5th clear in a row
+        assert_eq!(1 + 2, attack_lines(&clear(2, TSpin::None), 4, false));
+        assert_eq!(0, attack_lines(&clear(0, TSpin::Full), 0, true));
+    }
+
+    #[test]
+    fn attacks_become_garbage_for_the_opponent() {
+        // a game starting with an I piece
+        let mut versus = (0..)
+            .map(|seed| Versus::new(1, Randomizer::Bag, seed))
+            .find(|v| v.player(0).piece().unwrap().name() == 'I')
+            .unwrap();
+        let game = versus.player_mut(0);
+        let mut board = game.board().clone();
+        for y in board.height() - 4..board.height() {
+            for x in 1..board.width() {
+                board.set_cell(x, y, Some(0));
+            }
+        }
+        // (not a perfect clear)
+        board.set_cell(9, board.height() - 5, Some(0));
+        game.set_board(board);
+        // a vertical I in the hole: a tetris
+        game.rotate_piece(Rotation::Clockwise);
+        while game.move_piece(-1, 0) {}
+        game.hard_drop();
+        assert_eq!(4, game.lines());
+        // the garbage arrives on the next tick
+        versus.update(REPLAY_TICK);
+        versus.update(REPLAY_TICK);
+        assert_eq!(4, versus.player(1).pending_garbage());
+        assert!(!versus.is_over());
+        // ... and it's in the replay of the opponent
+        let received =
+            |input: &ReplayInput| matches!(input.event, ReplayEvent::Garbage { lines: 4, .. });
+        assert!(versus.replay(1).inputs.iter().any(received));
+    }
+}